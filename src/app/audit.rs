@@ -0,0 +1,228 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState as AuditState},
+};
+use tui_popup::Popup;
+
+use crate::app::{
+    CodeCache, SaveSnippet, View, history,
+    secrets::{self, Finding},
+    undo::Change,
+};
+
+/// warning shown when a snippet about to be saved contains possible secrets
+pub struct SecretPrompt {
+    desc_findings: Vec<Finding>,
+    code_findings: Vec<Finding>,
+}
+
+/// secrets found in a single snippet, used by the audit view
+pub struct AuditEntry {
+    idx: usize,
+    findings: Vec<Finding>,
+}
+
+/// all secret values in a snippet, used to only warn about newly added ones
+pub fn secret_values(snippet: &SaveSnippet) -> Vec<String> {
+    let desc = secrets::scan(&snippet.desc)
+        .into_iter()
+        .map(|f| snippet.desc[f.range].to_string());
+    let code = secrets::scan(&snippet.code)
+        .into_iter()
+        .map(|f| snippet.code[f.range].to_string());
+    desc.chain(code).collect()
+}
+
+impl CodeCache {
+    /// scans the open form and asks what to do with secrets that were not known before.
+    /// returns true if a prompt was opened.
+    pub(super) fn check_secrets(&mut self) -> bool {
+        let Some(form) = &self.form else {
+            return false;
+        };
+        let new_findings = |text: &str| -> Vec<Finding> {
+            secrets::scan(text)
+                .into_iter()
+                .filter(|f| {
+                    !self
+                        .known_secrets
+                        .contains(&text[f.range.clone()].to_string())
+                })
+                .collect()
+        };
+        let desc_findings = new_findings(&form.desc.text());
+        let code_findings = new_findings(&form.code.text());
+        if desc_findings.is_empty() && code_findings.is_empty() {
+            return false;
+        }
+        self.secret_prompt = Some(SecretPrompt {
+            desc_findings,
+            code_findings,
+        });
+        true
+    }
+
+    pub(super) fn handle_secret_prompt_key(&mut self, key: KeyCode) {
+        let Some(prompt) = self.secret_prompt.take() else {
+            return;
+        };
+        let Some(form) = &mut self.form else {
+            return;
+        };
+        match key {
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                let desc = form.desc.text();
+                form.desc
+                    .set_text(&secrets::redact(&desc, &prompt.desc_findings));
+                if !prompt.code_findings.is_empty() {
+                    let code = form.code.text();
+                    form.code.set_text(&if form.template {
+                        secrets::redact(&code, &prompt.code_findings)
+                    } else {
+                        secrets::redact_plain(&code, &prompt.code_findings)
+                    });
+                    form.template = true;
+                }
+                self.save_form();
+                self.notify("Replaced secrets with placeholders");
+            }
+            KeyCode::Char('k') | KeyCode::Char('K') => {
+                let desc = form.desc.text();
+                let code = form.code.text();
+                for (text, findings) in [
+                    (&desc, &prompt.desc_findings),
+                    (&code, &prompt.code_findings),
+                ] {
+                    self.known_secrets
+                        .extend(findings.iter().map(|f| text[f.range.clone()].to_string()));
+                }
+                self.save_form();
+            }
+            // back to the form to fix it by hand
+            KeyCode::Esc => {}
+            _ => self.secret_prompt = Some(prompt),
+        }
+    }
+
+    pub(super) fn draw_secret_prompt(&self, frame: &mut Frame) {
+        let Some(prompt) = &self.secret_prompt else {
+            return;
+        };
+        let mut findings = prompt.code_findings.clone();
+        findings.extend(prompt.desc_findings.iter().cloned());
+        let body = format!(
+            "This snippet looks like it contains secrets:\n{}\n\nr to replace them with placeholders, k to keep them, esc to go back",
+            secrets::describe(&findings)
+        );
+        frame.render_widget(
+            Popup::new(body)
+                .title("Possible secrets")
+                .style(Style::default().fg(Color::Rgb(251, 73, 52))),
+            frame.area(),
+        );
+    }
+
+    pub(super) fn open_audit(&mut self) {
+        self.audit = self
+            .save_snippets
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, snippet)| {
+                let mut findings = secrets::scan(&snippet.code);
+                findings.extend(secrets::scan(&snippet.desc));
+                (!findings.is_empty()).then_some(AuditEntry { idx, findings })
+            })
+            .collect();
+        self.audit_state = AuditState::default();
+        if !self.audit.is_empty() {
+            self.audit_state.select(Some(0));
+        }
+        self.view = View::Audit;
+    }
+
+    pub(super) fn handle_audit_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
+            KeyCode::Esc | KeyCode::Char('a') | KeyCode::Char('A') => self.view = View::Library,
+            KeyCode::Down => self.audit_state.select_next(),
+            KeyCode::Up => self.audit_state.select_previous(),
+            KeyCode::Enter => {
+                if let Some(entry) = self.audit_state.selected().and_then(|i| self.audit.get(i)) {
+                    self.select(entry.idx);
+                    self.view = View::Library;
+                }
+            }
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                let Some(idx) = self
+                    .audit_state
+                    .selected()
+                    .and_then(|i| self.audit.get(i))
+                    .map(|entry| entry.idx)
+                else {
+                    return;
+                };
+                if !self.read_only(idx)
+                    && let Some(before) = self.save_snippets.get(idx).cloned()
+                {
+                    let mut after = before.clone();
+                    let findings = secrets::scan(&after.code);
+                    if !findings.is_empty() {
+                        after.code = if after.template {
+                            secrets::redact(&after.code, &findings)
+                        } else {
+                            secrets::redact_plain(&after.code, &findings)
+                        };
+                        after.template = true;
+                    }
+                    after.desc = secrets::redact(&after.desc, &secrets::scan(&after.desc));
+                    history::revise(&before, &mut after);
+                    history::scrub(&mut after);
+                    self.change(Change::update(idx, before, after));
+                    let selected = self.audit_state.selected();
+                    self.open_audit();
+                    self.audit_state
+                        .select(selected.map(|i| i.min(self.audit.len().saturating_sub(1))));
+                    self.notify("Replaced secrets with placeholders");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn draw_audit(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .audit
+            .iter()
+            .map(|entry| {
+                let title = self
+                    .save_snippets
+                    .get(entry.idx)
+                    .map(|s| s.title.as_str())
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or("untitled");
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{title}: "), Style::default().bold()),
+                    Span::raw(secrets::describe(&entry.findings)),
+                ]))
+            })
+            .collect();
+        let block = Block::bordered()
+            .title(if self.audit.is_empty() {
+                "No secrets found"
+            } else {
+                "Snippets with possible secrets"
+            })
+            .title_alignment(Alignment::Center)
+            .border_style(Style::default().fg(Color::Rgb(124, 111, 100)));
+        let list = List::new(items)
+            .block(block)
+            .style(Style::default().fg(Color::Rgb(250, 189, 47)))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Rgb(254, 128, 25))
+                    .fg(Color::Rgb(28, 28, 32)),
+            );
+        frame.render_stateful_widget(list, area, &mut self.audit_state);
+    }
+}
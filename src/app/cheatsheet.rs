@@ -0,0 +1,334 @@
+use std::{path::Path, sync::LazyLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{
+    SaveSnippet,
+    form::parse_tags,
+    import::Candidate,
+    template::{self, Part},
+};
+
+/// `<name>` in pet and navi commands, pet also allows `<name=default>`
+static VARIABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<([A-Za-z_][A-Za-z0-9_-]*)(?:=([^<>]*))?>").expect("invalid variable regex")
+});
+
+/// `$ name: command` lines of navi suggesting values for a variable
+static SUGGESTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\$\s*([A-Za-z_][A-Za-z0-9_-]*)\s*:\s*(.*)$").expect("invalid suggestion regex")
+});
+
+/// a pet `snippet.toml`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PetFile {
+    #[serde(default)]
+    snippets: Vec<PetSnippet>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PetSnippet {
+    description: String,
+    command: String,
+    #[serde(default)]
+    tag: Vec<String>,
+    #[serde(default)]
+    output: String,
+}
+
+/// turns pet and navi variables into placeholders, a choice like `<name=|_a_||_b_|>` keeps the first.
+/// literal `{{` is escaped until `template::mark_template` decides.
+fn from_variables(command: &str) -> String {
+    VARIABLE
+        .replace_all(
+            &template::escape_braces(command),
+            |caps: &regex::Captures| {
+                let name = caps[1].replace('-', "_");
+                match caps.get(2).map(|m| m.as_str()) {
+                    Some(default) => {
+                        let default = default
+                            .strip_prefix("|_")
+                            .and_then(|choices| choices.split("_|").next())
+                            .unwrap_or(default);
+                        format!("{{{{{name}:{}}}}}", default.replace(['{', '}'], ""))
+                    }
+                    None => format!("{{{{{name}}}}}"),
+                }
+            },
+        )
+        .into_owned()
+}
+
+/// turns placeholders into variables, defaults only if the format can hold them
+fn to_variables(code: &str, defaults: bool) -> String {
+    template::parts(code)
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => text.to_string(),
+            Part::Escape => "{{".to_string(),
+            Part::Placeholder(p) => match p.default {
+                Some(default) if defaults => format!("<{}={default}>", p.name),
+                _ => format!("<{}>", p.name),
+            },
+        })
+        .collect()
+}
+
+/// snippets of a pet `snippet.toml`, descriptions become titles
+pub fn parse_pet(text: &str, path: &Path) -> Result<Vec<Candidate>, String> {
+    let pet: PetFile = toml::from_str(text)
+        .map_err(|err| format!("{} is not a pet snippet file: {err}", path.display()))?;
+    Ok(pet
+        .snippets
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let mut snippet = SaveSnippet::new(from_variables(entry.command.trim_end()), "sh");
+            snippet.title = entry.description;
+            snippet.tags = parse_tags(&entry.tag.join(","));
+            template::mark_template(&mut snippet);
+            Candidate::new(snippet, format!("{}: snippet {}", path.display(), i + 1))
+        })
+        .collect())
+}
+
+/// snippets as a pet `snippet.toml`
+pub fn export_pet(snippets: &[&SaveSnippet]) -> String {
+    let pet = PetFile {
+        snippets: snippets
+            .iter()
+            .map(|snippet| PetSnippet {
+                description: snippet.title.clone(),
+                command: to_variables(&template::template_code(snippet), true),
+                tag: snippet.tags.clone(),
+                output: String::new(),
+            })
+            .collect(),
+    };
+    toml::to_string(&pet).expect("failed to serialize pet snippets")
+}
+
+/// snippets of a navi `.cheat` file.
+/// the `#` line is the title, `;` comments and `$` suggestions go into the description
+/// and a suggestion that only echoes a value becomes the placeholder default.
+pub fn parse_navi(text: &str, path: &Path) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    // first snippet of the current `%` section
+    let mut section = 0;
+    let mut title = String::new();
+    let mut comments: Vec<String> = Vec::new();
+    // the snippet the command lines are added to, None after a blank line
+    let mut current: Option<usize> = None;
+    for (n, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(header) = trimmed.strip_prefix('%') {
+            tags = parse_tags(header);
+            section = candidates.len();
+            current = None;
+        } else if let Some(description) = trimmed.strip_prefix('#') {
+            title = description.trim().to_string();
+            current = None;
+        } else if let Some(comment) = trimmed.strip_prefix(';') {
+            comments.push(comment.trim().to_string());
+        } else if let Some(caps) = SUGGESTION.captures(trimmed) {
+            // suggestions belong to the cheats of the section they are in
+            for candidate in &mut candidates[section..] {
+                suggest(&mut candidate.snippet, &caps[1], &caps[2], trimmed);
+            }
+            current = None;
+        } else if trimmed.is_empty() || trimmed.starts_with('@') {
+            current = None;
+        } else if let Some(idx) = current {
+            let snippet = &mut candidates[idx].snippet;
+            snippet.code.push('\n');
+            snippet.code.push_str(&from_variables(line));
+        } else {
+            let mut snippet = SaveSnippet::new(from_variables(line), "sh");
+            snippet.title = if title.is_empty() {
+                trimmed.to_string()
+            } else {
+                std::mem::take(&mut title)
+            };
+            snippet.desc = std::mem::take(&mut comments).join("\n");
+            snippet.tags = tags.clone();
+            current = Some(candidates.len());
+            candidates.push(Candidate::new(
+                snippet,
+                format!("{}:{}", path.display(), n + 1),
+            ));
+        }
+    }
+    for candidate in &mut candidates {
+        template::mark_template(&mut candidate.snippet);
+    }
+    candidates
+}
+
+/// applies a `$ name: command` line to a snippet using the variable
+fn suggest(snippet: &mut SaveSnippet, name: &str, command: &str, line: &str) {
+    let name = name.replace('-', "_");
+    let plain = format!("{{{{{name}}}}}");
+    if !snippet.code.contains(&plain) {
+        return;
+    }
+    // `echo value` is a default, anything else is kept for the next export
+    let value = command.strip_prefix("echo ").map(str::trim);
+    match value {
+        Some(value) if !value.contains(['|', '$', '`', '{', '}', ';']) => {
+            let value = value.trim_matches(['"', '\'']);
+            snippet.code = snippet
+                .code
+                .replace(&plain, &format!("{{{{{name}:{value}}}}}"));
+        }
+        _ => {
+            if !snippet.desc.is_empty() {
+                snippet.desc.push('\n');
+            }
+            snippet.desc.push_str(line);
+        }
+    }
+}
+
+/// snippets as a navi `.cheat` file, with a `%` section per set of tags
+pub fn export_navi(snippets: &[&SaveSnippet]) -> String {
+    let mut sorted: Vec<&SaveSnippet> = snippets.to_vec();
+    sorted.sort_by_key(|snippet| snippet.tags.clone());
+    let mut text = String::new();
+    let mut section: Option<&Vec<String>> = None;
+    for snippet in sorted {
+        if section != Some(&snippet.tags) {
+            let tags = if snippet.tags.is_empty() {
+                "codecache".to_string()
+            } else {
+                snippet.tags.join(", ")
+            };
+            text.push_str(&format!("% {tags}\n\n"));
+            section = Some(&snippet.tags);
+        }
+        let (suggestions, comments): (Vec<&str>, Vec<&str>) = snippet
+            .desc
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .partition(|line| SUGGESTION.is_match(line.trim()));
+        for comment in comments {
+            text.push_str(&format!("; {comment}\n"));
+        }
+        text.push_str(&format!("# {}\n", snippet.title));
+        text.push_str(&to_variables(&template::template_code(snippet), false));
+        text.push('\n');
+        for suggestion in suggestions {
+            text.push_str(&format!("{}\n", suggestion.trim()));
+        }
+        // navi has no defaults, a suggestion with a single value comes closest
+        for p in template::placeholders(&template::template_code(snippet)) {
+            if let Some(default) = p.default {
+                text.push_str(&format!("$ {}: echo {default}\n", p.name));
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippets(candidates: Vec<Candidate>) -> Vec<SaveSnippet> {
+        candidates.into_iter().map(|c| c.snippet).collect()
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            from_variables("ssh <user=root>@<remote-host> -i <key=|_a_||_b_|>"),
+            "ssh {{user:root}}@{{remote_host}} -i {{key:a}}"
+        );
+        assert_eq!(
+            to_variables("ssh {{user:root}}@{{host}} \\{{x}}", true),
+            "ssh <user=root>@<host> {{x}}"
+        );
+        assert_eq!(to_variables("ssh {{user:root}}", false), "ssh <user>");
+    }
+
+    #[test]
+    fn parses_pet() {
+        let text = r#"
+[[snippets]]
+  description = "Connect"
+  command = "ssh <user=root>@<host>"
+  tag = ["net", "ssh"]
+
+[[snippets]]
+  description = "Format"
+  command = "docker ps --format '{{.Names}}'"
+"#;
+        let snippets = snippets(parse_pet(text, Path::new("snippet.toml")).unwrap());
+        assert_eq!(snippets[0].title, "Connect");
+        assert_eq!(snippets[0].code, "ssh {{user:root}}@{{host}}");
+        assert_eq!(snippets[0].tags, ["net", "ssh"]);
+        assert_eq!(snippets[0].lang, "sh");
+        assert!(snippets[0].template);
+        assert_eq!(snippets[1].code, "docker ps --format '{{.Names}}'");
+        assert!(!snippets[1].template);
+        assert!(parse_pet("snippets = 1", Path::new("snippet.toml")).is_err());
+    }
+
+    #[test]
+    fn parses_navi() {
+        let text = "% git, code\n\n; switches branches\n# Checkout\ngit checkout <branch>\n\n# Commit\ngit commit \\\n  -m <message>\n\n$ branch: git branch | cut -c 3-\n$ message: echo \"wip\"\n";
+        let snippets = snippets(parse_navi(text, Path::new("git.cheat")));
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].title, "Checkout");
+        assert_eq!(snippets[0].code, "git checkout {{branch}}");
+        assert_eq!(
+            snippets[0].desc,
+            "switches branches\n$ branch: git branch | cut -c 3-"
+        );
+        assert_eq!(snippets[0].tags, ["git", "code"]);
+        assert_eq!(snippets[1].code, "git commit \\\n  -m {{message:wip}}");
+        assert!(snippets[1].template);
+    }
+
+    fn library() -> Vec<SaveSnippet> {
+        let mut checkout = SaveSnippet::new("git checkout {{branch:main}}", "sh");
+        checkout.title = "Checkout".to_string();
+        checkout.desc = "switches branches\n$ branch: git branch | cut -c 3-".to_string();
+        checkout.tags = vec!["git".to_string()];
+        checkout.template = true;
+        let mut format = SaveSnippet::new("docker ps --format '{{.Names}}'", "sh");
+        format.title = "Format".to_string();
+        vec![checkout, format]
+    }
+
+    #[test]
+    fn pet_round_trip() {
+        let library = library();
+        let text = export_pet(&library.iter().collect::<Vec<_>>());
+        let snippets = snippets(parse_pet(&text, Path::new("snippet.toml")).unwrap());
+        for (snippet, original) in snippets.iter().zip(&library) {
+            assert_eq!(snippet.title, original.title);
+            assert_eq!(snippet.code, original.code);
+            assert_eq!(snippet.tags, original.tags);
+            assert_eq!(snippet.template, original.template);
+        }
+    }
+
+    #[test]
+    fn navi_round_trip() {
+        let library = library();
+        let text = export_navi(&library.iter().collect::<Vec<_>>());
+        assert!(text.contains("% codecache\n\n# Format\n"));
+        assert!(text.contains("$ branch: echo main\n"));
+        let snippets = snippets(parse_navi(&text, Path::new("codecache.cheat")));
+        let format = snippets.iter().find(|s| s.title == "Format").unwrap();
+        assert_eq!(format.code, library[1].code);
+        assert!(!format.template);
+        let checkout = snippets.iter().find(|s| s.title == "Checkout").unwrap();
+        assert_eq!(checkout.code, library[0].code);
+        assert_eq!(checkout.desc, library[0].desc);
+        assert_eq!(checkout.tags, library[0].tags);
+    }
+}
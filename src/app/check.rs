@@ -0,0 +1,408 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{CodeCache, SaveSnippet, runner, source::file_hash, template, timestamp};
+
+/// checks for languages the config does not name.
+/// the code is piped to the command, or written to `{file}` if the command has it.
+const DEFAULT_CHECKS: [(&str, &str); 9] = [
+    (
+        "rs",
+        "rustc --edition 2024 --crate-type lib --emit metadata --out-dir {dir} -A dead_code -A unused {file}",
+    ),
+    ("py", "python3 -m py_compile {file}"),
+    ("sh", "shellcheck --shell bash --color never {file}"),
+    ("bash", "shellcheck --shell bash --color never {file}"),
+    ("json", "jq empty"),
+    ("js", "node --check {file}"),
+    ("rb", "ruby -wc {file}"),
+    ("pl", "perl -c {file}"),
+    ("go", "gofmt -e -l {file}"),
+];
+
+/// diagnostics are cut to their first lines so the library stays small
+const STORED_LINES: usize = 40;
+
+/// checks and formatters that take longer are stopped, a hanging one would block its caller
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// outcome of the last check, stored with the snippet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub at: u64,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub diagnostics: String,
+    /// hash of the checked code, to tell when the snippet was edited since
+    pub hash: String,
+}
+
+impl CheckResult {
+    /// whether the code changed after it was checked
+    pub fn is_outdated(&self, code: &str) -> bool {
+        self.hash != file_hash(code)
+    }
+}
+
+/// the configured checks on top of the defaults
+pub fn checks(config: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut checks: BTreeMap<String, String> = DEFAULT_CHECKS
+        .iter()
+        .map(|(lang, check)| (lang.to_string(), check.to_string()))
+        .collect();
+    checks.extend(config.clone());
+    checks
+}
+
+/// placeholders are filled with their default or their name, which keeps most code valid
+fn checkable_code(snippet: &SaveSnippet) -> String {
+    let code = template::template_code(snippet);
+    let values: Vec<(String, String)> = template::placeholders(&code)
+        .into_iter()
+        .map(|p| {
+            let value = p.default.unwrap_or_else(|| p.name.clone());
+            (p.name, value)
+        })
+        .collect();
+    let code = template::fill(&code, &values);
+    if snippet.lang == "rs" && !has_rust_items(&code) {
+        // statements are checked as the body of a function
+        format!("fn snippet() {{\n{code}\n}}\n")
+    } else {
+        code
+    }
+}
+
+/// whether rust code is made of items like functions, or is just statements
+pub(super) fn has_rust_items(code: &str) -> bool {
+    const ITEMS: [&str; 14] = [
+        "fn ",
+        "pub ",
+        "struct ",
+        "enum ",
+        "impl ",
+        "impl<",
+        "trait ",
+        "use ",
+        "mod ",
+        "const ",
+        "static ",
+        "type ",
+        "#",
+        "macro_rules!",
+    ];
+    // only the top level lines matter, bodies are indented
+    code.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with(char::is_whitespace))
+        .all(|line| line.starts_with(['}', '/']) || ITEMS.iter().any(|item| line.starts_with(item)))
+}
+
+/// runs the check of the snippet's language, None if there is none for it
+/// and an error if the check could not be run, e.g. because the linter is not installed
+pub fn check(
+    snippet: &SaveSnippet,
+    checks: &BTreeMap<String, String>,
+) -> Option<Result<CheckResult, String>> {
+    let command = checks.get(&snippet.lang)?;
+    let code = checkable_code(snippet);
+    let (passed, diagnostics) = match run_check(command, &code, &snippet.lang) {
+        Ok(outcome) => outcome,
+        Err(err) => return Some(Err(err)),
+    };
+    let lines: Vec<&str> = diagnostics.trim_end().lines().collect();
+    let mut diagnostics = lines[..lines.len().min(STORED_LINES)].join("\n");
+    if lines.len() > STORED_LINES {
+        diagnostics.push_str(&format!("\n… {} more line(s)", lines.len() - STORED_LINES));
+    }
+    Some(Ok(CheckResult {
+        at: timestamp::now(),
+        passed,
+        diagnostics,
+        hash: file_hash(&snippet.code),
+    }))
+}
+
+/// returns whether the command succeeded and what it printed
+fn run_check(command: &str, code: &str, lang: &str) -> Result<(bool, String), String> {
+    let mut args =
+        shell_words::split(command).map_err(|err| format!("invalid check {command:?}: {err}"))?;
+    if args.is_empty() {
+        return Err(format!("no check for {lang}"));
+    }
+    let uses_file = args
+        .iter()
+        .any(|arg| arg.contains("{file}") || arg.contains("{dir}"));
+    let dir = if uses_file {
+        Some(runner::temp_dir()?)
+    } else {
+        None
+    };
+    let file_name = format!("snippet.{lang}");
+    if let Some(dir) = &dir {
+        let file = dir.join(&file_name);
+        fs::write(&file, code).map_err(|err| format!("{}: {err}", file.display()))?;
+        for arg in &mut args {
+            *arg = arg
+                .replace("{file}", &file.to_string_lossy())
+                .replace("{dir}", &dir.to_string_lossy());
+        }
+    }
+    let result = run_command(&args, code, dir.is_none());
+    if let Some(dir) = &dir {
+        let _ = fs::remove_dir_all(dir);
+    }
+    let (passed, stdout, stderr) = result?;
+    let output = stdout + &stderr;
+    // diagnostics name the snippet instead of the temporary file
+    let output = match &dir {
+        Some(dir) => output.replace(
+            &dir.join(&file_name).to_string_lossy().to_string(),
+            &file_name,
+        ),
+        None => output,
+    };
+    Ok((passed, output))
+}
+
+/// runs a command, piping the code to it if asked, and returns whether it succeeded,
+/// its stdout and its stderr. it is killed if it does not finish in time.
+pub(super) fn run_command(
+    args: &[String],
+    code: &str,
+    stdin: bool,
+) -> Result<(bool, String, String), String> {
+    run_with_timeout(args, code, stdin, TIMEOUT)
+}
+
+fn run_with_timeout(
+    args: &[String],
+    code: &str,
+    stdin: bool,
+    timeout: Duration,
+) -> Result<(bool, String, String), String> {
+    let mut command = Command::new(&args[0]);
+    command
+        .args(&args[1..])
+        .stdin(if stdin { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command
+        .spawn()
+        .map_err(|err| format!("failed to start {}: {err}", args[0]))?;
+    // written from a thread, a check printing a lot before reading everything would block us
+    let writer = child.stdin.take().map(|mut pipe| {
+        let code = code.to_string();
+        thread::spawn(move || {
+            let _ = pipe.write_all(code.as_bytes());
+        })
+    });
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < timeout => thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                runner::kill(&mut child);
+                let _ = child.wait();
+                return Err(format!(
+                    "{} did not finish within {:.0}s",
+                    args[0],
+                    timeout.as_secs_f32()
+                ));
+            }
+            Err(err) => return Err(format!("failed to run {}: {err}", args[0])),
+        }
+    };
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let output = |reader: Option<thread::JoinHandle<String>>| {
+        reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default()
+    };
+    Ok((status.success(), output(stdout), output(stderr)))
+}
+
+/// reads a pipe on a thread, so a full stdout can't block the command while it writes stderr
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        String::from_utf8_lossy(&bytes).to_string()
+    })
+}
+
+/// a check running on its own thread, so the app keeps responding
+pub struct CheckJob {
+    id: String,
+    result: Receiver<Option<Result<CheckResult, String>>>,
+}
+
+impl CodeCache {
+    /// starts checking the selected snippet, the result is recorded with it once it is done
+    pub(super) fn check_snippet(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        if snippet.is_locked() {
+            self.notify("Reveal the snippet with x before checking it");
+            return;
+        }
+        if !self.checks.contains_key(&snippet.lang) {
+            self.notify(format!(
+                "No check for {}, add one under [checks] in the config",
+                snippet.lang
+            ));
+            return;
+        }
+        if self.check_job.is_some() {
+            self.notify("Another check is still running");
+            return;
+        }
+        let (sender, result) = mpsc::channel();
+        let (snippet, checks) = (snippet.clone(), self.checks.clone());
+        self.check_job = Some(CheckJob {
+            id: snippet.id.clone(),
+            result,
+        });
+        self.notify(format!("Checking {}…", snippet.title));
+        thread::spawn(move || {
+            let _ = sender.send(check(&snippet, &checks));
+        });
+    }
+
+    /// records the result of a finished check, called on every tick
+    pub(super) fn poll_check(&mut self) {
+        let Some(job) = &self.check_job else {
+            return;
+        };
+        let result = match job.result.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Some(Err("the check stopped".to_string())),
+        };
+        let id = job.id.clone();
+        self.check_job = None;
+        let result = match result {
+            Some(Ok(result)) => result,
+            Some(Err(err)) => {
+                self.notify(format!("Could not check the snippet: {err}"));
+                return;
+            }
+            None => return,
+        };
+        // the snippet may have moved, been deleted or edited while it was checked
+        let Some(idx) = self.save_snippets.iter().position(|s| s.id == id) else {
+            return;
+        };
+        let snippet = &self.save_snippets[idx];
+        let msg = if result.passed {
+            "Check passed".to_string()
+        } else {
+            let first = result.diagnostics.lines().next().unwrap_or_default();
+            format!("Check failed: {first}")
+        };
+        // diagnostics quote the code, secrets would end up in cleartext
+        if !snippet.secret && !result.is_outdated(&snippet.code) {
+            self.save_snippets[idx].check = Some(result);
+            self.refresh_cards();
+        }
+        self.notify(msg);
+    }
+}
+
+/// label of the check badge on a card and whether it is a failure
+pub fn badge(result: &CheckResult, code: &str) -> (String, bool) {
+    if result.is_outdated(code) {
+        ("edited since last check".to_string(), false)
+    } else if result.passed {
+        (format!("✔ checked {}", timestamp::ago(result.at)), false)
+    } else {
+        (
+            format!("✘ check failed {}", timestamp::ago(result.at)),
+            true,
+        )
+    }
+}
+
+/// a snippet's check in the output of `codecache check`
+pub fn report(snippet: &SaveSnippet, result: &CheckResult) -> String {
+    let mut text = format!(
+        "{} {} [{}]",
+        if result.passed { "ok  " } else { "FAIL" },
+        snippet.title,
+        snippet.lang
+    );
+    if !result.passed {
+        for line in result.diagnostics.lines() {
+            text.push_str(&format!("\n    {line}"));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &str) -> Vec<String> {
+        shell_words::split(command).unwrap()
+    }
+
+    #[test]
+    fn commands_get_the_code_and_report_their_output() {
+        let (passed, stdout, stderr) =
+            run_command(&args("bash -c 'cat; echo oops >&2'"), "hello", true).unwrap();
+        assert!(passed);
+        assert_eq!(stdout, "hello");
+        assert_eq!(stderr, "oops\n");
+        // more output than a pipe holds, on both pipes
+        let (_, stdout, stderr) = run_command(
+            &args("bash -c 'seq 100000; seq 100000 >&2; exit 1'"),
+            "",
+            false,
+        )
+        .unwrap();
+        assert_eq!(stdout.lines().count(), 100000);
+        assert_eq!(stderr.lines().count(), 100000);
+    }
+
+    #[test]
+    fn slow_commands_are_stopped() {
+        let started = Instant::now();
+        let err = run_with_timeout(
+            &args("bash -c 'sleep 30 & sleep 30'"),
+            "",
+            false,
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert!(err.contains("did not finish"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rust_statements_are_checked_in_a_function() {
+        assert!(has_rust_items("fn main() {\n    run();\n}"));
+        assert!(has_rust_items("#[derive(Debug)]\nstruct A;"));
+        assert!(!has_rust_items("let x = 1;\nprintln!(\"{x}\");"));
+
+        let mut snippet = SaveSnippet::new("let {{name:x}} = 1;", "rs");
+        snippet.template = true;
+        assert_eq!(checkable_code(&snippet), "fn snippet() {\nlet x = 1;\n}\n");
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    env,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use base64::Engine;
+
+use crate::config::{ClipboardBackend, Config};
+
+/// common interface for everything that can hold copied text
+pub trait ClipboardProvider {
+    /// short name shown to the user
+    fn name(&self) -> &'static str;
+    fn get_text(&mut self) -> Result<String, String>;
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// picks a clipboard provider based on the config, never fails
+pub fn from_config(config: &Config) -> Box<dyn ClipboardProvider> {
+    match config.clipboard {
+        ClipboardBackend::Auto => detect(),
+        ClipboardBackend::Arboard => match arboard::Clipboard::new() {
+            Ok(clipboard) => Box::new(ArboardClipboard(clipboard)),
+            Err(_) => Box::new(InternalClipboard::default()),
+        },
+        ClipboardBackend::Osc52 => Box::new(Osc52Clipboard::default()),
+        ClipboardBackend::Tmux => Box::new(CommandClipboard::tmux()),
+        ClipboardBackend::WlCopy => Box::new(CommandClipboard::wayland()),
+        ClipboardBackend::Xclip => Box::new(CommandClipboard::xclip()),
+        ClipboardBackend::Xsel => Box::new(CommandClipboard::xsel()),
+        ClipboardBackend::Pbcopy => Box::new(CommandClipboard::pbcopy()),
+        ClipboardBackend::Command => Box::new(CommandClipboard {
+            name: "command",
+            copy: split_command(config.clipboard_copy.as_deref()),
+            paste: split_command(config.clipboard_paste.as_deref()),
+        }),
+        ClipboardBackend::Internal => Box::new(InternalClipboard::default()),
+    }
+}
+
+/// chooses a backend that works in the current environment
+fn detect() -> Box<dyn ClipboardProvider> {
+    let over_ssh = env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some();
+    let in_tmux = env::var_os("TMUX").is_some();
+
+    // a local display server is useless when we are on a remote host
+    if over_ssh {
+        if in_tmux && has_command("tmux") {
+            return Box::new(CommandClipboard::tmux());
+        }
+        return Box::new(Osc52Clipboard::default());
+    }
+
+    if let Ok(clipboard) = arboard::Clipboard::new() {
+        return Box::new(ArboardClipboard(clipboard));
+    }
+
+    if env::var_os("WAYLAND_DISPLAY").is_some() && has_command("wl-copy") {
+        return Box::new(CommandClipboard::wayland());
+    }
+    if env::var_os("DISPLAY").is_some() {
+        if has_command("xclip") {
+            return Box::new(CommandClipboard::xclip());
+        }
+        if has_command("xsel") {
+            return Box::new(CommandClipboard::xsel());
+        }
+    }
+    if cfg!(target_os = "macos") && has_command("pbcopy") {
+        return Box::new(CommandClipboard::pbcopy());
+    }
+    if in_tmux && has_command("tmux") {
+        return Box::new(CommandClipboard::tmux());
+    }
+
+    Box::new(InternalClipboard::default())
+}
+
+/// checks whether an executable exists somewhere in PATH
+fn has_command(name: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| {
+            let path = dir.join(name);
+            path.is_file() || path.with_extension("exe").is_file()
+        })
+    })
+}
+
+fn split_command(command: Option<&str>) -> Vec<String> {
+    command
+        .and_then(|cmd| shell_words::split(cmd).ok())
+        .unwrap_or_default()
+}
+
+/// system clipboard through arboard (X11, Wayland, macOS, Windows)
+pub struct ArboardClipboard(arboard::Clipboard);
+
+impl ClipboardProvider for ArboardClipboard {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        self.0.get_text().map_err(|err| err.to_string())
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.0.set_text(text).map_err(|err| err.to_string())
+    }
+}
+
+/// clipboard backed by external programs such as wl-copy, xclip or tmux
+pub struct CommandClipboard {
+    name: &'static str,
+    copy: Vec<String>,
+    paste: Vec<String>,
+}
+
+impl CommandClipboard {
+    fn new(name: &'static str, copy: &[&str], paste: &[&str]) -> Self {
+        Self {
+            name,
+            copy: copy.iter().map(|s| s.to_string()).collect(),
+            paste: paste.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn tmux() -> Self {
+        // -w also forwards the buffer to the outer terminal if set-clipboard is on
+        Self::new(
+            "tmux",
+            &["tmux", "load-buffer", "-w", "-"],
+            &["tmux", "save-buffer", "-"],
+        )
+    }
+
+    fn wayland() -> Self {
+        Self::new("wl-copy", &["wl-copy"], &["wl-paste", "--no-newline"])
+    }
+
+    fn xclip() -> Self {
+        Self::new(
+            "xclip",
+            &["xclip", "-selection", "clipboard", "-in"],
+            &["xclip", "-selection", "clipboard", "-out"],
+        )
+    }
+
+    fn xsel() -> Self {
+        Self::new(
+            "xsel",
+            &["xsel", "--clipboard", "--input"],
+            &["xsel", "--clipboard", "--output"],
+        )
+    }
+
+    fn pbcopy() -> Self {
+        Self::new("pbcopy", &["pbcopy"], &["pbpaste"])
+    }
+}
+
+impl ClipboardProvider for CommandClipboard {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        let Some((program, args)) = self.paste.split_first() else {
+            return Err("no paste command configured".to_string());
+        };
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| format!("failed to run {program}: {err}"))?;
+        if !output.status.success() {
+            return Err(format!("{program} exited with {}", output.status));
+        }
+        String::from_utf8(output.stdout).map_err(|_| "clipboard is not valid UTF-8".to_string())
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        let Some((program, args)) = self.copy.split_first() else {
+            return Err("no copy command configured".to_string());
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("failed to run {program}: {err}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|err| format!("failed to write to {program}: {err}"))?;
+        }
+        // wl-copy and xclip fork into the background, so only the parent exit is awaited
+        let status = child
+            .wait()
+            .map_err(|err| format!("failed to wait for {program}: {err}"))?;
+        if !status.success() {
+            return Err(format!("{program} exited with {status}"));
+        }
+        Ok(())
+    }
+}
+
+/// copies by sending an OSC 52 escape sequence to the terminal, works over SSH.
+/// terminals rarely allow reading the clipboard this way, so pasting falls back to
+/// whatever was last copied inside codecache.
+#[derive(Default)]
+pub struct Osc52Clipboard {
+    buffer: InternalClipboard,
+}
+
+impl ClipboardProvider for Osc52Clipboard {
+    fn name(&self) -> &'static str {
+        "osc52"
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        self.buffer.get_text()
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+        let mut sequence = format!("\x1b]52;c;{encoded}\x07");
+        if env::var_os("TMUX").is_some() {
+            // wrap in a DCS passthrough so tmux hands it to the outer terminal
+            sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+        }
+        let mut stdout = std::io::stdout();
+        stdout
+            .write_all(sequence.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|err| format!("failed to write to terminal: {err}"))?;
+        self.buffer.set_text(text)
+    }
+}
+
+/// clipboard that only lives inside this codecache session
+#[derive(Default)]
+pub struct InternalClipboard {
+    text: Option<String>,
+}
+
+impl ClipboardProvider for InternalClipboard {
+    fn name(&self) -> &'static str {
+        "internal"
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        self.text.clone().ok_or_else(|| {
+            "no system clipboard available, paste into the terminal instead".to_string()
+        })
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        self.text = Some(text.to_string());
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use ratatui::{
     layout::Constraint::{Fill, Length, Min},
     prelude::*,
@@ -6,14 +8,132 @@ use ratatui::{
 use serde::{Deserialize, Serialize};
 use tui_widget_list::{ListBuilder, ListState, ListView};
 
-use crate::app::{highlight::Highlighter, language::get_lang};
+use crate::app::{
+    check::CheckResult,
+    highlight::Highlighter,
+    history::Revision,
+    language::get_lang,
+    runner::LastRun,
+    source::{Source, file_hash},
+};
+
+/// name of the library in `snippets.json` or `library_dir` when filtering by library
+pub const PERSONAL_LIBRARY: &str = "personal";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SaveSnippet {
+    /// stable identity for merging libraries, made up for snippets saved before it existed
+    #[serde(default)]
+    pub id: String,
     pub title: String,
     pub desc: String,
     pub code: String,
     pub lang: String,
+    /// free-form labels, set in the snippet form
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// short abbreviation editors expand into the snippet, like a VS Code prefix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+    /// `{{name}}` in the code is a placeholder asked for on use, plain code keeps its braces
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub template: bool,
+    /// file the snippet was taken from with `codecache add`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    /// code is encrypted at rest and hidden until revealed
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub secret: bool,
+    /// encrypted code of a secret snippet, `code` stays empty until it is revealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<String>,
+    /// earlier values of edited fields, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<Revision>,
+    /// output of the last run, kept when the user asked for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<LastRun>,
+    /// result of the last `codecache check`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<CheckResult>,
+    /// the other version after a merge that changed the same fields on both sides, until resolved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<Box<SaveSnippet>>,
+    /// team library a read-only snippet was loaded from, `None` for the personal library
+    #[serde(skip)]
+    pub library: Option<String>,
+    /// team library the snippet was copied from with `p`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<String>,
+}
+
+impl SaveSnippet {
+    pub fn new(code: impl Into<String>, lang: impl Into<String>) -> Self {
+        Self {
+            id: new_id(),
+            title: String::new(),
+            desc: String::new(),
+            code: code.into(),
+            lang: lang.into(),
+            tags: Vec::new(),
+            trigger: None,
+            template: false,
+            source: None,
+            secret: false,
+            sealed: None,
+            history: Vec::new(),
+            last_run: None,
+            check: None,
+            conflict: None,
+            library: None,
+            forked_from: None,
+        }
+    }
+
+    /// name of the library the snippet is shown from
+    pub fn library_name(&self) -> &str {
+        self.library.as_deref().unwrap_or(PERSONAL_LIBRARY)
+    }
+
+    /// secret snippet whose code has not been decrypted in this session
+    pub fn is_locked(&self) -> bool {
+        self.secret && self.sealed.is_some() && self.code.is_empty()
+    }
+}
+
+/// a random id for a new snippet
+pub fn new_id() -> String {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).expect("failed to get random bytes");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// gives snippets without an id one made from their content, so the same library
+/// gets the same ids on every machine
+pub fn ensure_ids(snippets: &mut [SaveSnippet]) {
+    let mut seen = HashSet::new();
+    for snippet in snippets.iter_mut() {
+        if snippet.id.is_empty() {
+            let content = format!(
+                "{}\0{}\0{}\0{}",
+                snippet.title,
+                snippet.lang,
+                snippet.code,
+                snippet.sealed.as_deref().unwrap_or_default()
+            );
+            snippet.id = file_hash(&content)[..16].to_string();
+        }
+        // identical snippets would get the same id
+        let mut n = 1;
+        while !seen.insert(snippet.id.clone()) {
+            n += 1;
+            snippet.id = format!("{}-{n}", snippet.id.split('-').next().unwrap_or_default());
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 #[derive(Debug, Clone)]
@@ -27,6 +147,17 @@ pub struct CodeSnippet {
     border_style: Style,
     highlighted_code: Option<Text<'static>>,
     lang: String,
+    tags: Vec<String>,
+    /// where the snippet came from and whether that file changed since
+    source: Option<(String, bool)>,
+    /// summary of the stored output and whether the run failed
+    last_run: Option<(String, bool)>,
+    /// badge of the last check, whether it failed and its first diagnostic
+    check: Option<(String, bool, Option<String>)>,
+    /// changed on both sides of a merge and not resolved yet
+    conflict: bool,
+    /// library the snippet belongs to, shown once team libraries are mounted
+    library: Option<String>,
 }
 
 pub struct SnippetList<'a> {
@@ -47,27 +178,108 @@ impl CodeSnippet {
             title: title.into(),
             highlighted_code: None,
             lang: lang.into(),
+            tags: Vec::new(),
+            source: None,
+            last_run: None,
+            check: None,
+            conflict: false,
+            library: None,
         }
     }
 
+    pub fn with_library(mut self, library: String) -> Self {
+        self.library = Some(library);
+        self
+    }
+
+    pub fn with_conflict(mut self) -> Self {
+        self.conflict = true;
+        self
+    }
+
+    pub fn with_check(mut self, badge: String, failed: bool, diagnostic: Option<String>) -> Self {
+        self.check = Some((badge, failed, diagnostic));
+        self
+    }
+
+    pub fn with_source(mut self, source: String, stale: bool) -> Self {
+        self.source = Some((source, stale));
+        self
+    }
+
+    pub fn with_last_run(mut self, summary: String, failed: bool) -> Self {
+        self.last_run = Some((summary, failed));
+        self
+    }
+
+    pub fn with_tags(mut self, tags: &[String]) -> Self {
+        self.tags = tags.to_vec();
+        self
+    }
+
+    /// first diagnostic of a failed check, shown below the description
+    fn diagnostic(&self) -> Option<&str> {
+        self.check
+            .as_ref()
+            .and_then(|(_, failed, diagnostic)| diagnostic.as_deref().filter(|_| *failed))
+    }
+
     pub fn height(&self) -> u16 {
         let description_lines = (self.text.lines().count() as u16).max(1);
         let code_lines = (self.code.lines().count() as u16).max(1);
         let border_height = 4;
-        description_lines + code_lines + border_height
+        let source_lines = self.source.is_some() as u16
+            + self.last_run.is_some() as u16
+            + self.diagnostic().is_some() as u16;
+        description_lines + source_lines + code_lines + border_height
     }
 }
 
 impl Widget for CodeSnippet {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        let diagnostic = self.diagnostic().map(str::to_string);
+
         // Center the card horizontally
         let [_, block_area, _] = Layout::horizontal([Fill(1), Min(70), Fill(1)]).areas(area);
 
         // Outer block with title
-        let block = Block::bordered()
+        let mut block = Block::bordered()
             .title(self.title)
             .title_alignment(Alignment::Center)
             .border_style(self.border_style);
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("#{tag}")).collect();
+            block = block.title_bottom(Line::from(format!(" {} ", tags.join(" "))).right_aligned());
+        }
+        if let Some(library) = &self.library {
+            block = block.title_bottom(
+                Line::styled(
+                    format!(" {library} "),
+                    Style::default().fg(Color::Rgb(131, 165, 152)),
+                )
+                .left_aligned(),
+            );
+        }
+        if let Some((badge, failed, _)) = &self.check {
+            let color = if *failed {
+                Color::Rgb(251, 73, 52)
+            } else {
+                Color::Rgb(184, 187, 38)
+            };
+            block = block.title(
+                Line::styled(format!(" {badge} "), Style::default().fg(color)).right_aligned(),
+            );
+        }
+
+        if self.conflict {
+            block = block.title(
+                Line::styled(
+                    " merge conflict, press m ",
+                    Style::default().fg(Color::Rgb(251, 73, 52)),
+                )
+                .left_aligned(),
+            );
+        }
 
         let inner_area = block.inner(block_area);
         block.render(block_area, buf);
@@ -75,13 +287,51 @@ impl Widget for CodeSnippet {
         let desc_lines = self.text.lines().count().max(1) as u16;
         let code_lines = self.code.lines().count().max(1) as u16 + 2;
 
-        let [desc_area, code_area] =
-            Layout::vertical([Length(desc_lines), Length(code_lines)]).areas(inner_area);
+        let source_lines = self.source.is_some() as u16;
+        let run_lines = self.last_run.is_some() as u16;
+        let check_lines = diagnostic.is_some() as u16;
+
+        let [desc_area, source_area, run_area, check_area, code_area] = Layout::vertical([
+            Length(desc_lines),
+            Length(source_lines),
+            Length(run_lines),
+            Length(check_lines),
+            Length(code_lines),
+        ])
+        .areas(inner_area);
+
+        if let Some(diagnostic) = diagnostic {
+            Line::styled(
+                format!("check: {diagnostic}"),
+                Style::default().fg(Color::Rgb(251, 73, 52)),
+            )
+            .render(check_area, buf);
+        }
 
         Paragraph::new(self.text)
             .style(self.text_style)
             .render(desc_area, buf);
 
+        if let Some((source, stale)) = self.source {
+            let color = if stale {
+                Color::Rgb(251, 73, 52)
+            } else {
+                Color::Rgb(102, 92, 84)
+            };
+            Line::styled(format!("source: {source}"), Style::default().fg(color))
+                .render(source_area, buf);
+        }
+
+        if let Some((summary, failed)) = self.last_run {
+            let color = if failed {
+                Color::Rgb(251, 73, 52)
+            } else {
+                Color::Rgb(102, 92, 84)
+            };
+            Line::styled(format!("last run: {summary}"), Style::default().fg(color))
+                .render(run_area, buf);
+        }
+
         let code_block = Block::bordered()
             .border_style(self.code_frame_style)
             .title(get_lang(&self.lang))
@@ -0,0 +1,192 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    io::{self, Write},
+};
+
+use argon2::Argon2;
+use base64::Engine;
+use chacha20poly1305::{
+    Key, XChaCha20Poly1305, XNonce,
+    aead::{Aead, KeyInit},
+};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use zeroize::Zeroizing;
+
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// holds the session passphrase and the keys derived from it.
+/// everything secret is wiped from memory when the keyring is dropped.
+pub struct Keyring {
+    passphrase: Option<Zeroizing<String>>,
+    keys: HashMap<[u8; SALT_LEN], Zeroizing<[u8; 32]>>,
+    /// salt used for everything sealed in this session, so the slow key derivation runs once
+    salt: [u8; SALT_LEN],
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self {
+            passphrase: None,
+            keys: HashMap::new(),
+            salt: random(),
+        }
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    pub fn unlock(&mut self, passphrase: Zeroizing<String>) {
+        self.keys.clear();
+        self.passphrase = Some(passphrase);
+    }
+
+    pub fn lock(&mut self) {
+        self.keys.clear();
+        self.passphrase = None;
+    }
+
+    fn key(&mut self, salt: [u8; SALT_LEN]) -> Result<&Zeroizing<[u8; 32]>, String> {
+        let Some(passphrase) = &self.passphrase else {
+            return Err("no passphrase entered".to_string());
+        };
+        match self.keys.entry(salt) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let mut key = Zeroizing::new([0u8; 32]);
+                Argon2::default()
+                    .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+                    .map_err(|err| format!("failed to derive key: {err}"))?;
+                Ok(entry.insert(key))
+            }
+        }
+    }
+
+    /// encrypts text and returns it as a base64 string
+    pub fn seal(&mut self, plaintext: &str) -> Result<String, String> {
+        let salt = self.salt;
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(self.key(salt)?.as_ref()));
+        let nonce: [u8; NONCE_LEN] = random();
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| "failed to encrypt".to_string())?;
+
+        let mut blob = Vec::with_capacity(1 + SALT_LEN + NONCE_LEN + ciphertext.len());
+        blob.push(VERSION);
+        blob.extend_from_slice(&salt);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        Ok(base64::engine::general_purpose::STANDARD.encode(blob))
+    }
+
+    /// decrypts a string produced by `seal`
+    pub fn open(&mut self, sealed: &str) -> Result<Zeroizing<String>, String> {
+        let blob = base64::engine::general_purpose::STANDARD
+            .decode(sealed)
+            .map_err(|_| "encrypted data is corrupted".to_string())?;
+        if blob.len() < 1 + SALT_LEN + NONCE_LEN || blob[0] != VERSION {
+            return Err("encrypted data is corrupted or from a newer version".to_string());
+        }
+        let (salt, rest) = blob[1..].split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let salt: [u8; SALT_LEN] = salt.try_into().expect("salt has a fixed length");
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(self.key(salt)?.as_ref()));
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(XNonce::from_slice(nonce), ciphertext)
+                .map_err(|_| "wrong passphrase".to_string())?,
+        );
+        String::from_utf8(plaintext.to_vec())
+            .map(Zeroizing::new)
+            .map_err(|_| "encrypted data is corrupted".to_string())
+    }
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).expect("failed to get random bytes");
+    bytes
+}
+
+/// asks for a passphrase on the terminal without echoing it, before the TUI starts
+pub fn prompt_passphrase(prompt: &str) -> Option<Zeroizing<String>> {
+    let mut stderr = io::stderr();
+    write!(stderr, "{prompt}: ").ok()?;
+    stderr.flush().ok()?;
+
+    terminal::enable_raw_mode().ok()?;
+    let mut passphrase = Zeroizing::new(String::new());
+    let submitted = loop {
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => break true,
+            KeyCode::Esc => break false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break false,
+            KeyCode::Backspace => {
+                passphrase.pop();
+            }
+            KeyCode::Char(c) => passphrase.push(c),
+            _ => {}
+        }
+    };
+    let _ = terminal::disable_raw_mode();
+    let _ = writeln!(stderr);
+
+    (submitted && !passphrase.is_empty()).then_some(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unlocked(passphrase: &str) -> Keyring {
+        let mut keyring = Keyring::new();
+        keyring.unlock(Zeroizing::new(passphrase.to_string()));
+        keyring
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut keyring = unlocked("correct horse");
+        let sealed = keyring.seal("export TOKEN=hunter2").unwrap();
+        assert!(!sealed.contains("hunter2"));
+        // a new session derives the key again from the salt in the blob
+        let mut other = unlocked("correct horse");
+        assert_eq!(
+            other.open(&sealed).unwrap().as_str(),
+            "export TOKEN=hunter2"
+        );
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let sealed = unlocked("correct horse").seal("code").unwrap();
+        assert_eq!(
+            unlocked("battery staple").open(&sealed).unwrap_err(),
+            "wrong passphrase"
+        );
+    }
+
+    #[test]
+    fn locked_keyring_cannot_seal() {
+        assert!(Keyring::new().seal("code").is_err());
+    }
+
+    #[test]
+    fn corrupted_data() {
+        let mut keyring = unlocked("correct horse");
+        assert!(keyring.open("not base64!").is_err());
+        assert!(keyring.open("AQID").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use ratatui::{
+    layout::Constraint::{Fill, Length},
+    prelude::*,
+    widgets::{Block, Paragraph},
+};
+use similar::{ChangeTag, TextDiff};
+
+use crate::app::highlight::Highlighter;
+
+const DELETE_BG: Color = Color::Rgb(74, 30, 30);
+const INSERT_BG: Color = Color::Rgb(30, 60, 34);
+const GUTTER: Color = Color::Rgb(124, 111, 100);
+
+/// syntax highlighted line diff between two versions of some code
+pub struct DiffView<'a> {
+    pub old: &'a str,
+    pub new: &'a str,
+    pub lang: &'a str,
+    pub highlighter: &'a Highlighter,
+    pub side_by_side: bool,
+    /// number of lines scrolled down
+    pub scroll: u16,
+    pub block: Block<'a>,
+}
+
+/// one row of the diff, `None` on a side means there is no line there
+struct Row {
+    tag: ChangeTag,
+    old: Option<usize>,
+    new: Option<usize>,
+}
+
+impl DiffView<'_> {
+    fn rows(&self) -> Vec<Row> {
+        let diff = TextDiff::from_lines(self.old, self.new);
+        let mut rows = Vec::new();
+        for change in diff.iter_all_changes() {
+            rows.push(Row {
+                tag: change.tag(),
+                old: change.old_index(),
+                new: change.new_index(),
+            });
+        }
+        rows
+    }
+
+    /// pairs deleted and inserted lines next to each other for the side by side view
+    fn paired_rows(&self) -> Vec<(Option<usize>, Option<usize>, ChangeTag)> {
+        let mut paired = Vec::new();
+        let rows = self.rows();
+        let mut i = 0;
+        while i < rows.len() {
+            if rows[i].tag == ChangeTag::Equal {
+                paired.push((rows[i].old, rows[i].new, ChangeTag::Equal));
+                i += 1;
+                continue;
+            }
+            let deleted: Vec<usize> = rows[i..]
+                .iter()
+                .take_while(|r| r.tag == ChangeTag::Delete)
+                .filter_map(|r| r.old)
+                .collect();
+            i += deleted.len();
+            let inserted: Vec<usize> = rows[i..]
+                .iter()
+                .take_while(|r| r.tag == ChangeTag::Insert)
+                .filter_map(|r| r.new)
+                .collect();
+            i += inserted.len();
+            for n in 0..deleted.len().max(inserted.len()) {
+                paired.push((
+                    deleted.get(n).copied(),
+                    inserted.get(n).copied(),
+                    ChangeTag::Delete,
+                ));
+            }
+        }
+        paired
+    }
+}
+
+/// highlights code and returns its lines, so single lines can be picked out
+fn highlighted_lines(highlighter: &Highlighter, code: &str, lang: &str) -> Vec<Line<'static>> {
+    let text = match highlighter.highlight(code, lang) {
+        Ok(text) => text,
+        Err(text) => text,
+    };
+    text.lines
+}
+
+fn diff_line(
+    lines: &[Line<'static>],
+    idx: Option<usize>,
+    sign: &'static str,
+    bg: Option<Color>,
+) -> Line<'static> {
+    let Some(idx) = idx else {
+        return Line::default();
+    };
+    let mut spans = vec![
+        Span::styled(format!("{:>4} ", idx + 1), Style::default().fg(GUTTER)),
+        Span::raw(sign),
+    ];
+    if let Some(line) = lines.get(idx) {
+        spans.extend(line.spans.iter().cloned());
+    }
+    let line = Line::from(spans);
+    match bg {
+        Some(bg) => line.style(Style::default().bg(bg)),
+        None => line,
+    }
+}
+
+impl Widget for DiffView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let old_lines = highlighted_lines(self.highlighter, self.old, self.lang);
+        let new_lines = highlighted_lines(self.highlighter, self.new, self.lang);
+        let inner = self.block.inner(area);
+        self.block.clone().render(area, buf);
+
+        if self.side_by_side {
+            let mut left = Vec::new();
+            let mut right = Vec::new();
+            for (old, new, tag) in self.paired_rows() {
+                let changed = tag != ChangeTag::Equal;
+                left.push(diff_line(
+                    &old_lines,
+                    old,
+                    if changed { "-" } else { " " },
+                    changed.then_some(DELETE_BG),
+                ));
+                right.push(diff_line(
+                    &new_lines,
+                    new,
+                    if changed { "+" } else { " " },
+                    changed.then_some(INSERT_BG),
+                ));
+            }
+            let [left_area, _, right_area] =
+                Layout::horizontal([Fill(1), Length(1), Fill(1)]).areas(inner);
+            Paragraph::new(left)
+                .scroll((self.scroll, 0))
+                .render(left_area, buf);
+            Paragraph::new(right)
+                .scroll((self.scroll, 0))
+                .render(right_area, buf);
+        } else {
+            let lines: Vec<Line> = self
+                .rows()
+                .into_iter()
+                .map(|row| match row.tag {
+                    ChangeTag::Equal => diff_line(&new_lines, row.new, " ", None),
+                    ChangeTag::Delete => diff_line(&old_lines, row.old, "-", Some(DELETE_BG)),
+                    ChangeTag::Insert => diff_line(&new_lines, row.new, "+", Some(INSERT_BG)),
+                })
+                .collect();
+            Paragraph::new(lines)
+                .scroll((self.scroll, 0))
+                .render(inner, buf);
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::DefaultHasher},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::app::{
+    CodeCache, SaveSnippet, View,
+    check::CheckResult,
+    ensure_ids,
+    history::Revision,
+    merge,
+    runner::LastRun,
+    source::{Source, file_hash},
+    undo::UndoStack,
+};
+
+/// a snippet is a file like `retry-loop.rs` next to a sidecar `retry-loop.rs.toml`
+/// holding everything but the code and the language
+const SIDECAR: &str = "toml";
+
+/// reads every snippet of a library directory, ordered by file name
+pub fn load(dir: &Path) -> Vec<SaveSnippet> {
+    scan(dir).into_iter().map(|(_, snippet)| snippet).collect()
+}
+
+/// snippets of the directory with their file names
+fn scan(dir: &Path) -> Vec<(String, SaveSnippet)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    let files: HashSet<&str> = names.iter().map(String::as_str).collect();
+
+    let mut snippets: Vec<(String, SaveSnippet)> = names
+        .iter()
+        // files without a sidecar, like a README, are not snippets
+        .filter(|name| files.contains(sidecar_name(name).as_str()))
+        .filter_map(|name| Some((name.clone(), read(dir, name)?)))
+        .collect();
+    let mut library: Vec<SaveSnippet> = snippets.iter().map(|(_, s)| s.clone()).collect();
+    ensure_ids(&mut library);
+    for ((_, snippet), with_id) in snippets.iter_mut().zip(library) {
+        snippet.id = with_id.id;
+    }
+    snippets
+}
+
+fn sidecar_name(name: &str) -> String {
+    format!("{name}.{SIDECAR}")
+}
+
+/// reads a snippet file and its sidecar, skipping ones that can't be parsed
+fn read(dir: &Path, name: &str) -> Option<SaveSnippet> {
+    let code = fs::read_to_string(dir.join(name)).ok()?;
+    let sidecar = fs::read_to_string(dir.join(sidecar_name(name))).ok()?;
+    let mut meta: toml::Table = toml::from_str(&sidecar).ok()?;
+    let lang = Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "txt".to_string());
+    // only the newline `write` adds, blank lines the code ends with are kept
+    let code = code.strip_suffix('\n').unwrap_or(&code);
+    meta.insert("code".to_string(), code.into());
+    meta.insert("lang".to_string(), lang.into());
+    // hand written sidecars may leave out anything but the title
+    for field in ["title", "desc"] {
+        meta.entry(field).or_insert_with(|| "".into());
+    }
+    let mut snippet: SaveSnippet = toml::Value::Table(meta).try_into().ok()?;
+    if snippet.id.is_empty() {
+        // named after the file, so the id stays when the code is edited outside codecache
+        snippet.id = file_hash(name)[..16].to_string();
+    }
+    Some(snippet)
+}
+
+/// file extension for a language, only letters and digits so the name stays in the directory
+pub(super) fn extension(lang: &str) -> String {
+    let ext: String = lang.chars().filter(|c| c.is_alphanumeric()).collect();
+    if ext.is_empty() {
+        "txt".to_string()
+    } else {
+        ext
+    }
+}
+
+/// file name for a snippet, its title in kebab case with the language as extension.
+/// the name and its sidecar must both be free.
+fn file_name(snippet: &SaveSnippet, taken: &HashSet<String>) -> String {
+    let mut stem = String::new();
+    for c in snippet.title.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            stem.push(c);
+        } else if !stem.is_empty() && !stem.ends_with('-') {
+            stem.push('-');
+        }
+    }
+    let stem = stem.trim_end_matches('-');
+    let stem = if stem.is_empty() { "snippet" } else { stem };
+    let lang = extension(&snippet.lang);
+    let mut name = format!("{stem}.{lang}");
+    let mut n = 1;
+    while taken.contains(&name) || taken.contains(&sidecar_name(&name)) {
+        n += 1;
+        name = format!("{stem}-{n}.{lang}");
+    }
+    name
+}
+
+/// writes the library as one file and sidecar per snippet. snippets keep their file
+/// as long as their language stays, and files of removed snippets are deleted.
+pub fn save(dir: &Path, snippets: &[SaveSnippet]) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    let existing: HashMap<String, String> = scan(dir)
+        .into_iter()
+        .map(|(name, snippet)| (snippet.id, name))
+        .collect();
+
+    // files that are no snippet, like a README or one whose sidecar is broken, are never overwritten
+    let owned: HashSet<String> = existing
+        .values()
+        .flat_map(|name| [name.clone(), sidecar_name(name)])
+        .collect();
+    let mut taken: HashSet<String> = fs::read_dir(dir)
+        .map_err(|err| format!("{}: {err}", dir.display()))?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !owned.contains(name))
+        .collect();
+    for snippet in snippets {
+        let name = match existing.get(&snippet.id) {
+            Some(name)
+                if Path::new(name)
+                    .extension()
+                    .is_some_and(|ext| *ext == *extension(&snippet.lang))
+                    && !taken.contains(name) =>
+            {
+                name.clone()
+            }
+            _ => file_name(snippet, &taken),
+        };
+        write(dir, &name, snippet)?;
+        taken.insert(name);
+    }
+    for name in existing.values().filter(|name| !taken.contains(*name)) {
+        for file in [name.clone(), sidecar_name(name)] {
+            let path = dir.join(file);
+            fs::remove_file(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// contents of a sidecar, the fields of a snippet except its code and language
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    id: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    desc: &'a str,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    trigger: &'a Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    template: bool,
+    source: &'a Option<Source>,
+    #[serde(skip_serializing_if = "is_false")]
+    secret: bool,
+    sealed: &'a Option<String>,
+    #[serde(skip_serializing_if = "<[Revision]>::is_empty")]
+    history: &'a [Revision],
+    last_run: &'a Option<LastRun>,
+    check: &'a Option<CheckResult>,
+    conflict: &'a Option<Box<SaveSnippet>>,
+    forked_from: &'a Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn write(dir: &Path, name: &str, snippet: &SaveSnippet) -> Result<(), String> {
+    let sidecar = toml::to_string(&Sidecar {
+        id: &snippet.id,
+        title: &snippet.title,
+        desc: &snippet.desc,
+        tags: &snippet.tags,
+        trigger: &snippet.trigger,
+        template: snippet.template,
+        source: &snippet.source,
+        secret: snippet.secret,
+        sealed: &snippet.sealed,
+        history: &snippet.history,
+        last_run: &snippet.last_run,
+        check: &snippet.check,
+        conflict: &snippet.conflict,
+        forked_from: &snippet.forked_from,
+    })
+    .map_err(|err| format!("{name}: {err}"))?;
+    let code = if snippet.code.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", snippet.code)
+    };
+    for (file, content) in [(name.to_string(), code), (sidecar_name(name), sidecar)] {
+        let path = dir.join(file);
+        // unchanged files are left alone, so their modification time says what changed
+        if fs::read_to_string(&path).is_ok_and(|old| old == content) {
+            continue;
+        }
+        fs::write(&path, content).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// changes whenever a file in the directory is added, removed or modified
+fn fingerprint(dir: &Path) -> u64 {
+    let mut files: Vec<(String, u64, Option<SystemTime>)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            Some((name, meta.len(), meta.modified().ok()))
+        })
+        .collect();
+    files.sort();
+    let mut hasher = DefaultHasher::new();
+    files.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// a library directory the app reloads when its files change
+pub struct DirWatch {
+    dir: PathBuf,
+    fingerprint: u64,
+    /// the library as last read from the directory, the base for merging local edits
+    snapshot: Vec<SaveSnippet>,
+    last_poll: Instant,
+}
+
+impl CodeCache {
+    /// reloads the library when files in `dir` change while the app runs
+    pub fn watch_dir(&mut self, dir: &Path) {
+        self.dir_watch = Some(DirWatch {
+            dir: dir.to_path_buf(),
+            fingerprint: fingerprint(dir),
+            snapshot: self
+                .save_snippets
+                .iter()
+                .filter(|s| s.library.is_none())
+                .cloned()
+                .collect(),
+            last_poll: Instant::now(),
+        });
+    }
+
+    /// merges changes made on disk, e.g. by `git pull`, with edits made in the app since
+    pub(super) fn poll_dir(&mut self) {
+        let Some(watch) = &mut self.dir_watch else {
+            return;
+        };
+        // indices held by a form or panel would point at other snippets after a reload
+        let busy = self.view != View::Library || self.form.is_some() || self.fill_prompt.is_some();
+        if busy || watch.last_poll.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        watch.last_poll = Instant::now();
+        let fingerprint = fingerprint(&watch.dir);
+        if fingerprint == watch.fingerprint {
+            return;
+        }
+        watch.fingerprint = fingerprint;
+        let disk = load(&watch.dir);
+        if disk == watch.snapshot {
+            return;
+        }
+        // team snippets are on both sides unchanged, so they stay where they are
+        let layers: Vec<SaveSnippet> = self
+            .save_snippets
+            .iter()
+            .filter(|s| s.library.is_some())
+            .cloned()
+            .collect();
+        let base = [watch.snapshot.as_slice(), &layers].concat();
+        let theirs = [disk.as_slice(), &layers].concat();
+        let merged = merge::merge(&base, &self.save_snippets, &theirs);
+        let conflicts = merged.conflicts.len();
+        watch.snapshot = disk;
+        self.save_snippets = merged.into_snippets();
+        self.check_sources();
+        self.refresh_cards();
+        // undo would apply old changes to whatever snippet now has their index
+        self.undo_stack = UndoStack::default();
+        if let Some(selected) = self.selected() {
+            self.fix_selection(selected);
+        }
+        if conflicts > 0 {
+            self.notify(format!(
+                "Reloaded the library from disk, {conflicts} conflict(s) with your edits, press m to resolve them"
+            ));
+        } else {
+            self.notify("Reloaded the library from disk");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::runner::temp_dir;
+
+    fn snippet(title: &str, code: &str, lang: &str) -> SaveSnippet {
+        let mut snippet = SaveSnippet::new(code, lang);
+        snippet.title = title.to_string();
+        snippet
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_dir().unwrap();
+        let mut list = snippet("List files!", "ls -la\n\n", "sh");
+        list.tags = vec!["fs".to_string()];
+        list.template = true;
+        let mut token = snippet("Token", "", "sh");
+        token.secret = true;
+        token.sealed = Some("sealed".to_string());
+        let library = vec![list, token];
+        save(&dir, &library).unwrap();
+        assert_eq!(
+            files(&dir),
+            [
+                "list-files.sh",
+                "list-files.sh.toml",
+                "token.sh",
+                "token.sh.toml"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("list-files.sh")).unwrap(),
+            "ls -la\n\n\n"
+        );
+        assert_eq!(load(&dir), library);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_names_stay_in_the_directory() {
+        let taken = HashSet::new();
+        assert_eq!(
+            file_name(&snippet("Read ../etc", "x", "../../sh"), &taken),
+            "read-etc.sh"
+        );
+        assert_eq!(file_name(&snippet("???", "x", "/"), &taken), "snippet.txt");
+        let taken = HashSet::from(["a.rs".to_string(), "a-2.rs".to_string()]);
+        assert_eq!(file_name(&snippet("A", "x", "rs"), &taken), "a-3.rs");
+    }
+
+    #[test]
+    fn files_follow_the_library() {
+        let dir = temp_dir().unwrap();
+        let mut library = vec![snippet("One", "1", "py"), snippet("Two", "2", "py")];
+        save(&dir, &library).unwrap();
+        // a new language moves the file, a removed snippet loses its files
+        library[0].lang = "rb".to_string();
+        library.pop();
+        save(&dir, &library).unwrap();
+        assert_eq!(files(&dir), ["one.rb", "one.rb.toml"]);
+        assert_eq!(load(&dir), library);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hand_written_snippets() {
+        let dir = temp_dir().unwrap();
+        fs::write(dir.join("hello.py"), "print('hi')\n").unwrap();
+        fs::write(dir.join("hello.py.toml"), "title = \"Hello\"\n").unwrap();
+        fs::write(dir.join("README.md"), "# not a snippet\n").unwrap();
+        fs::write(dir.join("broken.sh"), "ls\n").unwrap();
+        fs::write(dir.join("broken.sh.toml"), "title = [\n").unwrap();
+        let snippets = load(&dir);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].title, "Hello");
+        assert_eq!(snippets[0].code, "print('hi')");
+        assert_eq!(snippets[0].lang, "py");
+        // the id comes from the file name, so it survives edits to the code
+        assert_eq!(snippets[0].id, file_hash("hello.py")[..16]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn other_files_are_not_overwritten() {
+        let dir = temp_dir().unwrap();
+        fs::write(dir.join("notes.sh"), "# my notes\n").unwrap();
+        fs::write(dir.join("broken.sh"), "ls\n").unwrap();
+        fs::write(dir.join("broken.sh.toml"), "title = [\n").unwrap();
+        let snippets = ["Notes", "Broken"].map(|title| {
+            let mut snippet = SaveSnippet::new("echo", "sh");
+            snippet.title = title.to_string();
+            snippet
+        });
+
+        save(&dir, &snippets).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("notes.sh")).unwrap(),
+            "# my notes\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("broken.sh")).unwrap(), "ls\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("broken.sh.toml")).unwrap(),
+            "title = [\n"
+        );
+        assert!(dir.join("notes-2.sh").exists() && dir.join("broken-2.sh").exists());
+        // loaded by file name, broken-2.sh before notes-2.sh
+        assert_eq!(load(&dir), [snippets[1].clone(), snippets[0].clone()]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
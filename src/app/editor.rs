@@ -0,0 +1,692 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Paragraph},
+};
+
+use crate::app::highlight::Highlighter;
+
+/// columns a tab character takes on screen
+const TAB_WIDTH: usize = 4;
+/// undo steps kept per editor
+const UNDO_LIMIT: usize = 200;
+
+/// a cursor position as (line, character)
+type Pos = (usize, usize);
+
+/// what the last edit was, consecutive typing is undone as one step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    Typing,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    lines: Vec<String>,
+    row: usize,
+    col: usize,
+}
+
+/// text input used by the snippet form, from a single line up to a small code editor
+#[derive(Debug, Clone)]
+pub struct Editor {
+    lines: Vec<String>,
+    /// cursor line
+    row: usize,
+    /// cursor position in characters
+    col: usize,
+    /// other end of the selection, the cursor is the end that moves
+    anchor: Option<Pos>,
+    multiline: bool,
+    line_numbers: bool,
+    /// first visible line
+    scroll: usize,
+    /// visible lines at the last render, used for page up / page down
+    height: usize,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: Option<EditKind>,
+}
+
+impl Editor {
+    pub fn single_line(text: &str) -> Self {
+        Self::new(&text.replace('\n', " "), false)
+    }
+
+    pub fn multi_line(text: &str) -> Self {
+        Self::new(text, true)
+    }
+
+    /// multi line editor with line numbers, indentation helpers and highlighting
+    pub fn code(text: &str) -> Self {
+        let mut editor = Self::new(text, true);
+        editor.line_numbers = true;
+        editor
+    }
+
+    fn new(text: &str, multiline: bool) -> Self {
+        Self {
+            lines: split_lines(text),
+            row: 0,
+            col: 0,
+            anchor: None,
+            multiline,
+            line_numbers: false,
+            scroll: 0,
+            height: 1,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// replaces the whole text, can be undone
+    pub fn set_text(&mut self, text: &str) {
+        self.record(EditKind::Other);
+        self.lines = split_lines(text);
+        self.anchor = None;
+        self.end_of_text();
+    }
+
+    pub fn end_of_text(&mut self) {
+        self.row = self.lines.len() - 1;
+        self.col = self.line_len();
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    /// byte offset of a character position in a line
+    fn byte_idx(&self, row: usize, col: usize) -> usize {
+        let line = &self.lines[row];
+        line.char_indices()
+            .nth(col)
+            .map(|(i, _)| i)
+            .unwrap_or(line.len())
+    }
+
+    /// text of the current line before the cursor
+    fn before_cursor(&self) -> &str {
+        &self.lines[self.row][..self.byte_idx(self.row, self.col)]
+    }
+
+    /// saves the current state for undo unless it continues the last edit
+    fn record(&mut self, kind: EditKind) {
+        if kind == EditKind::Typing && self.last_edit == Some(EditKind::Typing) {
+            return;
+        }
+        self.undo.push(Snapshot {
+            lines: self.lines.clone(),
+            row: self.row,
+            col: self.col,
+        });
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.last_edit = Some(kind);
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+        let current = Snapshot {
+            lines: std::mem::replace(&mut self.lines, snapshot.lines),
+            row: self.row,
+            col: self.col,
+        };
+        self.row = snapshot.row;
+        self.col = snapshot.col;
+        self.anchor = None;
+        self.last_edit = None;
+        current
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            let current = self.restore(snapshot);
+            self.redo.push(current);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            let current = self.restore(snapshot);
+            self.undo.push(current);
+        }
+    }
+
+    /// start and end of the selection in text order, `None` if nothing is selected
+    fn selection(&self) -> Option<(Pos, Pos)> {
+        let anchor = self.anchor?;
+        let cursor = (self.row, self.col);
+        match anchor.cmp(&cursor) {
+            std::cmp::Ordering::Less => Some((anchor, cursor)),
+            std::cmp::Ordering::Greater => Some((cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.selection().is_some()
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.anchor = None;
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        if start.0 == end.0 {
+            let line = &self.lines[start.0];
+            return Some(line[self.byte_idx(start.0, start.1)..self.byte_idx(end.0, end.1)].into());
+        }
+        let mut text = self.lines[start.0][self.byte_idx(start.0, start.1)..].to_string();
+        for line in &self.lines[start.0 + 1..end.0] {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&self.lines[end.0][..self.byte_idx(end.0, end.1)]);
+        Some(text)
+    }
+
+    /// removes the selected text, returns false if nothing was selected
+    fn remove_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        let tail = self.lines[end.0][self.byte_idx(end.0, end.1)..].to_string();
+        let head_end = self.byte_idx(start.0, start.1);
+        self.lines[start.0].truncate(head_end);
+        self.lines[start.0].push_str(&tail);
+        self.lines.drain(start.0 + 1..=end.0);
+        (self.row, self.col) = start;
+        self.anchor = None;
+        true
+    }
+
+    /// deletes the selection as its own undo step, used for cut
+    pub fn delete_selection(&mut self) {
+        if self.has_selection() {
+            self.record(EditKind::Other);
+            self.remove_selection();
+        }
+    }
+
+    fn select_all(&mut self) {
+        self.anchor = Some((0, 0));
+        self.end_of_text();
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        if c == '\n' {
+            self.newline();
+            return;
+        }
+        let kind = if c.is_whitespace() {
+            EditKind::Other
+        } else {
+            EditKind::Typing
+        };
+        self.record(kind);
+        self.remove_selection();
+        // a closing bracket on an otherwise empty line goes back one level
+        if self.line_numbers
+            && matches!(c, '}' | ']' | ')')
+            && !self.before_cursor().is_empty()
+            && self.before_cursor().trim().is_empty()
+        {
+            self.dedent_line(self.row);
+        }
+        let idx = self.byte_idx(self.row, self.col);
+        self.lines[self.row].insert(idx, c);
+        self.col += 1;
+    }
+
+    /// inserts text as is, without auto-indent, e.g. for pastes
+    pub fn insert_str(&mut self, text: &str) {
+        self.record(EditKind::Other);
+        self.remove_selection();
+        for c in text.chars() {
+            match c {
+                '\r' => {}
+                '\n' if self.multiline => self.split_line(),
+                '\n' => self.insert_raw(' '),
+                c => self.insert_raw(c),
+            }
+        }
+        self.last_edit = None;
+    }
+
+    fn insert_raw(&mut self, c: char) {
+        let idx = self.byte_idx(self.row, self.col);
+        self.lines[self.row].insert(idx, c);
+        self.col += 1;
+    }
+
+    fn split_line(&mut self) {
+        let idx = self.byte_idx(self.row, self.col);
+        let rest = self.lines[self.row].split_off(idx);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.col = 0;
+    }
+
+    /// starts a new line with the indentation of the current one
+    fn newline(&mut self) {
+        if !self.multiline {
+            return;
+        }
+        self.record(EditKind::Other);
+        self.remove_selection();
+        if !self.line_numbers {
+            self.split_line();
+            return;
+        }
+        let before = self.before_cursor();
+        let indent: String = before
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let opens_block = before.trim_end().ends_with(['{', '[', '(', ':']);
+        let closes_block = self.lines[self.row][self.byte_idx(self.row, self.col)..]
+            .trim_start()
+            .starts_with(['}', ']', ')']);
+
+        self.split_line();
+        let rest = self.lines[self.row].trim_start().to_string();
+        self.lines[self.row] = format!("{indent}{rest}");
+        self.col = indent.chars().count();
+        if opens_block {
+            let unit = self.indent_unit();
+            if closes_block {
+                // keep the closing bracket on its own line below the cursor
+                let closing = std::mem::take(&mut self.lines[self.row]);
+                self.lines.insert(self.row + 1, closing);
+                self.lines[self.row] = indent.clone();
+            }
+            let idx = self.byte_idx(self.row, self.col);
+            self.lines[self.row].insert_str(idx, &unit);
+            self.col += unit.chars().count();
+        }
+    }
+
+    /// one level of indentation as used by the text, tabs or a number of spaces
+    fn indent_unit(&self) -> String {
+        if self.lines.iter().any(|line| line.starts_with('\t')) {
+            return "\t".to_string();
+        }
+        let width = self
+            .lines
+            .iter()
+            .map(|line| line.chars().take_while(|c| *c == ' ').count())
+            .filter(|n| *n > 0 && is_indent_width(*n))
+            .min()
+            .unwrap_or(TAB_WIDTH);
+        " ".repeat(width)
+    }
+
+    /// rows touched by the selection, or the cursor row
+    fn selected_rows(&self) -> std::ops::RangeInclusive<usize> {
+        match self.selection() {
+            Some((start, end)) => start.0..=end.0,
+            None => self.row..=self.row,
+        }
+    }
+
+    fn indent(&mut self) {
+        self.record(EditKind::Other);
+        let unit = self.indent_unit();
+        let rows = self.selected_rows();
+        if self
+            .selection()
+            .is_some_and(|(start, end)| start.0 != end.0)
+        {
+            for row in rows {
+                self.lines[row].insert_str(0, &unit);
+            }
+            let shift = unit.chars().count();
+            self.col += shift;
+            if let Some(anchor) = &mut self.anchor {
+                anchor.1 += shift;
+            }
+            return;
+        }
+        self.remove_selection();
+        if unit == "\t" {
+            self.insert_raw('\t');
+        } else {
+            // spaces up to the next indentation stop
+            let width = unit.len();
+            for _ in 0..width - self.col % width {
+                self.insert_raw(' ');
+            }
+        }
+    }
+
+    fn dedent(&mut self) {
+        self.record(EditKind::Other);
+        for row in self.selected_rows() {
+            self.dedent_line(row);
+        }
+    }
+
+    /// removes one level of indentation from a line and keeps the cursor on the same text
+    fn dedent_line(&mut self, row: usize) {
+        let unit = self.indent_unit();
+        let line = &self.lines[row];
+        let removed = if line.starts_with('\t') {
+            1
+        } else {
+            line.chars()
+                .take_while(|c| *c == ' ')
+                .count()
+                .min(unit.len())
+        };
+        self.lines[row].drain(..removed);
+        if self.row == row {
+            self.col = self.col.saturating_sub(removed);
+        }
+        if let Some(anchor) = &mut self.anchor
+            && anchor.0 == row
+        {
+            anchor.1 = anchor.1.saturating_sub(removed);
+        }
+    }
+
+    fn backspace(&mut self) {
+        self.record(EditKind::Other);
+        if self.remove_selection() {
+            return;
+        }
+        let before = self.before_cursor();
+        if self.line_numbers && self.col > 0 && before.chars().all(|c| c == ' ') {
+            // inside leading spaces, go back to the previous indentation stop
+            let width = self.indent_unit().len();
+            let remove = match self.col % width {
+                0 => width,
+                rest => rest,
+            };
+            let start = self.col - remove.min(self.col);
+            self.lines[self.row].drain(start..self.col);
+            self.col = start;
+        } else if self.col > 0 {
+            let idx = self.byte_idx(self.row, self.col - 1);
+            self.lines[self.row].remove(idx);
+            self.col -= 1;
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len();
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn delete(&mut self) {
+        self.record(EditKind::Other);
+        if self.remove_selection() {
+            return;
+        }
+        if self.col < self.line_len() {
+            let idx = self.byte_idx(self.row, self.col);
+            self.lines[self.row].remove(idx);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn move_left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len();
+        }
+    }
+
+    fn move_right(&mut self) {
+        if self.col < self.line_len() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    /// jumps over the word next to the cursor
+    fn move_word(&mut self, forward: bool) {
+        let chars: Vec<char> = self.lines[self.row].chars().collect();
+        if forward && self.col >= chars.len() || !forward && self.col == 0 {
+            if forward {
+                self.move_right();
+            } else {
+                self.move_left();
+            }
+            return;
+        }
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        if forward {
+            let mut col = self.col;
+            while col < chars.len() && !is_word(chars[col]) {
+                col += 1;
+            }
+            while col < chars.len() && is_word(chars[col]) {
+                col += 1;
+            }
+            self.col = col;
+        } else {
+            let mut col = self.col;
+            while col > 0 && !is_word(chars[col - 1]) {
+                col -= 1;
+            }
+            while col > 0 && is_word(chars[col - 1]) {
+                col -= 1;
+            }
+            self.col = col;
+        }
+    }
+
+    fn move_vertically(&mut self, lines: isize) {
+        self.row = self
+            .row
+            .saturating_add_signed(lines)
+            .min(self.lines.len() - 1);
+        self.col = self.col.min(self.line_len());
+    }
+
+    /// home goes to the first non-blank character, pressed again to the start of the line
+    fn move_home(&mut self) {
+        let indent = self.lines[self.row]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        self.col = if self.col == indent { 0 } else { indent };
+    }
+
+    fn move_cursor(&mut self, code: KeyCode, ctrl: bool, shift: bool) {
+        if shift {
+            self.anchor.get_or_insert((self.row, self.col));
+        } else {
+            self.anchor = None;
+        }
+        let page = self.height.max(1) as isize;
+        match code {
+            KeyCode::Left if ctrl => self.move_word(false),
+            KeyCode::Right if ctrl => self.move_word(true),
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Up => self.move_vertically(-1),
+            KeyCode::Down => self.move_vertically(1),
+            KeyCode::PageUp => self.move_vertically(-page),
+            KeyCode::PageDown => self.move_vertically(page),
+            KeyCode::Home if ctrl => (self.row, self.col) = (0, 0),
+            KeyCode::End if ctrl => self.end_of_text(),
+            KeyCode::Home => self.move_home(),
+            KeyCode::End => self.col = self.line_len(),
+            _ => {}
+        }
+        self.last_edit = None;
+    }
+
+    /// handles a key press, returns false if the key is not used by the editor
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Char('z') if ctrl => self.undo(),
+            KeyCode::Char('y') if ctrl => self.redo(),
+            KeyCode::Char('a') if ctrl => self.select_all(),
+            KeyCode::Char(c) if !ctrl => self.insert_char(c),
+            KeyCode::Enter if self.multiline => self.newline(),
+            KeyCode::Tab if self.line_numbers => self.indent(),
+            KeyCode::BackTab if self.line_numbers => self.dedent(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown
+                if !self.multiline || ctrl =>
+            {
+                return false;
+            }
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::PageUp
+            | KeyCode::PageDown
+            | KeyCode::Home
+            | KeyCode::End => self.move_cursor(key.code, ctrl, shift),
+            _ => return false,
+        }
+        true
+    }
+
+    /// draws the text inside a block and places the terminal cursor if focused.
+    /// with a highlighter and a language the text is syntax highlighted.
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        block: Block,
+        focused: bool,
+        highlight: Option<(&Highlighter, &str)>,
+    ) {
+        let inner = block.inner(area);
+        let height = inner.height.max(1) as usize;
+        self.height = height;
+        if self.row < self.scroll {
+            self.scroll = self.row;
+        } else if self.row >= self.scroll + height {
+            self.scroll = self.row + 1 - height;
+        }
+
+        let gutter = if self.line_numbers {
+            self.lines.len().to_string().len() + 1
+        } else {
+            0
+        };
+        let width = (inner.width as usize).saturating_sub(gutter).max(1);
+        let cursor_x = display_width(self.before_cursor());
+        let horizontal = cursor_x.saturating_sub(width - 1);
+
+        let highlighted =
+            highlight.map(
+                |(highlighter, lang)| match highlighter.highlight(&self.text(), lang) {
+                    Ok(text) | Err(text) => text,
+                },
+            );
+        let selection = self.selection();
+        let selected_style = Style::default().bg(Color::Rgb(80, 73, 69));
+
+        let text: Vec<Line> = (self.scroll..self.lines.len().min(self.scroll + height))
+            .map(|row| {
+                // every character with its style, before tabs are expanded
+                let styles: Vec<Style> = highlighted
+                    .as_ref()
+                    .and_then(|text| text.lines.get(row))
+                    .map(|line| {
+                        line.spans
+                            .iter()
+                            .flat_map(|span| {
+                                let style = line.style.patch(span.style);
+                                span.content.chars().map(move |_| style)
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let is_selected = |col: usize| {
+                    selection.is_some_and(|(start, end)| start <= (row, col) && (row, col) < end)
+                };
+
+                let mut spans = Vec::new();
+                if self.line_numbers {
+                    spans.push(Span::styled(
+                        format!("{:>w$} ", row + 1, w = gutter - 1),
+                        Style::default().fg(Color::Rgb(102, 92, 84)),
+                    ));
+                }
+                let mut x = 0;
+                let chars: Vec<char> = self.lines[row].chars().collect();
+                for (col, c) in chars.iter().enumerate() {
+                    let mut style = styles.get(col).copied().unwrap_or_default();
+                    if is_selected(col) {
+                        style = style.patch(selected_style);
+                    }
+                    let cells = if *c == '\t' {
+                        TAB_WIDTH - x % TAB_WIDTH
+                    } else {
+                        1
+                    };
+                    for cell in x..x + cells {
+                        if cell >= horizontal && cell < horizontal + width {
+                            let shown = if *c == '\t' { ' ' } else { *c };
+                            spans.push(Span::styled(shown.to_string(), style));
+                        }
+                    }
+                    x += cells;
+                }
+                // show that the line break is selected too
+                if is_selected(chars.len()) && x >= horizontal && x < horizontal + width {
+                    spans.push(Span::styled(" ", selected_style));
+                }
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(text).block(block), area);
+
+        if focused {
+            frame.set_cursor_position((
+                inner.x + (gutter + cursor_x - horizontal) as u16,
+                inner.y + (self.row - self.scroll) as u16,
+            ));
+        }
+    }
+}
+
+fn split_lines(text: &str) -> Vec<String> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect()
+}
+
+/// indentation widths that are usually one level of code indentation
+fn is_indent_width(width: usize) -> bool {
+    (2..=8).contains(&width)
+}
+
+/// columns the text takes on screen with tabs expanded
+fn display_width(text: &str) -> usize {
+    text.chars().fold(0, |x, c| {
+        if c == '\t' {
+            x + TAB_WIDTH - x % TAB_WIDTH
+        } else {
+            x + 1
+        }
+    })
+}
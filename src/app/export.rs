@@ -0,0 +1,425 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use syntect::{highlighting::Color as ThemeColor, html::highlighted_html_for_string};
+
+use crate::app::{SaveSnippet, highlight::Highlighter};
+
+/// snippets grouped under a language or tag name, groups sorted by name
+fn groups<'a>(
+    snippets: &[&'a SaveSnippet],
+    by_tag: bool,
+    highlighter: &Highlighter,
+) -> BTreeMap<String, Vec<&'a SaveSnippet>> {
+    let mut groups: BTreeMap<String, Vec<&SaveSnippet>> = BTreeMap::new();
+    for snippet in snippets {
+        if !by_tag {
+            let name = highlighter.language_name(&snippet.lang);
+            groups.entry(name).or_default().push(snippet);
+        } else if snippet.tags.is_empty() {
+            groups
+                .entry("untagged".to_string())
+                .or_default()
+                .push(snippet);
+        } else {
+            for tag in &snippet.tags {
+                groups.entry(format!("#{tag}")).or_default().push(snippet);
+            }
+        }
+    }
+    groups
+}
+
+/// one Markdown document with a section per language or tag
+pub fn markdown(snippets: &[&SaveSnippet], by_tag: bool, highlighter: &Highlighter) -> String {
+    let mut out = String::from("# Snippets\n");
+    for (group, snippets) in groups(snippets, by_tag, highlighter) {
+        out.push_str(&format!("\n## {group}\n"));
+        for snippet in snippets {
+            out.push_str(&format!("\n### {}\n\n", snippet.title));
+            if !snippet.desc.is_empty() {
+                out.push_str(&format!("{}\n\n", snippet.desc));
+            }
+            if !snippet.tags.is_empty() {
+                out.push_str(&format!("Tags: {}\n\n", hashtags(&snippet.tags)));
+            }
+            // the fence has to be longer than any backtick run in the code
+            let longest = snippet
+                .code
+                .split(|c| c != '`')
+                .map(str::len)
+                .max()
+                .unwrap_or(0);
+            let fence = "`".repeat(longest.max(2) + 1);
+            out.push_str(&format!(
+                "{fence}{}\n{}\n{fence}\n",
+                snippet.lang, snippet.code
+            ));
+        }
+    }
+    out
+}
+
+/// a standalone HTML page with highlighted code and a table of contents
+pub fn html(snippets: &[&SaveSnippet], by_tag: bool, highlighter: &Highlighter) -> String {
+    let groups = groups(snippets, by_tag, highlighter);
+    let anchors = unique_slugs(groups.keys().map(String::as_str));
+    let mut body = String::from("<h1>Snippets</h1>\n<nav><ul>\n");
+    for (group, anchor) in groups.keys().zip(&anchors) {
+        body.push_str(&format!(
+            "<li><a href=\"#{anchor}\">{}</a></li>\n",
+            escape(group)
+        ));
+    }
+    body.push_str("</ul></nav>\n");
+    for ((group, snippets), anchor) in groups.iter().zip(&anchors) {
+        body.push_str(&format!(
+            "<section id=\"{anchor}\">\n<h2>{}</h2>\n",
+            escape(group)
+        ));
+        for snippet in snippets {
+            body.push_str(&snippet_html(snippet, highlighter, None));
+        }
+        body.push_str("</section>\n");
+    }
+    page(
+        "Snippets",
+        &format!("<style>\n{}</style>", css(highlighter)),
+        &body,
+    )
+}
+
+/// unique file names without extension, e.g. "docker-cleanup" and "docker-cleanup-2"
+pub(super) fn file_names(snippets: &[&SaveSnippet]) -> Vec<String> {
+    unique_slugs(snippets.iter().map(|snippet| snippet.title.as_str()))
+}
+
+/// slugs with a number added to repeated ones, skipping numbers another text already took
+fn unique_slugs<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut used: HashSet<String> = HashSet::new();
+    texts
+        .into_iter()
+        .map(|text| {
+            let base = slug(text);
+            let mut name = base.clone();
+            let mut n = 1;
+            while used.contains(&name) {
+                n += 1;
+                name = format!("{base}-{n}");
+            }
+            used.insert(name.clone());
+            name
+        })
+        .collect()
+}
+
+/// writes files named by their relative path into a directory, for formats with several files
+pub fn write_files(dir: &Path, files: &BTreeMap<String, String>) -> Result<(), String> {
+    for (name, text) in files {
+        let path = dir.join(name);
+        let parent = path.parent().unwrap_or(dir);
+        fs::create_dir_all(parent).map_err(|err| format!("{}: {err}", parent.display()))?;
+        fs::write(&path, text).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// a browsable site with an index page, one page per snippet and per tag, and client-side search
+pub fn site(
+    snippets: &[&SaveSnippet],
+    dir: &Path,
+    highlighter: &Highlighter,
+) -> Result<usize, String> {
+    let write = |path: &Path, text: &str| {
+        fs::write(path, text).map_err(|err| format!("{}: {err}", path.display()))
+    };
+    for sub in ["snippets", "tags"] {
+        fs::create_dir_all(dir.join(sub))
+            .map_err(|err| format!("{}: {err}", dir.join(sub).display()))?;
+    }
+    write(&dir.join("style.css"), &css(highlighter))?;
+
+    let pages = file_names(snippets);
+    let mut tags: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, snippet) in snippets.iter().enumerate() {
+        for tag in &snippet.tags {
+            tags.entry(tag).or_default().push(i);
+        }
+    }
+    // "c" and "c++" have the same slug, but need their own pages
+    let tag_pages: BTreeMap<&str, String> = tags
+        .keys()
+        .copied()
+        .zip(unique_slugs(tags.keys().copied()))
+        .collect();
+
+    for (snippet, page_name) in snippets.iter().zip(&pages) {
+        let body = format!(
+            "<p><a href=\"../index.html\">← all snippets</a></p>\n{}",
+            snippet_html(snippet, highlighter, Some(&tag_pages))
+        );
+        write(
+            &dir.join("snippets").join(format!("{page_name}.html")),
+            &page(
+                &snippet.title,
+                "<link rel=\"stylesheet\" href=\"../style.css\">",
+                &body,
+            ),
+        )?;
+    }
+
+    for (tag, indices) in &tags {
+        let mut body = format!(
+            "<p><a href=\"../index.html\">← all snippets</a></p>\n<h1>#{}</h1>\n<ul class=\"snippets\">\n",
+            escape(tag)
+        );
+        for i in indices {
+            body.push_str(&list_item(
+                snippets[*i],
+                &pages[*i],
+                "../snippets/",
+                highlighter,
+            ));
+        }
+        body.push_str("</ul>\n");
+        write(
+            &dir.join("tags").join(format!("{}.html", tag_pages[tag])),
+            &page(
+                &format!("#{tag}"),
+                "<link rel=\"stylesheet\" href=\"../style.css\">",
+                &body,
+            ),
+        )?;
+    }
+
+    let mut body = String::from(
+        "<h1>Snippets</h1>\n<input id=\"search\" type=\"search\" placeholder=\"Search, e.g. docker tag:ops lang:sh\" autofocus>\n",
+    );
+    if !tags.is_empty() {
+        body.push_str("<p class=\"tags\">");
+        for (tag, page_name) in &tag_pages {
+            body.push_str(&format!(
+                "<a href=\"tags/{page_name}.html\">#{}</a> ",
+                escape(tag)
+            ));
+        }
+        body.push_str("</p>\n");
+    }
+    body.push_str("<ul class=\"snippets\" id=\"results\">\n");
+    for (snippet, page_name) in snippets.iter().zip(&pages) {
+        body.push_str(&list_item(snippet, page_name, "snippets/", highlighter));
+    }
+    body.push_str("</ul>\n<p id=\"empty\" hidden>No snippets match.</p>\n");
+
+    let index: Vec<serde_json::Value> = snippets
+        .iter()
+        .map(|snippet| {
+            serde_json::json!({
+                "text": format!("{}\n{}\n{}", snippet.title, snippet.desc, snippet.code).to_lowercase(),
+                "tags": snippet.tags,
+                "lang": snippet.lang.to_lowercase(),
+            })
+        })
+        .collect();
+    let index = serde_json::to_string(&index)
+        .expect("failed to serialize search index")
+        .replace("</", "<\\/");
+    body.push_str(&format!(
+        "<script>\nconst INDEX = {index};\n{SEARCH_JS}</script>\n"
+    ));
+    write(
+        &dir.join("index.html"),
+        &page(
+            "Snippets",
+            "<link rel=\"stylesheet\" href=\"style.css\">",
+            &body,
+        ),
+    )?;
+    Ok(snippets.len())
+}
+
+/// filters the index list with the same query syntax as the app
+const SEARCH_JS: &str = r##"const input = document.getElementById("search");
+const items = document.querySelectorAll("#results li");
+input.addEventListener("input", () => {
+  const words = input.value.toLowerCase().split(/\s+/).filter(w => w);
+  let shown = 0;
+  INDEX.forEach((entry, i) => {
+    const match = words.every(word => {
+      if (word.startsWith("tag:")) return entry.tags.includes(word.slice(4));
+      if (word.startsWith("#")) return entry.tags.includes(word.slice(1));
+      if (word.startsWith("lang:")) return entry.lang === word.slice(5);
+      return entry.text.includes(word) || entry.tags.some(tag => tag.includes(word));
+    });
+    items[i].hidden = !match;
+    if (match) shown++;
+  });
+  document.getElementById("empty").hidden = shown > 0;
+});
+"##;
+
+fn list_item(
+    snippet: &SaveSnippet,
+    page_name: &str,
+    prefix: &str,
+    highlighter: &Highlighter,
+) -> String {
+    let desc = snippet.desc.lines().next().unwrap_or_default();
+    format!(
+        "<li><a href=\"{prefix}{page_name}.html\">{}</a> <span class=\"lang\">{}</span> <span class=\"tags\">{}</span><br><span class=\"desc\">{}</span></li>\n",
+        escape(&snippet.title),
+        escape(&highlighter.language_name(&snippet.lang)),
+        escape(&hashtags(&snippet.tags)),
+        escape(desc),
+    )
+}
+
+/// title, description, tags and highlighted code of one snippet.
+/// with the names of the tag pages the tags link to them.
+fn snippet_html(
+    snippet: &SaveSnippet,
+    highlighter: &Highlighter,
+    tag_pages: Option<&BTreeMap<&str, String>>,
+) -> String {
+    let mut out = format!(
+        "<article id=\"{}\">\n<h3>{}</h3>\n",
+        slug(&snippet.title),
+        escape(&snippet.title)
+    );
+    if !snippet.desc.is_empty() {
+        out.push_str(&format!(
+            "<p class=\"desc\">{}</p>\n",
+            escape(&snippet.desc)
+        ));
+    }
+    if !snippet.tags.is_empty() {
+        let tags: Vec<String> = snippet
+            .tags
+            .iter()
+            .map(
+                |tag| match tag_pages.and_then(|pages| pages.get(tag.as_str())) {
+                    Some(page_name) => {
+                        format!("<a href=\"../tags/{page_name}.html\">#{}</a>", escape(tag))
+                    }
+                    None => format!("#{}", escape(tag)),
+                },
+            )
+            .collect();
+        out.push_str(&format!("<p class=\"tags\">{}</p>\n", tags.join(" ")));
+    }
+    let syntax = highlighter
+        .ps
+        .find_syntax_by_extension(&snippet.lang)
+        .unwrap_or_else(|| highlighter.ps.find_syntax_plain_text());
+    let code =
+        highlighted_html_for_string(&snippet.code, &highlighter.ps, syntax, highlighter.theme())
+            .unwrap_or_else(|_| format!("<pre>{}</pre>", escape(&snippet.code)));
+    out.push_str(&code);
+    out.push_str("</article>\n");
+    out
+}
+
+fn page(title: &str, head: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n{head}\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+/// page colors taken from the highlighting theme so code blocks blend in
+fn css(highlighter: &Highlighter) -> String {
+    let settings = &highlighter.theme().settings;
+    let hex = |color: Option<ThemeColor>, fallback: &str| {
+        color
+            .map(|c| format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b))
+            .unwrap_or_else(|| fallback.to_string())
+    };
+    let background = hex(settings.background, "#ffffff");
+    let foreground = hex(settings.foreground, "#222222");
+    let accent = hex(settings.caret, &foreground);
+    format!(
+        "body {{ background: {background}; color: {foreground}; font-family: sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; }}
+a {{ color: {accent}; }}
+pre {{ padding: 0.8rem; overflow-x: auto; border-radius: 4px; border: 1px solid {foreground}33; }}
+article {{ margin-bottom: 2rem; }}
+.desc, .lang, .tags {{ opacity: 0.75; }}
+.snippets li {{ margin-bottom: 0.6rem; }}
+#search {{ width: 100%; padding: 0.5rem; font-size: 1rem; background: {background}; color: {foreground}; border: 1px solid {foreground}66; }}
+"
+    )
+}
+
+fn hashtags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("#{tag}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// lowercase file name or anchor made of letters, digits and dashes
+pub(super) fn slug(text: &str) -> String {
+    let slug = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "snippet".to_string()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titled(title: &str) -> SaveSnippet {
+        let mut snippet = SaveSnippet::new("echo", "sh");
+        snippet.title = title.to_string();
+        snippet
+    }
+
+    #[test]
+    fn file_names_are_unique() {
+        let snippets = [
+            titled("Deploy 2"),
+            titled("Deploy"),
+            titled("Deploy"),
+            titled(""),
+        ];
+        let snippets: Vec<&SaveSnippet> = snippets.iter().collect();
+        assert_eq!(
+            file_names(&snippets),
+            ["deploy-2", "deploy", "deploy-3", "snippet"]
+        );
+    }
+
+    #[test]
+    fn tags_with_the_same_slug_get_their_own_pages() {
+        let dir = std::env::temp_dir().join(format!("codecache-site-{}", std::process::id()));
+        let mut c = titled("Hello");
+        c.tags = vec!["c".to_string()];
+        let mut cpp = titled("Hello");
+        cpp.tags = vec!["c++".to_string()];
+
+        site(&[&c, &cpp], &dir, &Highlighter::new()).unwrap();
+        let page = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert!(page("tags/c.html").contains("#c<"));
+        assert!(page("tags/c-2.html").contains("#c++<"));
+        assert!(page("snippets/hello-2.html").contains("../tags/c-2.html"));
+        assert!(page("index.html").contains("tags/c-2.html"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,468 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Constraint::{Fill, Length, Percentage},
+    prelude::*,
+    widgets::{Block, Clear, Paragraph},
+};
+
+use crate::app::{
+    CodeCache, SaveSnippet, View, editor::Editor, highlight::Highlighter, history, undo::Change,
+};
+
+/// what submitting the form does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormTarget {
+    /// add a new snippet
+    New,
+    /// change the snippet at this index
+    Edit(usize),
+    /// file the inbox item at this index into the library
+    Promote(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Desc,
+    Lang,
+    Tags,
+    Trigger,
+    Code,
+}
+
+const FIELDS: [Field; 6] = [
+    Field::Title,
+    Field::Desc,
+    Field::Lang,
+    Field::Tags,
+    Field::Trigger,
+    Field::Code,
+];
+
+/// form to create or edit all fields of a snippet at once
+pub struct SnippetForm {
+    pub target: FormTarget,
+    pub title: Editor,
+    pub desc: Editor,
+    pub lang: Editor,
+    pub tags: Editor,
+    pub trigger: Editor,
+    pub code: Editor,
+    /// `{{name}}` in the code is a placeholder, toggled with ctrl+t
+    pub template: bool,
+    focus: Field,
+    /// languages matching the language field, as (name, extension)
+    suggestions: Vec<(String, String)>,
+    suggestion: usize,
+    error: Option<String>,
+}
+
+impl SnippetForm {
+    pub fn new(target: FormTarget, snippet: &SaveSnippet) -> Self {
+        let mut form = Self {
+            target,
+            title: Editor::single_line(&snippet.title),
+            desc: Editor::multi_line(&snippet.desc),
+            lang: Editor::single_line(&snippet.lang),
+            tags: Editor::single_line(&snippet.tags.join(", ")),
+            trigger: Editor::single_line(snippet.trigger.as_deref().unwrap_or_default()),
+            code: Editor::code(&snippet.code),
+            template: snippet.template,
+            // quick fixes to existing snippets usually start in the code
+            focus: match target {
+                FormTarget::Edit(_) => Field::Code,
+                _ => Field::Title,
+            },
+            suggestions: Vec::new(),
+            suggestion: 0,
+            error: None,
+        };
+        for editor in [
+            &mut form.title,
+            &mut form.lang,
+            &mut form.tags,
+            &mut form.trigger,
+        ] {
+            editor.end_of_text();
+        }
+        form
+    }
+
+    fn focused(&mut self) -> &mut Editor {
+        match self.focus {
+            Field::Title => &mut self.title,
+            Field::Desc => &mut self.desc,
+            Field::Lang => &mut self.lang,
+            Field::Tags => &mut self.tags,
+            Field::Trigger => &mut self.trigger,
+            Field::Code => &mut self.code,
+        }
+    }
+
+    fn move_focus(&mut self, forward: bool) {
+        if self.focus == Field::Lang {
+            self.accept_suggestion();
+        }
+        let pos = FIELDS.iter().position(|f| *f == self.focus).unwrap_or(0);
+        let next = if forward {
+            (pos + 1) % FIELDS.len()
+        } else {
+            (pos + FIELDS.len() - 1) % FIELDS.len()
+        };
+        self.focus = FIELDS[next];
+    }
+
+    fn update_suggestions(&mut self, highlighter: &Highlighter) {
+        let query = self.lang.text().trim().to_lowercase();
+        self.suggestions = highlighter
+            .languages()
+            .into_iter()
+            .filter(|(name, ext)| {
+                query.is_empty() || ext.starts_with(&query) || name.to_lowercase().contains(&query)
+            })
+            .collect();
+        // an exact extension match is the most likely choice
+        if let Some(pos) = self.suggestions.iter().position(|(_, ext)| *ext == query) {
+            let exact = self.suggestions.remove(pos);
+            self.suggestions.insert(0, exact);
+        }
+        self.suggestion = 0;
+    }
+
+    fn accept_suggestion(&mut self) {
+        if let Some((_, ext)) = self.suggestions.get(self.suggestion) {
+            let ext = ext.clone();
+            self.lang.set_text(&ext);
+        }
+    }
+
+    /// turns the form into a snippet, or explains what is missing
+    fn validate(&self, highlighter: &Highlighter) -> Result<SaveSnippet, String> {
+        let title = self.title.text().trim().to_string();
+        let lang = self.lang.text().trim().to_string();
+        let code = self.code.text();
+        if title.is_empty() {
+            return Err("Title can't be empty".to_string());
+        }
+        if lang.is_empty() {
+            return Err("Pick a language, \"txt\" for plain text".to_string());
+        }
+        if !highlighter.knows(&lang) {
+            return Err(format!(
+                "Unknown language \"{lang}\", use a file extension like \"rs\""
+            ));
+        }
+        if code.trim().is_empty() {
+            return Err("Code can't be empty".to_string());
+        }
+        let mut snippet = SaveSnippet::new(code, lang);
+        snippet.title = title;
+        snippet.desc = self.desc.text().trim_end().to_string();
+        snippet.tags = parse_tags(&self.tags.text());
+        let trigger = self.trigger.text().trim().to_string();
+        if trigger.contains(char::is_whitespace) {
+            return Err("Trigger can't contain spaces".to_string());
+        }
+        snippet.trigger = (!trigger.is_empty()).then_some(trigger);
+        snippet.template = self.template;
+        Ok(snippet)
+    }
+}
+
+/// splits comma or space separated tags, dropping empty ones and duplicates
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split([',', ' ']) {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+impl CodeCache {
+    pub(super) fn open_form(&mut self, target: FormTarget, snippet: &SaveSnippet) {
+        let mut form = SnippetForm::new(target, snippet);
+        form.update_suggestions(&self.highlighter);
+        self.form = Some(form);
+    }
+
+    pub(super) fn handle_form_key(&mut self, key: KeyEvent) {
+        let Some(form) = &mut self.form else {
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc if form.focused().has_selection() => form.focused().clear_selection(),
+            KeyCode::Esc => {
+                self.form = None;
+                self.notify("Cancelled, nothing was changed");
+            }
+            KeyCode::Char('s') if ctrl => self.submit_form(),
+            KeyCode::Char('t') if ctrl => form.template = !form.template,
+            KeyCode::Char('c') | KeyCode::Char('x') if ctrl => {
+                if let Some(text) = form.focused().selected_text() {
+                    if key.code == KeyCode::Char('x') {
+                        form.focused().delete_selection();
+                    }
+                    self.copy(&text);
+                }
+            }
+            KeyCode::Char('v') if ctrl => match self.clipboard.get_text() {
+                Ok(text) => form.focused().insert_str(&text),
+                Err(err) => form.error = Some(format!("Failed to access clipboard: {err}")),
+            },
+            // tab indents inside the code, ctrl+↑/↓ still leaves it
+            KeyCode::Tab | KeyCode::BackTab if form.focus == Field::Code => {
+                form.code.handle_key(key);
+            }
+            KeyCode::Tab => form.move_focus(true),
+            KeyCode::BackTab => form.move_focus(false),
+            KeyCode::Down if ctrl => form.move_focus(true),
+            KeyCode::Up if ctrl => form.move_focus(false),
+            KeyCode::Down if form.focus == Field::Lang => {
+                if form.suggestion + 1 < form.suggestions.len() {
+                    form.suggestion += 1;
+                }
+            }
+            KeyCode::Up if form.focus == Field::Lang => {
+                form.suggestion = form.suggestion.saturating_sub(1);
+            }
+            // enter in single line fields moves on, multi line fields take it as a newline
+            KeyCode::Enter
+                if matches!(
+                    form.focus,
+                    Field::Title | Field::Lang | Field::Tags | Field::Trigger
+                ) =>
+            {
+                form.move_focus(true);
+            }
+            _ => {
+                form.error = None;
+                form.focused().handle_key(key);
+                if form.focus == Field::Lang {
+                    form.update_suggestions(&self.highlighter);
+                }
+            }
+        }
+    }
+
+    /// pasting while the form is open goes into the focused field
+    pub(super) fn paste_into_form(&mut self, text: &str) {
+        if let Some(form) = &mut self.form {
+            form.focused().insert_str(text);
+        }
+    }
+
+    fn submit_form(&mut self) {
+        let Some(form) = &mut self.form else {
+            return;
+        };
+        if form.focus == Field::Lang {
+            form.accept_suggestion();
+        }
+        match form.validate(&self.highlighter) {
+            Ok(_) => {
+                if !self.check_secrets() {
+                    self.save_form();
+                }
+            }
+            Err(err) => form.error = Some(err),
+        }
+    }
+
+    /// writes the form into the library, called once secrets have been dealt with
+    pub(super) fn save_form(&mut self) {
+        let Some(form) = self.form.take() else {
+            return;
+        };
+        let Ok(values) = form.validate(&self.highlighter) else {
+            self.form = Some(form);
+            return;
+        };
+        match form.target {
+            FormTarget::New | FormTarget::Promote(_) => {
+                let snippet = values;
+                let idx = self.save_snippets.len();
+                self.change(Change::Insert { idx, snippet });
+                self.view = View::Library;
+                self.notify("Snippet added");
+                if let FormTarget::Promote(inbox_idx) = form.target {
+                    self.remove_inbox_item(inbox_idx);
+                }
+            }
+            FormTarget::Edit(idx) => {
+                let Some(before) = self.save_snippets.get(idx).cloned() else {
+                    return;
+                };
+                let mut after = before.clone();
+                after.title = values.title;
+                after.desc = values.desc;
+                after.lang = values.lang;
+                after.tags = values.tags;
+                after.trigger = values.trigger;
+                after.code = values.code;
+                after.template = values.template;
+                if after != before {
+                    history::revise(&before, &mut after);
+                    self.change(Change::update(idx, before, after));
+                    self.notify("Snippet saved");
+                }
+            }
+        }
+        self.refresh_cards();
+    }
+
+    pub(super) fn draw_form(&mut self, frame: &mut Frame) {
+        let Some(form) = &mut self.form else {
+            return;
+        };
+        let [_, area, _] =
+            Layout::horizontal([Fill(1), Percentage(80), Fill(1)]).areas(frame.area());
+        let [_, area, _] = Layout::vertical([Length(1), Fill(1), Length(1)]).areas(area);
+        frame.render_widget(Clear, area);
+
+        let title = match form.target {
+            FormTarget::New => "New snippet",
+            FormTarget::Edit(_) => "Edit snippet",
+            FormTarget::Promote(_) => "Add clip to library",
+        };
+        let outer = Block::bordered()
+            .title(title)
+            .title_alignment(Alignment::Center)
+            .border_style(Style::default().fg(Color::LightBlue));
+        let inner = outer.inner(area);
+        frame.render_widget(outer, area);
+
+        let [
+            title_area,
+            desc_area,
+            lang_area,
+            tags_area,
+            code_area,
+            status_area,
+        ] = Layout::vertical([
+            Length(3),
+            Length(5),
+            Length(3),
+            Length(3),
+            Fill(1),
+            Length(1),
+        ])
+        .areas(inner);
+
+        let field_block = |name: &'static str, focused: bool| {
+            let color = if focused {
+                Color::Rgb(250, 189, 47)
+            } else {
+                Color::Rgb(124, 111, 100)
+            };
+            Block::bordered()
+                .title(name)
+                .border_style(Style::default().fg(color))
+        };
+
+        let focus = form.focus;
+        let lang = form.lang.text().trim().to_string();
+        form.title.render(
+            frame,
+            title_area,
+            field_block("Title", focus == Field::Title),
+            focus == Field::Title,
+            None,
+        );
+        form.desc.render(
+            frame,
+            desc_area,
+            field_block("Description", focus == Field::Desc),
+            focus == Field::Desc,
+            None,
+        );
+
+        let [lang_input_area, suggestion_area] =
+            Layout::horizontal([Length(20), Fill(1)]).areas(lang_area);
+        form.lang.render(
+            frame,
+            lang_input_area,
+            field_block("Language", focus == Field::Lang),
+            focus == Field::Lang,
+            None,
+        );
+        let suggestions: Vec<Span> = if focus == Field::Lang {
+            form.suggestions
+                .iter()
+                .enumerate()
+                .skip(form.suggestion.saturating_sub(2))
+                .take(8)
+                .map(|(i, (name, ext))| {
+                    let style = if i == form.suggestion {
+                        Style::default()
+                            .bg(Color::Rgb(254, 128, 25))
+                            .fg(Color::Rgb(28, 28, 32))
+                    } else {
+                        Style::default().fg(Color::Rgb(120, 112, 108))
+                    };
+                    Span::styled(format!(" {name} ({ext}) "), style)
+                })
+                .collect()
+        } else {
+            vec![Span::styled(
+                super::language::get_lang(form.lang.text().trim()),
+                Style::default().fg(Color::Rgb(120, 112, 108)),
+            )]
+        };
+        frame.render_widget(
+            Paragraph::new(Line::from(suggestions))
+                .block(Block::new().padding(ratatui::widgets::Padding::new(1, 0, 1, 0))),
+            suggestion_area,
+        );
+
+        let [tags_area, trigger_area] = Layout::horizontal([Fill(1), Length(24)]).areas(tags_area);
+        form.tags.render(
+            frame,
+            tags_area,
+            field_block("Tags (comma separated)", focus == Field::Tags),
+            focus == Field::Tags,
+            None,
+        );
+        form.trigger.render(
+            frame,
+            trigger_area,
+            field_block("Trigger", focus == Field::Trigger),
+            focus == Field::Trigger,
+            None,
+        );
+        form.code.render(
+            frame,
+            code_area,
+            field_block(
+                if form.template {
+                    "Code (template, {{name}} is asked for on use)"
+                } else {
+                    "Code"
+                },
+                focus == Field::Code,
+            ),
+            focus == Field::Code,
+            Some((&self.highlighter, &lang)),
+        );
+
+        let status = match &form.error {
+            Some(err) => Line::styled(err.clone(), Style::default().fg(Color::Rgb(251, 73, 52))),
+            None => Line::styled(
+                match focus {
+                    Field::Code => {
+                        "tab/shift+tab: indent, shift+arrows: select, ctrl+z/y: undo/redo, ctrl+t: template, ctrl+↑: previous field, ctrl+s: save, esc: cancel"
+                    }
+                    Field::Lang => {
+                        "tab/shift+tab: next/previous field, ↑/↓: pick language, ctrl+s: save, esc: cancel"
+                    }
+                    _ => "tab/shift+tab: next/previous field, ctrl+s: save, esc: cancel",
+                },
+                Style::default().fg(Color::Cyan),
+            ),
+        };
+        frame.render_widget(status.alignment(Alignment::Center), status_area);
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::BTreeMap,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Constraint::{Fill, Percentage},
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState as FormatState},
+};
+use similar::TextDiff;
+
+use crate::app::{
+    CodeCache, SaveSnippet, View,
+    check::{has_rust_items, run_command},
+    diff::DiffView,
+    history,
+    template::{self, Part, Placeholder},
+    undo::Change,
+};
+
+/// formatters for languages the config does not name, they read the code from stdin
+/// and write the formatted code to stdout
+const DEFAULT_FORMATTERS: [(&str, &str); 18] = [
+    ("rs", "rustfmt --edition 2024"),
+    ("py", "black --quiet -"),
+    ("sh", "shfmt"),
+    ("bash", "shfmt"),
+    ("go", "gofmt"),
+    ("toml", "taplo fmt -"),
+    ("js", "prettier --stdin-filepath snippet.js"),
+    ("jsx", "prettier --stdin-filepath snippet.jsx"),
+    ("ts", "prettier --stdin-filepath snippet.ts"),
+    ("tsx", "prettier --stdin-filepath snippet.tsx"),
+    ("json", "prettier --stdin-filepath snippet.json"),
+    ("css", "prettier --stdin-filepath snippet.css"),
+    ("scss", "prettier --stdin-filepath snippet.scss"),
+    ("html", "prettier --stdin-filepath snippet.html"),
+    ("md", "prettier --stdin-filepath snippet.md"),
+    ("yml", "prettier --stdin-filepath snippet.yml"),
+    ("c", "clang-format --assume-filename snippet.c"),
+    ("cpp", "clang-format --assume-filename snippet.cpp"),
+];
+
+/// the configured formatters on top of the defaults
+pub fn formatters(config: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut formatters: BTreeMap<String, String> = DEFAULT_FORMATTERS
+        .iter()
+        .map(|(lang, formatter)| (lang.to_string(), formatter.to_string()))
+        .collect();
+    formatters.extend(config.clone());
+    formatters
+}
+
+/// formats code with the formatter of its language, None if there is none.
+/// placeholders are swapped for identifiers while formatting, so they don't trip up the parser.
+pub fn format_code(
+    code: &str,
+    lang: &str,
+    formatters: &BTreeMap<String, String>,
+) -> Option<Result<String, String>> {
+    let command = formatters.get(lang)?;
+    Some(run_formatter(command, code, lang))
+}
+
+fn run_formatter(command: &str, code: &str, lang: &str) -> Result<String, String> {
+    let args = shell_words::split(command)
+        .map_err(|err| format!("invalid formatter {command:?}: {err}"))?;
+    if args.is_empty() {
+        return Err(format!("no formatter for {lang}"));
+    }
+
+    let mut masked = String::new();
+    let mut placeholders: Vec<(String, String)> = Vec::new();
+    for part in template::parts(code) {
+        match part {
+            Part::Text(text) => masked.push_str(text),
+            part => {
+                let token = format!("__codecache_{}__", placeholders.len());
+                let original = match part {
+                    Part::Placeholder(Placeholder {
+                        name,
+                        default: Some(default),
+                    }) => format!("{{{{{name}:{default}}}}}"),
+                    Part::Placeholder(Placeholder { name, .. }) => format!("{{{{{name}}}}}"),
+                    _ => "\\{{".to_string(),
+                };
+                masked.push_str(&token);
+                placeholders.push((token, original));
+            }
+        }
+    }
+    // rustfmt only formats items, statements are formatted as the body of a function
+    let wrapped = lang == "rs" && !has_rust_items(&masked);
+    if wrapped {
+        masked = format!("fn snippet() {{\n{masked}\n}}\n");
+    }
+
+    let (success, stdout, stderr) = run_command(&args, &masked, true)?;
+    if !success {
+        let first = stderr.lines().find(|line| !line.trim().is_empty());
+        return Err(first.unwrap_or("formatter failed").trim().to_string());
+    }
+
+    let mut formatted = stdout;
+    if wrapped {
+        let lines: Vec<&str> = formatted.trim_end().lines().collect();
+        let in_literal = string_continuations(formatted.trim_end());
+        if lines.len() >= 2 {
+            // lines inside a multi-line string were not indented, they are part of the string
+            formatted = lines[1..lines.len() - 1]
+                .iter()
+                .zip(&in_literal[1..])
+                .map(|(line, &in_literal)| match in_literal {
+                    true => line,
+                    false => line.strip_prefix("    ").unwrap_or(line),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+    for (token, original) in &placeholders {
+        formatted = formatted.replace(token, original);
+    }
+    Ok(formatted.trim_end().to_string())
+}
+
+/// for each line of rust code, whether it starts inside a string literal
+fn string_continuations(code: &str) -> Vec<bool> {
+    enum State {
+        Code,
+        Str,
+        /// raw string closed by a quote and this many hashes
+        Raw(usize),
+        LineComment,
+        BlockComment(usize),
+    }
+    let ident = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    let chars: Vec<char> = code.chars().collect();
+    let mut lines = vec![false];
+    let mut state = State::Code;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match state {
+            _ if c == '\n' => {
+                if let State::LineComment = state {
+                    state = State::Code;
+                }
+                lines.push(matches!(state, State::Str | State::Raw(_)));
+            }
+            State::Code => match c {
+                '/' if next == Some('/') => state = State::LineComment,
+                '/' if next == Some('*') => {
+                    state = State::BlockComment(1);
+                    i += 1;
+                }
+                '"' => state = State::Str,
+                // r"…" and br"…", but not the end of a name like `bar`
+                'r' if !ident(chars[..i].last())
+                    || (chars[..i].last() == Some(&'b') && !ident(chars[..i - 1].last())) =>
+                {
+                    let hashes = chars[i + 1..].iter().take_while(|c| **c == '#').count();
+                    if chars.get(i + 1 + hashes) == Some(&'"') {
+                        state = State::Raw(hashes);
+                        i += 1 + hashes;
+                    }
+                }
+                // char literals, a quote without a closing one is a lifetime
+                '\'' if next == Some('\\') => {
+                    i += 2;
+                    while i < chars.len() && chars[i] != '\'' {
+                        i += 1;
+                    }
+                }
+                '\'' if chars.get(i + 2) == Some(&'\'') => i += 2,
+                _ => {}
+            },
+            State::Str => match c {
+                '\\' if next != Some('\n') => i += 1,
+                '"' => state = State::Code,
+                _ => {}
+            },
+            State::Raw(hashes) => {
+                if c == '"' && chars[i + 1..].iter().take_while(|c| **c == '#').count() >= hashes {
+                    state = State::Code;
+                    i += hashes;
+                }
+            }
+            State::LineComment => {}
+            State::BlockComment(depth) => {
+                if c == '/' && next == Some('*') {
+                    state = State::BlockComment(depth + 1);
+                    i += 1;
+                } else if c == '*' && next == Some('/') {
+                    state = match depth {
+                        1 => State::Code,
+                        depth => State::BlockComment(depth - 1),
+                    };
+                    i += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+    lines
+}
+
+/// replaces the code of a snippet with its formatted version, keeping the old one in its history
+pub fn apply(snippet: &SaveSnippet, code: String) -> SaveSnippet {
+    let mut after = snippet.clone();
+    after.code = code;
+    history::revise(snippet, &mut after);
+    after
+}
+
+/// unified diff of a snippet and its formatted code for `codecache format`
+pub fn unified_diff(snippet: &SaveSnippet, code: &str) -> String {
+    TextDiff::from_lines(&format!("{}\n", snippet.code), &format!("{code}\n"))
+        .unified_diff()
+        .header(&snippet.title, &format!("{} (formatted)", snippet.title))
+        .to_string()
+}
+
+/// a snippet whose formatted code differs from the current one
+pub struct FormatEntry {
+    idx: usize,
+    code: String,
+    selected: bool,
+}
+
+/// snippets being formatted on their own thread
+pub struct FormatJob {
+    results: Receiver<Vec<Formatted>>,
+}
+
+/// outcome of formatting one snippet, with the code it started from
+struct Formatted {
+    id: String,
+    before: String,
+    result: Option<Result<String, String>>,
+}
+
+/// preview of formatting changes before they are applied
+pub struct FormatPanel {
+    entries: Vec<FormatEntry>,
+    state: FormatState,
+    side_by_side: bool,
+    scroll: u16,
+}
+
+impl CodeCache {
+    /// starts formatting the given snippets, the changes are shown once all are done
+    pub(super) fn format_snippets(&mut self, indices: Vec<usize>) {
+        if self.format_job.is_some() {
+            self.notify("Still formatting, wait for it to finish");
+            return;
+        }
+        let snippets: Vec<(String, String, String)> = indices
+            .into_iter()
+            .filter_map(|idx| self.save_snippets.get(idx))
+            .filter(|snippet| !snippet.is_locked())
+            .map(|snippet| {
+                (
+                    snippet.id.clone(),
+                    snippet.code.clone(),
+                    snippet.lang.clone(),
+                )
+            })
+            .collect();
+        let formatters = self.formatters.clone();
+        let (sender, results) = mpsc::channel();
+        self.notify(format!("Formatting {} snippet(s)…", snippets.len()));
+        self.format_job = Some(FormatJob { results });
+        thread::spawn(move || {
+            let results = snippets
+                .into_iter()
+                .map(|(id, before, lang)| Formatted {
+                    result: format_code(&before, &lang, &formatters),
+                    id,
+                    before,
+                })
+                .collect();
+            let _ = sender.send(results);
+        });
+    }
+
+    /// shows the changes once formatting finished, called on every tick
+    pub(super) fn poll_format(&mut self) {
+        let Some(job) = &self.format_job else {
+            return;
+        };
+        let results = match job.results.try_recv() {
+            Ok(results) => results,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Vec::new(),
+        };
+        self.format_job = None;
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        for Formatted { id, before, result } in results {
+            // snippets edited while they were formatted keep the edit
+            let Some(idx) = self
+                .save_snippets
+                .iter()
+                .position(|s| s.id == id && s.code == before)
+            else {
+                continue;
+            };
+            match result {
+                Some(Ok(code)) if code != before => entries.push(FormatEntry {
+                    idx,
+                    code,
+                    selected: true,
+                }),
+                Some(Err(err)) => errors.push(format!("{}: {err}", self.save_snippets[idx].title)),
+                _ => {}
+            }
+        }
+        if entries.is_empty() {
+            match errors.first() {
+                Some(err) if errors.len() == 1 => self.notify(format!("Could not format {err}")),
+                Some(_) => self.notify(format!(
+                    "Nothing to format, {} snippet(s) failed",
+                    errors.len()
+                )),
+                None => self.notify("Nothing to format"),
+            }
+            return;
+        }
+        if !errors.is_empty() {
+            self.notify(format!(
+                "{} snippet(s) could not be formatted",
+                errors.len()
+            ));
+        }
+        let mut state = FormatState::default();
+        state.select(Some(0));
+        self.format = Some(FormatPanel {
+            entries,
+            state,
+            side_by_side: false,
+            scroll: 0,
+        });
+        self.view = View::Format;
+    }
+
+    /// formats the selected snippet, if its language has a formatter
+    pub(super) fn format_snippet(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        if snippet.is_locked() {
+            self.notify("Reveal the snippet with x before formatting it");
+            return;
+        }
+        if !self.formatters.contains_key(&snippet.lang) {
+            self.notify(format!(
+                "No formatter for {}, add one under [formatters] in the config",
+                snippet.lang
+            ));
+            return;
+        }
+        self.format_snippets(vec![idx]);
+    }
+
+    /// formats pasted code if the user asked for it, keeping it as it is if that fails
+    pub(super) fn format_pasted(&self, code: &str, lang: &str) -> String {
+        if !self.format_on_paste {
+            return code.to_string();
+        }
+        match format_code(code, lang, &self.formatters) {
+            Some(Ok(formatted)) => formatted,
+            _ => code.to_string(),
+        }
+    }
+
+    pub(super) fn handle_format_key(&mut self, key: KeyCode) {
+        let Some(panel) = &mut self.format else {
+            self.view = View::Library;
+            return;
+        };
+        match key {
+            KeyCode::Esc => {
+                self.format = None;
+                self.view = View::Library;
+            }
+            KeyCode::Down => {
+                panel.state.select_next();
+                panel.scroll = 0;
+            }
+            KeyCode::Up => {
+                panel.state.select_previous();
+                panel.scroll = 0;
+            }
+            KeyCode::PageDown => panel.scroll = panel.scroll.saturating_add(10),
+            KeyCode::PageUp => panel.scroll = panel.scroll.saturating_sub(10),
+            KeyCode::Char(' ') => {
+                if let Some(entry) = panel
+                    .state
+                    .selected()
+                    .and_then(|i| panel.entries.get_mut(i))
+                {
+                    entry.selected = !entry.selected;
+                }
+            }
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                let select = !panel.entries.iter().all(|e| e.selected);
+                for entry in &mut panel.entries {
+                    entry.selected = select;
+                }
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') => panel.side_by_side = !panel.side_by_side,
+            KeyCode::Enter => self.finish_format(),
+            _ => {}
+        }
+    }
+
+    /// applies the checked changes as one undoable edit
+    fn finish_format(&mut self) {
+        let Some(panel) = self.format.take() else {
+            return;
+        };
+        self.view = View::Library;
+        let changes: Vec<Change> = panel
+            .entries
+            .into_iter()
+            .filter(|entry| entry.selected)
+            .filter_map(|entry| {
+                let before = self.save_snippets.get(entry.idx)?.clone();
+                let after = apply(&before, entry.code);
+                Some(Change::update(entry.idx, before, after))
+            })
+            .collect();
+        let count = changes.len();
+        match count {
+            0 => return,
+            1 => self.change(changes.into_iter().next().expect("one change")),
+            _ => self.change(Change::Batch(changes)),
+        }
+        self.notify(format!("Formatted {count} snippet(s), press u to undo"));
+    }
+
+    pub(super) fn draw_format(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(panel) = &mut self.format else {
+            return;
+        };
+        let [list_area, diff_area] = Layout::horizontal([Percentage(30), Fill(1)]).areas(area);
+
+        let items: Vec<ListItem> = panel
+            .entries
+            .iter()
+            .map(|entry| {
+                let check = if entry.selected { "[x]" } else { "[ ]" };
+                let title = self
+                    .save_snippets
+                    .get(entry.idx)
+                    .map(|s| s.title.as_str())
+                    .unwrap_or_default();
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{check} ")),
+                    Span::styled(title.to_string(), Style::default().bold()),
+                ]))
+            })
+            .collect();
+        let selected = panel.entries.iter().filter(|e| e.selected).count();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!("{selected} of {} selected", panel.entries.len()))
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            )
+            .style(Style::default().fg(Color::Rgb(250, 189, 47)))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Rgb(254, 128, 25))
+                    .fg(Color::Rgb(28, 28, 32)),
+            );
+        frame.render_stateful_widget(list, list_area, &mut panel.state);
+
+        let Some((entry, snippet)) = panel
+            .state
+            .selected()
+            .and_then(|i| panel.entries.get(i))
+            .and_then(|entry| Some((entry, self.save_snippets.get(entry.idx)?)))
+        else {
+            return;
+        };
+        let mode = if panel.side_by_side {
+            "side by side"
+        } else {
+            "unified"
+        };
+        frame.render_widget(
+            DiffView {
+                old: &snippet.code,
+                new: &entry.code,
+                lang: &snippet.lang,
+                highlighter: &self.highlighter,
+                side_by_side: panel.side_by_side,
+                scroll: panel.scroll,
+                block: Block::bordered()
+                    .title(format!("formatting changes ({mode})"))
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            },
+            diff_area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_survive_formatting() {
+        let code = "echo {{name:world}} {{greeting}} \\{{literal}}";
+        assert_eq!(run_formatter("cat", code, "sh").unwrap(), code);
+        // the formatter only sees identifiers in their place
+        let seen = run_formatter("sed s/__codecache_/seen_/g", code, "sh").unwrap();
+        assert_eq!(seen, "echo seen_0__ seen_1__ seen_2__literal}}");
+    }
+
+    #[test]
+    fn rust_statements_are_formatted_in_a_function() {
+        let code = "let  x = {{value:1}};\nprintln!(\"{x}\");";
+        assert_eq!(
+            run_formatter("rustfmt --edition 2024", code, "rs").unwrap(),
+            "let x = {{value:1}};\nprintln!(\"{x}\");"
+        );
+        // multi-line strings keep their lines as they are
+        let code = "let s = \"a\n    b\nc\";\nlet r = r#\"\n  d\"#;";
+        assert_eq!(
+            run_formatter("rustfmt --edition 2024", code, "rs").unwrap(),
+            code
+        );
+    }
+
+    #[test]
+    fn finds_lines_inside_strings() {
+        let code = "let a = 'x'; // \"\nlet s = \"one\ntwo \\\" still\nend\";\n/* \" */ let c = '\\'';\nlet r = br##\"\n\"# \"##;\nfn f<'a>() {}";
+        assert_eq!(
+            string_continuations(code),
+            [false, false, true, true, false, false, true, false]
+        );
+    }
+}
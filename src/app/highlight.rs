@@ -1,12 +1,15 @@
 use ratatui::text::Text;
 use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
+use syntect::highlighting::{Theme, ThemeSet};
 use syntect::parsing::SyntaxSet;
 use syntect::util::{LinesWithEndings, as_24_bit_terminal_escaped};
 
+pub const DEFAULT_THEME: &str = "base16-eighties.dark";
+
 pub struct Highlighter {
     pub ps: SyntaxSet,
     pub ts: ThemeSet,
+    theme: String,
 }
 
 impl Highlighter {
@@ -14,9 +17,85 @@ impl Highlighter {
         Self {
             ps: SyntaxSet::load_defaults_newlines(),
             ts: ThemeSet::load_defaults(),
+            theme: DEFAULT_THEME.to_string(),
+        }
+    }
+
+    /// switches to another syntect theme, keeping the current one if it does not exist
+    pub fn set_theme(&mut self, theme: &str) -> Result<(), String> {
+        if !self.ts.themes.contains_key(theme) {
+            let known: Vec<&str> = self.ts.themes.keys().map(|name| name.as_str()).collect();
+            return Err(format!(
+                "unknown theme \"{theme}\", available: {}",
+                known.join(", ")
+            ));
+        }
+        self.theme = theme.to_string();
+        Ok(())
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.ts.themes[&self.theme]
+    }
+
+    /// all languages syntect knows as (name, extension), sorted by name
+    pub fn languages(&self) -> Vec<(String, String)> {
+        let mut languages: Vec<(String, String)> = self
+            .ps
+            .syntaxes()
+            .iter()
+            .filter_map(|syntax| {
+                let ext = syntax.file_extensions.first()?;
+                Some((syntax.name.clone(), ext.clone()))
+            })
+            .collect();
+        languages.sort_by_key(|(name, _)| name.to_lowercase());
+        languages.dedup_by(|a, b| a.1 == b.1);
+        languages
+    }
+
+    /// display name of a language, e.g. "Rust" for "rs"
+    pub fn language_name(&self, lang: &str) -> String {
+        self.ps
+            .find_syntax_by_extension(lang)
+            .map(|syntax| syntax.name.clone())
+            .unwrap_or_else(|| lang.to_string())
+    }
+
+    /// checks whether code in this language can be highlighted
+    pub fn knows(&self, lang: &str) -> bool {
+        self.ps.find_syntax_by_extension(lang).is_some()
+    }
+
+    /// file extension for a language name or extension such as "rust" or "py"
+    pub fn extension_for(&self, token: &str) -> Option<String> {
+        let syntax = self.ps.find_syntax_by_token(token)?;
+        syntax.file_extensions.first().cloned()
+    }
+
+    /// file extension for a TextMate scope such as "source.python" or "source.shell.bash",
+    /// falling back to shorter scopes
+    pub fn extension_for_scope(&self, scope: &str) -> Option<String> {
+        let mut scope = scope.trim();
+        loop {
+            let syntax = self
+                .ps
+                .syntaxes()
+                .iter()
+                .find(|syntax| syntax.scope.build_string() == scope);
+            if let Some(syntax) = syntax {
+                return syntax.file_extensions.first().cloned();
+            }
+            scope = scope.rsplit_once('.')?.0;
         }
     }
 
+    /// TextMate scope of a language, e.g. "source.rust" for "rs"
+    pub fn scope_for(&self, lang: &str) -> Option<String> {
+        let syntax = self.ps.find_syntax_by_extension(lang)?;
+        Some(syntax.scope.build_string())
+    }
+
     pub fn highlight(&self, code: &str, lang: &str) -> Result<Text<'static>, Text<'static>> {
         /*let syntax = self
         .ps
@@ -30,10 +109,10 @@ impl Highlighter {
             }
         };
 
-        let mut h = HighlightLines::new(syntax, &self.ts.themes["base16-eighties.dark"]);
+        let mut h = HighlightLines::new(syntax, self.theme());
 
         let mut final_str = String::new();
-        for line in LinesWithEndings::from(&code) {
+        for line in LinesWithEndings::from(code) {
             let ranges = h.highlight_line(line, &self.ps).unwrap();
             let escaped = as_24_bit_terminal_escaped(&ranges[..], false);
             final_str.push_str(&escaped);
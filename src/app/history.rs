@@ -0,0 +1,352 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Constraint::{Fill, Length},
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState as RevisionState},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{CodeCache, SaveSnippet, View, diff::DiffView, secrets, timestamp, undo::Change};
+
+/// previous values of the fields that one edit changed, unchanged fields are `None`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    pub at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+}
+
+impl Revision {
+    /// names of the fields changed by this revision
+    pub fn fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.title.is_some() {
+            fields.push("title");
+        }
+        if self.desc.is_some() {
+            fields.push("description");
+        }
+        if self.code.is_some() {
+            fields.push("code");
+        }
+        if self.lang.is_some() {
+            fields.push("language");
+        }
+        fields
+    }
+}
+
+/// appends a revision to `after` for every field that differs from `before`.
+/// the code of secret snippets is never kept in the history, it would be stored in cleartext.
+pub fn revise(before: &SaveSnippet, after: &mut SaveSnippet) {
+    let changed = |old: &String, new: &String| (old != new).then(|| old.clone());
+    let revision = Revision {
+        at: timestamp::now(),
+        title: changed(&before.title, &after.title),
+        desc: changed(&before.desc, &after.desc),
+        code: changed(&before.code, &after.code).filter(|_| !before.secret && !after.secret),
+        lang: changed(&before.lang, &after.lang),
+    };
+    if !revision.fields().is_empty() {
+        after.history.push(revision);
+    }
+}
+
+/// drops earlier code from the history of a snippet that became secret, the revisions are
+/// stored in cleartext and only the current code is encrypted
+pub fn forget_code(snippet: &mut SaveSnippet) {
+    for revision in &mut snippet.history {
+        revision.code = None;
+    }
+    snippet
+        .history
+        .retain(|revision| !revision.fields().is_empty());
+}
+
+/// replaces secrets in older revisions too, so redacting a snippet really removes them
+pub fn scrub(snippet: &mut SaveSnippet) {
+    for revision in &mut snippet.history {
+        for text in [&mut revision.desc, &mut revision.code]
+            .into_iter()
+            .flatten()
+        {
+            *text = secrets::redact(text, &secrets::scan(text));
+        }
+    }
+}
+
+/// a full version of a snippet rebuilt from its history
+#[derive(Debug, Clone)]
+pub struct Version {
+    /// time of the edit that replaced this version, `None` for the current one
+    pub replaced_at: Option<u64>,
+    pub changed: Vec<&'static str>,
+    pub title: String,
+    pub desc: String,
+    pub code: String,
+    pub lang: String,
+}
+
+/// all versions of a snippet, newest first, starting with the current one
+pub fn versions(snippet: &SaveSnippet) -> Vec<Version> {
+    let mut current = Version {
+        replaced_at: None,
+        changed: Vec::new(),
+        title: snippet.title.clone(),
+        desc: snippet.desc.clone(),
+        code: snippet.code.clone(),
+        lang: snippet.lang.clone(),
+    };
+    let mut versions = vec![current.clone()];
+    for revision in snippet.history.iter().rev() {
+        current.replaced_at = Some(revision.at);
+        current.changed = revision.fields();
+        if let Some(title) = &revision.title {
+            current.title = title.clone();
+        }
+        if let Some(desc) = &revision.desc {
+            current.desc = desc.clone();
+        }
+        if let Some(code) = &revision.code {
+            current.code = code.clone();
+        }
+        if let Some(lang) = &revision.lang {
+            current.lang = lang.clone();
+        }
+        versions.push(current.clone());
+    }
+    versions
+}
+
+/// state of the history panel for one snippet
+pub struct HistoryPanel {
+    idx: usize,
+    versions: Vec<Version>,
+    state: RevisionState,
+    side_by_side: bool,
+    scroll: u16,
+}
+
+impl CodeCache {
+    pub(super) fn open_history(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        if snippet.is_locked() {
+            self.notify("Reveal the snippet with x before viewing its history");
+            return;
+        }
+        let versions = versions(snippet);
+        if versions.len() < 2 {
+            self.notify("This snippet has not been edited yet");
+            return;
+        }
+        let mut state = RevisionState::default();
+        state.select(Some(1));
+        self.history = Some(HistoryPanel {
+            idx,
+            versions,
+            state,
+            side_by_side: false,
+            scroll: 0,
+        });
+        self.view = View::History;
+    }
+
+    pub(super) fn handle_history_key(&mut self, key: KeyCode) {
+        let Some(panel) = &mut self.history else {
+            self.view = View::Library;
+            return;
+        };
+        match key {
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
+            KeyCode::Esc | KeyCode::Char('h') | KeyCode::Char('H') => {
+                self.history = None;
+                self.view = View::Library;
+            }
+            KeyCode::Down => {
+                panel.state.select_next();
+                panel.scroll = 0;
+            }
+            KeyCode::Up => {
+                panel.state.select_previous();
+                panel.scroll = 0;
+            }
+            KeyCode::PageDown => panel.scroll = panel.scroll.saturating_add(10),
+            KeyCode::PageUp => panel.scroll = panel.scroll.saturating_sub(10),
+            KeyCode::Char('s') | KeyCode::Char('S') => panel.side_by_side = !panel.side_by_side,
+            KeyCode::Enter | KeyCode::Char('r') | KeyCode::Char('R') => {
+                let idx = panel.idx;
+                let Some(version) = panel.state.selected().and_then(|i| panel.versions.get(i))
+                else {
+                    return;
+                };
+                if version.replaced_at.is_none() {
+                    return;
+                }
+                let version = version.clone();
+                self.restore_version(idx, version);
+            }
+            _ => {}
+        }
+    }
+
+    /// turns an old version into the current one, recording it as a new edit
+    fn restore_version(&mut self, idx: usize, version: Version) {
+        if self.read_only(idx) {
+            return;
+        }
+        let Some(before) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        let mut after = before.clone();
+        after.title = version.title;
+        after.desc = version.desc;
+        after.code = version.code;
+        after.lang = version.lang;
+        revise(&before, &mut after);
+        self.change(Change::update(idx, before, after));
+        self.history = None;
+        self.view = View::Library;
+        self.notify("Restored revision, press u to undo");
+    }
+
+    pub(super) fn draw_history(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(panel) = &mut self.history else {
+            return;
+        };
+        let [list_area, diff_area] = Layout::horizontal([Length(36), Fill(1)]).areas(area);
+
+        let items: Vec<ListItem> = panel
+            .versions
+            .iter()
+            .map(|version| match version.replaced_at {
+                None => ListItem::new("current version"),
+                Some(at) => ListItem::new(vec![
+                    Line::from(format!("before edit {}", timestamp::ago(at))),
+                    Line::from(format!("  changed {}", version.changed.join(", ")))
+                        .style(Style::default().fg(Color::Rgb(120, 112, 108))),
+                ]),
+            })
+            .collect();
+        let title = panel
+            .versions
+            .first()
+            .map(|v| format!("History of {}", v.title))
+            .unwrap_or_default();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            )
+            .style(Style::default().fg(Color::Rgb(250, 189, 47)))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Rgb(254, 128, 25))
+                    .fg(Color::Rgb(28, 28, 32)),
+            );
+        frame.render_stateful_widget(list, list_area, &mut panel.state);
+
+        let (Some(selected), Some(current)) = (
+            panel.state.selected().and_then(|i| panel.versions.get(i)),
+            panel.versions.first(),
+        ) else {
+            return;
+        };
+
+        // metadata changes are shown above the code diff
+        let mut header = Vec::new();
+        for (name, old, new) in [
+            ("title", &selected.title, &current.title),
+            ("description", &selected.desc, &current.desc),
+            ("language", &selected.lang, &current.lang),
+        ] {
+            if old != new {
+                header.push(Line::from(vec![
+                    Span::styled(format!("{name}: "), Style::default().bold()),
+                    Span::styled(old.replace('\n', " "), Style::default().fg(Color::Red)),
+                    Span::raw(" → "),
+                    Span::styled(new.replace('\n', " "), Style::default().fg(Color::Green)),
+                ]));
+            }
+        }
+        let [header_area, code_area] =
+            Layout::vertical([Length(header.len() as u16), Fill(1)]).areas(diff_area);
+        frame.render_widget(Text::from(header), header_area);
+
+        let mode = if panel.side_by_side {
+            "side by side"
+        } else {
+            "unified"
+        };
+        frame.render_widget(
+            DiffView {
+                old: &selected.code,
+                new: &current.code,
+                lang: &current.lang,
+                highlighter: &self.highlighter,
+                side_by_side: panel.side_by_side,
+                scroll: panel.scroll,
+                block: Block::bordered()
+                    .title(format!("changes since this version ({mode})"))
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            },
+            code_area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(title: &str, code: &str) -> SaveSnippet {
+        let mut snippet = SaveSnippet::new(code, "sh");
+        snippet.title = title.to_string();
+        snippet
+    }
+
+    #[test]
+    fn revisions_rebuild_versions() {
+        let v1 = snippet("deploy", "make deploy");
+        let mut v2 = snippet("deploy prod", "make deploy ENV=prod");
+        revise(&v1, &mut v2);
+        let versions = versions(&v2);
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[1].title, "deploy");
+        assert_eq!(versions[1].code, "make deploy");
+        assert_eq!(versions[1].changed, ["title", "code"]);
+    }
+
+    #[test]
+    fn secret_code_is_not_kept() {
+        let mut before = snippet("token", "export TOKEN=abc");
+        before.secret = true;
+        let mut after = before.clone();
+        after.code = "export TOKEN=def".to_string();
+        revise(&before, &mut after);
+        assert!(after.history.is_empty());
+    }
+
+    #[test]
+    fn forgetting_code_keeps_other_fields() {
+        let v1 = snippet("token", "export TOKEN=abc");
+        let mut v2 = snippet("token", "export TOKEN=def");
+        revise(&v1, &mut v2);
+        let mut v3 = snippet("api token", "export TOKEN=def");
+        v3.history = v2.history.clone();
+        revise(&v2, &mut v3);
+        forget_code(&mut v3);
+        assert_eq!(v3.history.len(), 1);
+        assert_eq!(v3.history[0].title.as_deref(), Some("token"));
+        assert!(v3.history.iter().all(|revision| revision.code.is_none()));
+    }
+}
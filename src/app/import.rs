@@ -0,0 +1,298 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Constraint::{Fill, Percentage},
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState as ImportState, Paragraph},
+};
+
+use crate::app::{
+    CodeCache, SaveSnippet, View, cheatsheet, highlight::Highlighter, inbox::normalize, markdown,
+    textmate, undo::Change, vim, vscode, yasnippet,
+};
+
+/// a snippet found in an import file, waiting in the preview
+pub struct Candidate {
+    pub snippet: SaveSnippet,
+    /// file and line it was found at
+    pub origin: String,
+    pub selected: bool,
+    pub duplicate: bool,
+}
+
+impl Candidate {
+    pub fn new(snippet: SaveSnippet, origin: String) -> Self {
+        Self {
+            snippet,
+            origin,
+            selected: true,
+            duplicate: false,
+        }
+    }
+}
+
+/// snippets found in the given files and directories
+pub fn scan(paths: &[PathBuf]) -> Result<Vec<Candidate>, String> {
+    let highlighter = Highlighter::new();
+    let mut files = Vec::new();
+    for path in paths {
+        let path = fs::canonicalize(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        if path.is_dir() {
+            collect_files(&path, &mut files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let mut candidates = Vec::new();
+    for file in files {
+        let text = fs::read_to_string(&file).map_err(|err| format!("{}: {err}", file.display()))?;
+        match Format::of(&file) {
+            Some(Format::Markdown) => {
+                candidates.extend(markdown::parse(&text, &file, &highlighter))
+            }
+            Some(Format::Vscode) => candidates.extend(vscode::parse(&text, &file, &highlighter)?),
+            Some(Format::Sublime) => {
+                candidates.extend(textmate::parse_sublime(&text, &file, &highlighter)?);
+            }
+            Some(Format::TextMate) => {
+                candidates.extend(textmate::parse_tm(&text, &file, &highlighter)?);
+            }
+            Some(Format::Vim) => candidates.extend(vim::parse(&text, &file, &highlighter)),
+            Some(Format::VimSingle) => {
+                candidates.extend(vim::parse_single(&text, &file, &highlighter));
+            }
+            Some(Format::Yasnippet) => {
+                candidates.extend(yasnippet::parse(&text, &file, &highlighter));
+            }
+            Some(Format::Pet) => candidates.extend(cheatsheet::parse_pet(&text, &file)?),
+            Some(Format::Navi) => candidates.extend(cheatsheet::parse_navi(&text, &file)),
+            None => {
+                return Err(format!(
+                    "{} is not a Markdown or snippet file",
+                    file.display()
+                ));
+            }
+        }
+    }
+    Ok(candidates)
+}
+
+/// file formats snippets can be imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Markdown,
+    Vscode,
+    Sublime,
+    TextMate,
+    /// UltiSnips or SnipMate `.snippets`
+    Vim,
+    /// SnipMate `.snippet` with a single snippet
+    VimSingle,
+    Yasnippet,
+    /// pet `snippet.toml`
+    Pet,
+    /// navi `.cheat`
+    Navi,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "md" | "markdown" | "mdx" => Some(Format::Markdown),
+            "code-snippets" | "json" => Some(Format::Vscode),
+            "sublime-snippet" => Some(Format::Sublime),
+            "tmsnippet" => Some(Format::TextMate),
+            "snippets" => Some(Format::Vim),
+            "snippet" => Some(Format::VimSingle),
+            "yasnippet" => Some(Format::Yasnippet),
+            "toml" => Some(Format::Pet),
+            "cheat" => Some(Format::Navi),
+            // yasnippet files usually have no extension, their directory is named after a mode
+            _ if yasnippet::is_snippet_file(path) => Some(Format::Yasnippet),
+            _ => None,
+        }
+    }
+}
+
+/// all importable files below a directory, skipping hidden directories and dependencies
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if path.is_dir() {
+            // .vscode holds the project's snippet files
+            let hidden = name.starts_with('.') && name != ".vscode";
+            if !hidden && name != "node_modules" && name != "target" {
+                collect_files(&path, files);
+            }
+        // plain json files are only snippets when asked for directly
+        } else if Format::of(&path)
+            .is_some_and(|format| !matches!(format, Format::Vscode | Format::Pet))
+            || name.ends_with(".code-snippets")
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// unchecks snippets that are already in the library or earlier in the import
+pub fn mark_duplicates(candidates: &mut [Candidate], library: &[SaveSnippet]) {
+    let mut seen: Vec<String> = library.iter().map(|s| normalize(&s.code)).collect();
+    for candidate in candidates {
+        let code = normalize(&candidate.snippet.code);
+        if seen.contains(&code) {
+            candidate.duplicate = true;
+            candidate.selected = false;
+        } else {
+            seen.push(code);
+        }
+    }
+}
+
+impl CodeCache {
+    /// shows the import preview, used by `codecache import`
+    pub fn open_import(&mut self, mut candidates: Vec<Candidate>) {
+        mark_duplicates(&mut candidates, &self.save_snippets);
+        self.import = candidates;
+        self.import_state = ImportState::default();
+        self.import_state.select(Some(0));
+        self.view = View::Import;
+    }
+
+    pub(super) fn handle_import_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
+            KeyCode::Esc => {
+                self.import.clear();
+                self.view = View::Library;
+                self.notify("Import cancelled");
+            }
+            KeyCode::Down => self.import_state.select_next(),
+            KeyCode::Up => self.import_state.select_previous(),
+            KeyCode::Char(' ') => {
+                if let Some(candidate) = self
+                    .import_state
+                    .selected()
+                    .and_then(|i| self.import.get_mut(i))
+                {
+                    candidate.selected = !candidate.selected;
+                }
+            }
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                let select = !self.import.iter().all(|c| c.selected || c.duplicate);
+                for candidate in self.import.iter_mut().filter(|c| !c.duplicate) {
+                    candidate.selected = select;
+                }
+            }
+            KeyCode::Enter => self.finish_import(),
+            _ => {}
+        }
+    }
+
+    /// adds the checked snippets to the library as one undoable change
+    fn finish_import(&mut self) {
+        let start = self.save_snippets.len();
+        let changes: Vec<Change> = std::mem::take(&mut self.import)
+            .into_iter()
+            .filter(|c| c.selected)
+            .enumerate()
+            .map(|(i, c)| Change::Insert {
+                idx: start + i,
+                snippet: c.snippet,
+            })
+            .collect();
+        let count = changes.len();
+        if count > 0 {
+            self.change(Change::Batch(changes));
+        }
+        self.view = View::Library;
+        self.notify(format!("Imported {count} snippet(s)"));
+    }
+
+    pub(super) fn draw_import(&mut self, frame: &mut Frame, area: Rect) {
+        let [list_area, preview_area] = Layout::horizontal([Percentage(45), Fill(1)]).areas(area);
+
+        let items: Vec<ListItem> = self
+            .import
+            .iter()
+            .map(|candidate| {
+                let check = if candidate.selected { "[x]" } else { "[ ]" };
+                let mut spans = vec![
+                    Span::raw(format!("{check} ")),
+                    Span::styled(candidate.snippet.title.clone(), Style::default().bold()),
+                    Span::raw(format!(" ({})", candidate.snippet.lang)),
+                ];
+                if candidate.duplicate {
+                    spans.push(Span::styled(
+                        " duplicate",
+                        Style::default().fg(Color::Rgb(251, 73, 52)),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let selected = self.import.iter().filter(|c| c.selected).count();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!("{selected} of {} selected", self.import.len()))
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            )
+            .style(Style::default().fg(Color::Rgb(250, 189, 47)))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Rgb(254, 128, 25))
+                    .fg(Color::Rgb(28, 28, 32)),
+            );
+        frame.render_stateful_widget(list, list_area, &mut self.import_state);
+
+        let Some(candidate) = self
+            .import_state
+            .selected()
+            .and_then(|i| self.import.get(i))
+        else {
+            return;
+        };
+        let code = match self
+            .highlighter
+            .highlight(&candidate.snippet.code, &candidate.snippet.lang)
+        {
+            Ok(text) | Err(text) => text,
+        };
+        let mut text = Text::default();
+        if !candidate.snippet.desc.is_empty() {
+            text.push_line(Line::styled(
+                candidate.snippet.desc.clone(),
+                Style::default().fg(Color::Rgb(120, 112, 108)),
+            ));
+            text.push_line(Line::default());
+        }
+        text.extend(code);
+        frame.render_widget(
+            Paragraph::new(text).block(
+                Block::bordered()
+                    .title(candidate.origin.clone())
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            ),
+            preview_area,
+        );
+    }
+}
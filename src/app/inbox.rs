@@ -0,0 +1,320 @@
+use std::{
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{
+        CodeCache, SaveSnippet, View, clipboard::ClipboardProvider, codesnippet::CodeSnippet,
+        form::FormTarget, language::guess_lang, secrets, timestamp,
+    },
+    config::{self, Config},
+};
+
+/// a clip captured by the clipboard watcher that has not been filed yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboxItem {
+    pub code: String,
+    pub lang: String,
+    pub captured_at: u64,
+}
+
+fn inbox_path() -> PathBuf {
+    config::data_path("inbox.json")
+}
+
+pub fn load() -> Vec<InboxItem> {
+    let inbox_file = fs::read_to_string(inbox_path()).unwrap_or("[]".to_string());
+    serde_json::from_str(&inbox_file).unwrap_or_else(|_| Vec::new())
+}
+
+pub fn save(items: &[InboxItem]) -> Result<(), String> {
+    let inbox_str = serde_json::to_string_pretty(items).expect("failed to save inbox to file");
+    let path = inbox_path();
+    fs::write(&path, inbox_str).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// builds the cards shown in the inbox view
+pub fn inbox_cards(items: &[InboxItem]) -> Vec<CodeSnippet> {
+    items
+        .iter()
+        .map(|item| {
+            CodeSnippet::new(
+                format!("captured {}", timestamp::ago(item.captured_at)),
+                String::new(),
+                item.code.clone(),
+                item.lang.clone(),
+            )
+        })
+        .collect()
+}
+
+impl CodeCache {
+    pub(super) fn toggle_capture(&mut self) {
+        if self.watcher.take().is_some() {
+            self.notify("Clipboard capture stopped");
+        } else {
+            self.watcher = Some(Watcher::new(Duration::from_secs(1)));
+            self.notify("Capturing code from the clipboard into the inbox");
+        }
+    }
+
+    pub(super) fn open_inbox(&mut self) {
+        // the `watch` command may have added clips in the meantime
+        self.inbox = load();
+        self.view = View::Inbox;
+        if self.inbox_state.selected.is_none() && !self.inbox.is_empty() {
+            self.inbox_state.select(Some(0));
+        }
+    }
+
+    /// files a clip from the watcher into the inbox
+    pub(super) fn capture(&mut self, text: &str) {
+        let library: Vec<String> = self.save_snippets.iter().map(|s| s.code.clone()).collect();
+        let mut inbox = load();
+        if let Some(item) = capture(text, &inbox, &library) {
+            inbox.push(item);
+            let saved = save(&inbox);
+            self.inbox = inbox;
+            match saved {
+                Ok(()) => self.notify(format!("Captured clip, {} in inbox", self.inbox.len())),
+                Err(err) => self.notify(format!("Could not save the inbox: {err}")),
+            }
+        }
+    }
+
+    pub(super) fn handle_inbox_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
+            KeyCode::Esc | KeyCode::Char('i') | KeyCode::Char('I') => self.view = View::Library,
+            KeyCode::Down | KeyCode::PageDown => self.inbox_state.next(),
+            KeyCode::Up | KeyCode::PageUp => self.inbox_state.previous(),
+            KeyCode::Char('w') | KeyCode::Char('W') => self.toggle_capture(),
+            KeyCode::Char('c') | KeyCode::Char('C') => {
+                if let Some(item) = self.inbox_state.selected.and_then(|i| self.inbox.get(i)) {
+                    let code = item.code.clone();
+                    self.copy(&code);
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if let Some(idx) = self.inbox_state.selected {
+                    self.remove_inbox_item(idx);
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('p') | KeyCode::Char('P') => {
+                if let Some(idx) = self.inbox_state.selected {
+                    self.promote(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn remove_inbox_item(&mut self, idx: usize) -> Option<InboxItem> {
+        if idx >= self.inbox.len() {
+            return None;
+        }
+        let item = self.inbox.remove(idx);
+        if let Err(err) = save(&self.inbox) {
+            self.notify(format!("Could not save the inbox: {err}"));
+        }
+        if self.inbox.is_empty() {
+            self.inbox_state.select(None);
+        } else if idx >= self.inbox.len() {
+            self.inbox_state.select(Some(idx - 1));
+        }
+        Some(item)
+    }
+
+    /// opens the form to file an inbox item into the library
+    fn promote(&mut self, idx: usize) {
+        let Some(item) = self.inbox.get(idx) else {
+            return;
+        };
+        let snippet = SaveSnippet::new(item.code.clone(), item.lang.clone());
+        self.open_form(FormTarget::Promote(idx), &snippet);
+    }
+}
+
+/// polls a clipboard and reports text that was not seen before
+pub struct Watcher {
+    last_text: Option<String>,
+    last_poll: Instant,
+    interval: Duration,
+}
+
+impl Watcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            last_text: None,
+            last_poll: Instant::now() - interval,
+            interval,
+        }
+    }
+
+    /// marks text as seen, e.g. after codecache itself copied it
+    pub fn ignore(&mut self, text: &str) {
+        self.last_text = Some(text.to_string());
+    }
+
+    /// returns the clipboard text if it changed since the last poll and the interval passed
+    pub fn poll(&mut self, clipboard: &mut dyn ClipboardProvider) -> Option<String> {
+        if self.last_poll.elapsed() < self.interval {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let text = clipboard.get_text().ok()?;
+        if self.last_text.as_deref() == Some(text.as_str()) {
+            return None;
+        }
+        // the first poll only records what was already there
+        let first = self.last_text.is_none();
+        self.last_text = Some(text.clone());
+        (!first).then_some(text)
+    }
+}
+
+/// checks a clip and turns it into an inbox item unless it is prose, a secret or a repeat
+pub fn capture(text: &str, inbox: &[InboxItem], library: &[String]) -> Option<InboxItem> {
+    let code = text.trim_matches(|c| c == '\n' || c == '\r');
+    if !looks_like_code(code) || !secrets::scan(code).is_empty() {
+        return None;
+    }
+
+    let normalized = normalize(code);
+    let seen = inbox.iter().map(|item| &item.code).chain(library);
+    if seen.into_iter().any(|other| normalize(other) == normalized) {
+        return None;
+    }
+
+    Some(InboxItem {
+        code: code.to_string(),
+        lang: guess_lang(code),
+        captured_at: timestamp::now(),
+    })
+}
+
+/// whitespace-insensitive form of a snippet used for deduplication
+pub(super) fn normalize(code: &str) -> String {
+    code.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// scores a clip on typical code features to tell it apart from prose
+pub fn looks_like_code(text: &str) -> bool {
+    let text = text.trim();
+    if text.len() < 8 || text.len() > 50_000 {
+        return false;
+    }
+    // a lone link or path is not worth keeping
+    if !text.contains(char::is_whitespace)
+        && (text.starts_with("http://") || text.starts_with("https://") || text.starts_with('/'))
+    {
+        return false;
+    }
+
+    // prose is mostly sentences that end in punctuation
+    let sentences = text.matches(". ").count() + text.matches("? ").count();
+    let words = text.split_whitespace().count();
+    let prose = words > 8 && sentences * 12 >= words;
+
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() == 1 && !prose && super::language::looks_like_shell(lines[0]) {
+        return true;
+    }
+
+    let symbols = text
+        .chars()
+        .filter(|c| "{}()[];=<>:$&|".contains(*c))
+        .count();
+    let symbol_density = symbols as f32 / text.len() as f32;
+
+    let mut score = 0;
+    if symbol_density > 0.03 {
+        score += 1;
+    }
+    if symbol_density > 0.08 {
+        score += 1;
+    }
+    let code_endings = lines
+        .iter()
+        .filter(|l| {
+            let l = l.trim_end();
+            l.ends_with(';') || l.ends_with('{') || l.ends_with('}') || l.ends_with(':')
+        })
+        .count();
+    if code_endings * 3 >= lines.len() {
+        score += 1;
+    }
+    if lines.len() > 1
+        && lines
+            .iter()
+            .skip(1)
+            .any(|l| l.starts_with("  ") || l.starts_with('\t'))
+    {
+        score += 1;
+    }
+    if guess_lang(text) != "txt" {
+        score += 1;
+    }
+
+    if prose {
+        score -= 2;
+    }
+
+    score >= 2
+}
+
+/// runs the watcher in the foreground until interrupted, for `codecache watch`
+pub fn watch(config: &Config, library: &[String], interval: Duration) {
+    let mut clipboard = super::clipboard::from_config(config);
+    let mut watcher = Watcher::new(interval);
+    eprintln!(
+        "watching the {} clipboard, captured snippets go to the inbox (Ctrl+C to stop)",
+        clipboard.name()
+    );
+    loop {
+        if let Some(text) = watcher.poll(clipboard.as_mut()) {
+            let mut inbox = load();
+            if let Some(item) = capture(&text, &inbox, library) {
+                eprintln!(
+                    "captured {} line(s) of {}",
+                    item.code.lines().count(),
+                    super::language::get_lang(&item.lang)
+                );
+                inbox.push(item);
+                if let Err(err) = save(&inbox) {
+                    eprintln!("{err}");
+                }
+            }
+        }
+        thread::sleep(interval.min(Duration::from_millis(250)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_code() {
+        assert!(looks_like_code("git log --oneline -5"));
+        assert!(looks_like_code("fn main() {\n    println!(\"hi\");\n}"));
+    }
+
+    #[test]
+    fn skips_prose() {
+        assert!(!looks_like_code(
+            "make sure to review the doc before friday"
+        ));
+        assert!(!looks_like_code("if you get a chance, call me"));
+        assert!(!looks_like_code(
+            "Thanks for the update. I will go over it tomorrow. Let me know if anything changes."
+        ));
+    }
+}
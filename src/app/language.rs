@@ -21,3 +21,165 @@ pub fn get_lang(ext: &str) -> String {
     }
     .to_string()
 }
+
+/// guesses the file extension of a piece of code, falls back to "txt"
+pub fn guess_lang(code: &str) -> String {
+    let trimmed = code.trim();
+    let first_line = trimmed.lines().next().unwrap_or_default();
+
+    if let Some(shebang) = first_line.strip_prefix("#!") {
+        let lang = if shebang.contains("python") {
+            "py"
+        } else if shebang.contains("node") {
+            "js"
+        } else {
+            "sh"
+        };
+        return lang.to_string();
+    }
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return "json".to_string();
+    }
+
+    let has = |needles: &[&str]| needles.iter().any(|n| code.contains(n));
+    let lang = if has(&[
+        "fn ",
+        "let mut ",
+        "impl ",
+        "pub struct ",
+        "use std::",
+        "println!",
+    ]) {
+        "rs"
+    } else if has(&["package main", "func ", ":= "]) {
+        "go"
+    } else if has(&["public class ", "public static void", "System.out."]) {
+        "java"
+    } else if has(&["#include", "int main("]) {
+        if has(&["std::", "cout", "template<", "namespace "]) {
+            "cpp"
+        } else {
+            "c"
+        }
+    } else if has(&["using System", "namespace ", "Console.Write"]) {
+        "cs"
+    } else if has(&["def ", "import ", "elif ", "self.", "print("]) && !code.contains(';') {
+        "py"
+    } else if has(&["interface ", ": string", ": number", "export type "]) {
+        "ts"
+    } else if has(&["function ", "const ", "=> ", "console.log", "require("]) {
+        "js"
+    } else if has(&["<html", "<div", "<body", "<!DOCTYPE"]) {
+        "html"
+    } else if first_line.starts_with('[') && code.contains(" = ") {
+        "toml"
+    } else if code.contains(" {") && has(&["color:", "margin:", "display:", "padding:"]) {
+        "css"
+    } else if trimmed.starts_with("---")
+        || (trimmed.lines().count() > 1 && trimmed.lines().all(is_yaml_line))
+    {
+        "yaml"
+    } else if looks_like_shell(first_line) {
+        "sh"
+    } else {
+        "txt"
+    };
+    lang.to_string()
+}
+
+fn is_yaml_line(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("- ")
+        || line
+            .split_once(':')
+            .is_some_and(|(key, _)| !key.is_empty() && !key.contains(' '))
+}
+
+/// checks whether a line looks like a shell command
+pub fn looks_like_shell(line: &str) -> bool {
+    /// tools whose name alone gives a command away
+    const COMMANDS: &[&str] = &[
+        "sudo",
+        "git",
+        "ls",
+        "grep",
+        "docker",
+        "kubectl",
+        "cargo",
+        "npm",
+        "pip",
+        "curl",
+        "wget",
+        "ssh",
+        "scp",
+        "chmod",
+        "chown",
+        "systemctl",
+        "journalctl",
+        "rm",
+        "mv",
+        "cp",
+        "mkdir",
+        "awk",
+        "sed",
+        "brew",
+        "python3",
+        "rsync",
+        "jq",
+    ];
+    /// commands that are also english words, only counted with shell syntax after them
+    const WORDS: &[&str] = &[
+        "cd", "cat", "find", "echo", "export", "apt", "tar", "make", "for", "while", "if",
+        "python", "node", "go",
+    ];
+    let line = line.trim_start().trim_start_matches("$ ");
+    let mut words = line.split_whitespace();
+    let first_word = words.next().unwrap_or_default();
+    let has_syntax = words.any(is_shell_syntax);
+    COMMANDS.contains(&first_word)
+        || (WORDS.contains(&first_word) && has_syntax)
+        || line.contains(" | ")
+        || line.contains(" && ")
+}
+
+/// a flag, path, variable, pipe, redirect or assignment in a command line
+fn is_shell_syntax(word: &str) -> bool {
+    let word = word.trim_end_matches(['.', ',', '!', '?']);
+    word.starts_with('-')
+        || word.starts_with('.')
+        || word.starts_with('~')
+        || word.contains(['/', '$', '=', '|', '>', '<', ';', '&', '*', '`', '['])
+        // file names like main.go
+        || word.split_once('.').is_some_and(|(stem, ext)| !stem.is_empty() && !ext.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_commands() {
+        assert!(looks_like_shell("git status"));
+        assert!(looks_like_shell("$ docker ps -a"));
+        assert!(looks_like_shell("cd ~/src/codecache"));
+        assert!(looks_like_shell("make -j8 install"));
+        assert!(looks_like_shell("go run main.go"));
+        assert!(looks_like_shell("for f in *.txt; do echo $f; done"));
+        assert!(looks_like_shell("cat notes | wc -l"));
+    }
+
+    #[test]
+    fn english_is_not_shell() {
+        assert!(!looks_like_shell(
+            "make sure to review the doc before friday"
+        ));
+        assert!(!looks_like_shell("if you get a chance, call me"));
+        assert!(!looks_like_shell("go ahead and merge it."));
+        assert!(!looks_like_shell("find the time for a quick chat?"));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::app::{CodeCache, codesnippet::PERSONAL_LIBRARY, undo::Change};
+
+impl CodeCache {
+    /// indices of the snippets shown with the current library filter
+    pub(super) fn visible(&self) -> Vec<usize> {
+        self.save_snippets
+            .iter()
+            .enumerate()
+            .filter(|(_, snippet)| {
+                self.library_filter
+                    .as_deref()
+                    .is_none_or(|library| snippet.library_name() == library)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// index of the selected snippet in the library
+    pub(super) fn selected(&self) -> Option<usize> {
+        let position = self.list_state.selected?;
+        self.visible().get(position).copied()
+    }
+
+    /// selects a snippet, showing all libraries again if the filter hides it
+    pub(super) fn select(&mut self, idx: usize) {
+        let mut visible = self.visible();
+        if !visible.contains(&idx) && self.library_filter.take().is_some() {
+            visible = self.visible();
+        }
+        self.list_state
+            .select(visible.iter().position(|&i| i == idx));
+    }
+
+    /// tells the user a team snippet can't be changed, true if it is one
+    pub(super) fn read_only(&mut self, idx: usize) -> bool {
+        let Some(library) = self.save_snippets.get(idx).and_then(|s| s.library.clone()) else {
+            return false;
+        };
+        self.notify(format!(
+            "This snippet is from the read-only {library} library, press p to fork it"
+        ));
+        true
+    }
+
+    /// copies a team snippet into the personal library, where it can be edited
+    pub(super) fn fork(&mut self, idx: usize) {
+        let Some(before) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        let Some(library) = before.library.clone() else {
+            self.notify("This snippet is already in your personal library");
+            return;
+        };
+        let mut after = before.clone();
+        // same id, so the fork hides the team version the next time the libraries load
+        after.library = None;
+        after.forked_from = Some(library.clone());
+        self.change(Change::update(idx, before, after));
+        self.notify(format!(
+            "Forked the snippet from {library} into your personal library"
+        ));
+    }
+
+    /// cycles the list through all libraries, the personal one and each team library
+    pub(super) fn cycle_library_filter(&mut self) {
+        let mut libraries = vec![PERSONAL_LIBRARY.to_string()];
+        for snippet in &self.save_snippets {
+            if let Some(library) = &snippet.library
+                && !libraries.contains(library)
+            {
+                libraries.push(library.clone());
+            }
+        }
+        if libraries.len() == 1 {
+            self.notify("No team libraries, add them under [[libraries]] in the config");
+            return;
+        }
+        let selected = self.selected();
+        let next = match &self.library_filter {
+            None => 0,
+            Some(current) => libraries
+                .iter()
+                .position(|l| l == current)
+                .map_or(0, |i| i + 1),
+        };
+        self.library_filter = libraries.get(next).cloned();
+        // stay on the selected snippet if it is still shown
+        let visible = self.visible();
+        let position = selected
+            .and_then(|idx| visible.iter().position(|&i| i == idx))
+            .or((!visible.is_empty()).then_some(0));
+        self.list_state.select(position);
+        match &self.library_filter {
+            Some(library) => self.notify(format!("Showing the {library} library")),
+            None => self.notify("Showing all libraries"),
+        }
+    }
+}
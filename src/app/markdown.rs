@@ -0,0 +1,215 @@
+use std::path::Path;
+
+use crate::app::{
+    SaveSnippet,
+    highlight::Highlighter,
+    import::Candidate,
+    language::guess_lang,
+    source::{self, Source},
+};
+
+/// an open fenced code block
+struct Fence {
+    marker: char,
+    len: usize,
+    indent: usize,
+    info: String,
+    /// line number of the first line inside the block, 1-based
+    start: usize,
+    lines: Vec<String>,
+}
+
+/// finds the fenced code blocks of a Markdown file, titled after the heading above them
+pub fn parse(text: &str, path: &Path, highlighter: &Highlighter) -> Vec<Candidate> {
+    let file_name = path
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let hash = source::file_hash(text);
+    let mut heading: Option<String> = None;
+    // blocks under the current heading, later ones get numbered titles
+    let mut blocks = 0;
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut fence: Option<Fence> = None;
+    let mut candidates = Vec::new();
+
+    // `None` marks the end of the file, which closes a fence left open like CommonMark does
+    for (i, line) in text.lines().map(Some).chain([None]).enumerate() {
+        if let Some(open) = &mut fence {
+            if line.is_none_or(|line| is_closing(line, open.marker, open.len)) {
+                let open = fence.take().expect("fence is open");
+                let code = open.lines.join("\n");
+                if code.trim().is_empty() {
+                    continue;
+                }
+                let mut snippet =
+                    SaveSnippet::new(code.clone(), language(&open.info, &code, highlighter));
+                blocks += 1;
+                let title = heading.clone().unwrap_or_else(|| file_name.clone());
+                snippet.title = match blocks {
+                    1 => title,
+                    n => format!("{title} ({n})"),
+                };
+                snippet.desc = paragraph
+                    .iter()
+                    .filter(|line| !line.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                // indented blocks lose their indentation, so they would never match the file again
+                if open.indent == 0 {
+                    snippet.source = Some(Source {
+                        path: path.to_path_buf(),
+                        lines: Some((open.start, open.start + open.lines.len() - 1)),
+                        hash: hash.clone(),
+                    });
+                }
+                candidates.push(Candidate::new(
+                    snippet,
+                    format!("{}:{}", path.display(), open.start),
+                ));
+                // the paragraph only describes the first block after it
+                paragraph.clear();
+            } else if let Some(line) = line {
+                let content = strip_indent(line, open.indent);
+                open.lines.push(content.to_string());
+            }
+            continue;
+        }
+        let Some(line) = line else {
+            break;
+        };
+
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if let Some((marker, len)) = fence_marker(trimmed) {
+            fence = Some(Fence {
+                marker,
+                len,
+                indent,
+                info: trimmed[len..].trim().to_string(),
+                start: i + 2,
+                lines: Vec::new(),
+            });
+        } else if let Some(title) = heading_text(trimmed) {
+            heading = Some(title);
+            blocks = 0;
+            paragraph.clear();
+        } else if trimmed.is_empty() {
+            // keep the paragraph until the next block, but start over on the next text
+            if paragraph.last().is_some_and(|last| !last.is_empty()) {
+                paragraph.push(String::new());
+            }
+        } else {
+            if paragraph.last().is_some_and(|last| last.is_empty()) {
+                paragraph.clear();
+            }
+            paragraph.push(trimmed.to_string());
+        }
+    }
+    candidates
+}
+
+/// the character and length of an opening fence, e.g. ("`", 3)
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == marker).count();
+    // backticks are not allowed in the info string of a backtick fence
+    (len >= 3 && !(marker == '`' && line[len..].contains('`'))).then_some((marker, len))
+}
+
+fn is_closing(line: &str, marker: char, len: usize) -> bool {
+    let trimmed = line.trim();
+    trimmed.len() >= len && trimmed.chars().all(|c| c == marker)
+}
+
+fn heading_text(line: &str) -> Option<String> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 || !line[level..].starts_with(' ') {
+        return None;
+    }
+    Some(
+        line[level..]
+            .trim()
+            .trim_end_matches('#')
+            .trim()
+            .to_string(),
+    )
+}
+
+/// removes up to `indent` leading spaces, like the fence itself was indented
+fn strip_indent(line: &str, indent: usize) -> &str {
+    let spaces = line.chars().take(indent).take_while(|c| *c == ' ').count();
+    &line[spaces..]
+}
+
+/// file extension for the info string of a block, guessed from the code if there is none
+fn language(info: &str, code: &str, highlighter: &Highlighter) -> String {
+    let token = info
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '{')
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('.')
+        .to_lowercase();
+    let token = match token.as_str() {
+        "shell" | "console" | "shell-session" | "zsh" => "sh",
+        "yml" => "yaml",
+        "c++" => "cpp",
+        other => other,
+    };
+    if token.is_empty() {
+        return guess_lang(code);
+    }
+    highlighter
+        .extension_for(token)
+        .unwrap_or_else(|| guess_lang(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(text: &str) -> Vec<SaveSnippet> {
+        parse(text, Path::new("runbook.md"), &Highlighter::new())
+            .into_iter()
+            .map(|candidate| candidate.snippet)
+            .collect()
+    }
+
+    #[test]
+    fn titles_and_descriptions() {
+        let text = "# Deploy\n\nRoll out the api.\n\n```sh\nkubectl rollout restart deploy/api\n```\n\n```sh\nkubectl get pods\n```\n";
+        let snippets = blocks(text);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].title, "Deploy");
+        assert_eq!(snippets[0].desc, "Roll out the api.");
+        assert_eq!(snippets[0].code, "kubectl rollout restart deploy/api");
+        assert_eq!(snippets[0].lang, "sh");
+        assert_eq!(snippets[1].title, "Deploy (2)");
+        assert_eq!(snippets[1].desc, "");
+        assert_eq!(snippets[0].source.as_ref().unwrap().lines, Some((6, 6)));
+    }
+
+    #[test]
+    fn unclosed_fence_runs_to_the_end() {
+        let snippets = blocks("# Cleanup\n\n```sh\ndocker system prune\ndocker volume prune");
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].code, "docker system prune\ndocker volume prune");
+    }
+
+    #[test]
+    fn longer_fences_and_tildes() {
+        let text = "````md\n```sh\nls\n```\n````\n~~~\n{\"a\": 1}\n~~~\n";
+        let snippets = blocks(text);
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].code, "```sh\nls\n```");
+        assert_eq!(snippets[1].code, "{\"a\": 1}");
+    }
+
+    #[test]
+    fn indented_fences_lose_their_indentation() {
+        let snippets = blocks("1. Build\n\n   ```sh\n   cargo build\n   ```\n");
+        assert_eq!(snippets[0].code, "cargo build");
+        assert!(snippets[0].source.is_none());
+    }
+}
@@ -0,0 +1,635 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Constraint::{Fill, Length},
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState as ConflictState},
+};
+
+use crate::app::{CodeCache, SaveSnippet, View, codesnippet::new_id, diff::DiffView, undo::Change};
+
+/// a snippet both sides changed in different ways, the other fields are already merged
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub ours: SaveSnippet,
+    pub theirs: SaveSnippet,
+}
+
+/// outcome of merging two versions of a library
+#[derive(Debug, Clone, Default)]
+pub struct Merged {
+    /// the merged library, conflicting snippets in their `ours` version
+    pub snippets: Vec<SaveSnippet>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merged {
+    /// the merged library with their version of conflicting snippets stored in `conflict`
+    pub fn into_snippets(self) -> Vec<SaveSnippet> {
+        let mut theirs: HashMap<String, SaveSnippet> = self
+            .conflicts
+            .into_iter()
+            .map(|conflict| (conflict.ours.id.clone(), conflict.theirs))
+            .collect();
+        self.snippets
+            .into_iter()
+            .map(|mut snippet| {
+                if let Some(other) = theirs.remove(&snippet.id) {
+                    snippet.conflict = Some(Box::new(other));
+                }
+                snippet
+            })
+            .collect()
+    }
+}
+
+/// how a conflict is resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// our version stays, theirs is added as a copy tagged `conflict`
+    Both,
+}
+
+/// the snippets that replace a conflicting one after resolving it
+pub fn resolve(snippet: &SaveSnippet, resolution: Resolution) -> Vec<SaveSnippet> {
+    let mut ours = snippet.clone();
+    let Some(theirs) = ours.conflict.take() else {
+        return vec![ours];
+    };
+    let mut theirs = *theirs;
+    theirs.conflict = None;
+    match resolution {
+        Resolution::Ours => vec![ours],
+        Resolution::Theirs => vec![theirs],
+        Resolution::Both => {
+            theirs.id = new_id();
+            theirs.title = format!("{} (conflict)", theirs.title);
+            if !theirs.tags.iter().any(|tag| tag == "conflict") {
+                theirs.tags.push("conflict".to_string());
+            }
+            vec![ours, theirs]
+        }
+    }
+}
+
+/// three-way merge of libraries by snippet id, `base` being their last common version.
+/// edits to different fields of a snippet are combined, a deletion loses against an edit.
+pub fn merge(base: &[SaveSnippet], ours: &[SaveSnippet], theirs: &[SaveSnippet]) -> Merged {
+    let by_id = |snippets: &[SaveSnippet]| -> HashMap<String, usize> {
+        snippets
+            .iter()
+            .enumerate()
+            .map(|(idx, snippet)| (snippet.id.clone(), idx))
+            .collect()
+    };
+    let (base_ids, our_ids, their_ids) = (by_id(base), by_id(ours), by_id(theirs));
+    let in_base = |id: &str| base_ids.get(id).map(|&idx| &base[idx]);
+
+    let mut merged = Merged::default();
+    for snippet in ours {
+        let old = in_base(&snippet.id);
+        match their_ids.get(&snippet.id).map(|&idx| &theirs[idx]) {
+            Some(other) => {
+                let (snippet, conflicting) = merge_snippet(old, snippet, other);
+                if let Some(theirs) = conflicting {
+                    merged.conflicts.push(Conflict {
+                        ours: snippet.clone(),
+                        theirs,
+                    });
+                }
+                merged.snippets.push(snippet);
+            }
+            // deleted by them, kept if we edited it since
+            None if old == Some(snippet) => {}
+            None => merged.snippets.push(snippet.clone()),
+        }
+    }
+
+    // their new snippets go after the snippet they follow, so the order stays close to theirs
+    for (idx, snippet) in theirs.iter().enumerate() {
+        if our_ids.contains_key(&snippet.id) {
+            continue;
+        }
+        if in_base(&snippet.id) == Some(snippet) {
+            // deleted by us and not edited by them
+            continue;
+        }
+        let after = theirs[..idx]
+            .iter()
+            .rev()
+            .find_map(|previous| merged.snippets.iter().position(|s| s.id == previous.id));
+        match after {
+            Some(position) => merged.snippets.insert(position + 1, snippet.clone()),
+            None => {
+                let position = if idx == 0 { 0 } else { merged.snippets.len() };
+                merged.snippets.insert(position, snippet.clone());
+            }
+        }
+    }
+    merged
+}
+
+/// merges two versions of a snippet field by field. on a conflict the merged snippet
+/// has our values of the conflicting fields and their version of it comes along.
+fn merge_snippet(
+    base: Option<&SaveSnippet>,
+    ours: &SaveSnippet,
+    theirs: &SaveSnippet,
+) -> (SaveSnippet, Option<SaveSnippet>) {
+    if ours == theirs {
+        return (ours.clone(), None);
+    }
+    let mut merged = ours.clone();
+    let mut other = ours.clone();
+    let merged_fields = [
+        merge_field(
+            base.map(|b| &b.title),
+            &theirs.title,
+            &mut merged.title,
+            &mut other.title,
+        ),
+        merge_field(
+            base.map(|b| &b.desc),
+            &theirs.desc,
+            &mut merged.desc,
+            &mut other.desc,
+        ),
+        merge_field(
+            base.map(|b| &b.code),
+            &theirs.code,
+            &mut merged.code,
+            &mut other.code,
+        ),
+        merge_field(
+            base.map(|b| &b.lang),
+            &theirs.lang,
+            &mut merged.lang,
+            &mut other.lang,
+        ),
+        merge_field(
+            base.map(|b| &b.tags),
+            &theirs.tags,
+            &mut merged.tags,
+            &mut other.tags,
+        ),
+        merge_field(
+            base.map(|b| &b.trigger),
+            &theirs.trigger,
+            &mut merged.trigger,
+            &mut other.trigger,
+        ),
+        merge_field(
+            base.map(|b| &b.template),
+            &theirs.template,
+            &mut merged.template,
+            &mut other.template,
+        ),
+        merge_field(
+            base.map(|b| &b.source),
+            &theirs.source,
+            &mut merged.source,
+            &mut other.source,
+        ),
+        merge_field(
+            base.map(|b| &b.forked_from),
+            &theirs.forked_from,
+            &mut merged.forked_from,
+            &mut other.forked_from,
+        ),
+        // a conflict resolved on one side stays resolved
+        merge_field(
+            base.map(|b| &b.conflict),
+            &theirs.conflict,
+            &mut merged.conflict,
+            &mut other.conflict,
+        ),
+    ];
+    // sealed code only makes sense together with the secret flag
+    let secret = |s: &SaveSnippet| (s.secret, s.sealed.clone());
+    let secret_merged = match pick(base.map(secret), secret(ours), secret(theirs)) {
+        Some((flag, sealed)) => {
+            (merged.secret, merged.sealed) = (flag, sealed.clone());
+            (other.secret, other.sealed) = (flag, sealed);
+            true
+        }
+        None => {
+            (other.secret, other.sealed) = secret(theirs);
+            false
+        }
+    };
+    let conflicting = !secret_merged || merged_fields.contains(&false);
+
+    // both sides keep every revision either of them made
+    let mut history = ours.history.clone();
+    for revision in &theirs.history {
+        if !history.contains(revision) {
+            history.push(revision.clone());
+        }
+    }
+    history.sort_by_key(|revision| revision.at);
+    merged.last_run = newest(&ours.last_run, &theirs.last_run, |run| run.at);
+    merged.check = newest(&ours.check, &theirs.check, |check| check.at);
+    other.history = history.clone();
+    merged.history = history;
+    other.last_run = merged.last_run.clone();
+    other.check = merged.check.clone();
+
+    (merged, conflicting.then_some(other))
+}
+
+/// merges a field into both versions of a conflict, which start out as ours.
+/// returns false if the sides disagree, `other` then gets their value.
+fn merge_field<T: PartialEq + Clone>(
+    base: Option<&T>,
+    theirs: &T,
+    merged: &mut T,
+    other: &mut T,
+) -> bool {
+    match pick(base, &*merged, theirs) {
+        Some(value) => {
+            let value = value.clone();
+            *other = value.clone();
+            *merged = value;
+            true
+        }
+        None => {
+            *other = theirs.clone();
+            false
+        }
+    }
+}
+
+/// the value of a field after a three-way merge, None if both sides changed it differently
+fn pick<T: PartialEq>(base: Option<T>, ours: T, theirs: T) -> Option<T> {
+    if ours == theirs {
+        return Some(ours);
+    }
+    match base {
+        Some(base) if base == ours => Some(theirs),
+        Some(base) if base == theirs => Some(ours),
+        _ => None,
+    }
+}
+
+fn newest<T: Clone>(ours: &Option<T>, theirs: &Option<T>, at: fn(&T) -> u64) -> Option<T> {
+    match (ours, theirs) {
+        (Some(a), Some(b)) if at(b) > at(a) => Some(b.clone()),
+        (Some(a), _) => Some(a.clone()),
+        (None, b) => b.clone(),
+    }
+}
+
+/// state of the conflict resolver
+pub struct ConflictPanel {
+    state: ConflictState,
+    side_by_side: bool,
+    scroll: u16,
+}
+
+impl CodeCache {
+    /// positions of the snippets with an unresolved conflict
+    pub(super) fn conflicts(&self) -> Vec<usize> {
+        self.save_snippets
+            .iter()
+            .enumerate()
+            .filter(|(_, snippet)| snippet.conflict.is_some())
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// opens the resolver, at the selected snippet if it has a conflict
+    pub(super) fn open_conflicts(&mut self) {
+        let conflicts = self.conflicts();
+        if conflicts.is_empty() {
+            self.notify("No merge conflicts");
+            return;
+        }
+        let selected = self
+            .selected()
+            .and_then(|idx| conflicts.iter().position(|&i| i == idx))
+            .unwrap_or(0);
+        let mut state = ConflictState::default();
+        state.select(Some(selected));
+        self.conflict_panel = Some(ConflictPanel {
+            state,
+            side_by_side: true,
+            scroll: 0,
+        });
+        self.view = View::Merge;
+    }
+
+    pub(super) fn handle_conflict_key(&mut self, key: KeyCode) {
+        let conflicts = self.conflicts();
+        let Some(panel) = &mut self.conflict_panel else {
+            self.view = View::Library;
+            return;
+        };
+        let resolution = match key {
+            KeyCode::Esc => {
+                self.conflict_panel = None;
+                self.view = View::Library;
+                return;
+            }
+            KeyCode::Down => {
+                panel.state.select_next();
+                panel.scroll = 0;
+                return;
+            }
+            KeyCode::Up => {
+                panel.state.select_previous();
+                panel.scroll = 0;
+                return;
+            }
+            KeyCode::PageDown => {
+                panel.scroll = panel.scroll.saturating_add(10);
+                return;
+            }
+            KeyCode::PageUp => {
+                panel.scroll = panel.scroll.saturating_sub(10);
+                return;
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                panel.side_by_side = !panel.side_by_side;
+                return;
+            }
+            KeyCode::Char('o') | KeyCode::Char('O') => Resolution::Ours,
+            KeyCode::Char('t') | KeyCode::Char('T') => Resolution::Theirs,
+            KeyCode::Char('b') | KeyCode::Char('B') => Resolution::Both,
+            _ => return,
+        };
+        let Some(&idx) = panel.state.selected().and_then(|i| conflicts.get(i)) else {
+            return;
+        };
+        panel.scroll = 0;
+        self.resolve_conflict(idx, resolution);
+    }
+
+    /// replaces a conflicting snippet with the chosen version, as one undoable change
+    fn resolve_conflict(&mut self, idx: usize, resolution: Resolution) {
+        if self.read_only(idx) {
+            return;
+        }
+        let Some(before) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        let mut resolved = resolve(&before, resolution).into_iter();
+        let Some(after) = resolved.next() else {
+            return;
+        };
+        let mut changes = vec![Change::update(idx, before, after)];
+        changes.extend(
+            resolved
+                .enumerate()
+                .map(|(offset, snippet)| Change::Insert {
+                    idx: idx + 1 + offset,
+                    snippet,
+                }),
+        );
+        match changes.len() {
+            1 => self.change(changes.remove(0)),
+            _ => self.change(Change::Batch(changes)),
+        }
+
+        let left = self.conflicts().len();
+        if left == 0 {
+            self.conflict_panel = None;
+            self.view = View::Library;
+            self.notify("All conflicts resolved, press u to undo");
+        } else if let Some(panel) = &mut self.conflict_panel {
+            // the next conflict moves into the place of the resolved one
+            let selected = panel.state.selected().unwrap_or(0).min(left - 1);
+            panel.state.select(Some(selected));
+            self.notify(format!("{left} conflict(s) left, press u to undo"));
+        }
+    }
+
+    pub(super) fn draw_conflicts(&mut self, frame: &mut Frame, area: Rect) {
+        let conflicts = self.conflicts();
+        let Some(panel) = &mut self.conflict_panel else {
+            return;
+        };
+        let [list_area, diff_area] = Layout::horizontal([Length(36), Fill(1)]).areas(area);
+
+        let items: Vec<ListItem> = conflicts
+            .iter()
+            .filter_map(|&idx| self.save_snippets.get(idx))
+            .map(|snippet| ListItem::new(snippet.title.clone()))
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!("{} conflict(s)", conflicts.len()))
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            )
+            .style(Style::default().fg(Color::Rgb(250, 189, 47)))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Rgb(254, 128, 25))
+                    .fg(Color::Rgb(28, 28, 32)),
+            );
+        frame.render_stateful_widget(list, list_area, &mut panel.state);
+
+        let Some((ours, theirs)) = panel
+            .state
+            .selected()
+            .and_then(|i| conflicts.get(i))
+            .and_then(|&idx| self.save_snippets.get(idx))
+            .and_then(|ours| Some((ours, ours.conflict.as_deref()?)))
+        else {
+            return;
+        };
+
+        // metadata both sides changed is shown above the code diff
+        let mut header = Vec::new();
+        for (name, a, b) in [
+            ("title", ours.title.clone(), theirs.title.clone()),
+            ("description", ours.desc.clone(), theirs.desc.clone()),
+            ("language", ours.lang.clone(), theirs.lang.clone()),
+            ("tags", ours.tags.join(" "), theirs.tags.join(" ")),
+            (
+                "trigger",
+                ours.trigger.clone().unwrap_or_default(),
+                theirs.trigger.clone().unwrap_or_default(),
+            ),
+            (
+                "template",
+                ours.template.to_string(),
+                theirs.template.to_string(),
+            ),
+        ] {
+            if a != b {
+                header.push(Line::from(vec![
+                    Span::styled(format!("{name}: "), Style::default().bold()),
+                    Span::styled(a.replace('\n', " "), Style::default().fg(Color::Red)),
+                    Span::raw(" → "),
+                    Span::styled(b.replace('\n', " "), Style::default().fg(Color::Green)),
+                ]));
+            }
+        }
+        if ours.secret != theirs.secret || ours.sealed != theirs.sealed {
+            header.push(Line::from(Span::styled(
+                "secret code differs, reveal it after resolving",
+                Style::default().bold(),
+            )));
+        }
+        let [header_area, code_area] =
+            Layout::vertical([Length(header.len() as u16), Fill(1)]).areas(diff_area);
+        frame.render_widget(Text::from(header), header_area);
+
+        let mode = if panel.side_by_side {
+            "side by side"
+        } else {
+            "unified"
+        };
+        frame.render_widget(
+            DiffView {
+                old: &ours.code,
+                new: &theirs.code,
+                lang: &ours.lang,
+                highlighter: &self.highlighter,
+                side_by_side: panel.side_by_side,
+                scroll: panel.scroll,
+                block: Block::bordered()
+                    .title(format!("ours → theirs ({mode})"))
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(124, 111, 100))),
+            },
+            code_area,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::check::CheckResult;
+
+    fn snippet(id: &str, title: &str, code: &str) -> SaveSnippet {
+        let mut snippet = SaveSnippet::new(code, "sh");
+        snippet.id = id.to_string();
+        snippet.title = title.to_string();
+        snippet
+    }
+
+    fn ids(snippets: &[SaveSnippet]) -> Vec<&str> {
+        snippets.iter().map(|s| s.id.as_str()).collect()
+    }
+
+    #[test]
+    fn combines_edits_to_different_fields() {
+        let base = vec![snippet("a", "List", "ls")];
+        let mut ours = base.clone();
+        ours[0].title = "List files".to_string();
+        let mut theirs = base.clone();
+        theirs[0].code = "ls -la".to_string();
+        theirs[0].tags = vec!["fs".to_string()];
+
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.snippets[0].title, "List files");
+        assert_eq!(merged.snippets[0].code, "ls -la");
+        assert_eq!(merged.snippets[0].tags, ["fs"]);
+    }
+
+    #[test]
+    fn same_field_changed_on_both_sides_conflicts() {
+        let base = vec![snippet("a", "List", "ls")];
+        let mut ours = base.clone();
+        ours[0].code = "ls -l".to_string();
+        ours[0].title = "Long list".to_string();
+        let mut theirs = base.clone();
+        theirs[0].code = "ls -a".to_string();
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts.len(), 1);
+        let conflict = &merged.conflicts[0];
+        assert_eq!(conflict.ours.code, "ls -l");
+        assert_eq!(conflict.theirs.code, "ls -a");
+        // fields only one side changed are merged into both versions
+        assert_eq!(conflict.theirs.title, "Long list");
+
+        let snippets = merged.into_snippets();
+        assert_eq!(snippets[0].conflict.as_ref().unwrap().code, "ls -a");
+        assert_eq!(resolve(&snippets[0], Resolution::Theirs)[0].code, "ls -a");
+        let both = resolve(&snippets[0], Resolution::Both);
+        assert_eq!(both.len(), 2);
+        assert_eq!(both[1].title, "Long list (conflict)");
+        assert_eq!(both[1].tags, ["conflict"]);
+        assert_ne!(both[1].id, both[0].id);
+        assert!(both.iter().all(|s| s.conflict.is_none()));
+    }
+
+    #[test]
+    fn deletions_lose_against_edits() {
+        let base = vec![snippet("a", "A", "a"), snippet("b", "B", "b")];
+        // we deleted a, they edited it; they deleted b, we left it alone
+        let ours = vec![base[1].clone()];
+        let mut theirs = vec![base[0].clone()];
+        theirs[0].code = "a2".to_string();
+
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(ids(&merged.snippets), ["a"]);
+        assert_eq!(merged.snippets[0].code, "a2");
+    }
+
+    #[test]
+    fn new_snippets_keep_their_place() {
+        let base = vec![snippet("a", "A", "a"), snippet("b", "B", "b")];
+        let mut ours = base.clone();
+        ours.push(snippet("o", "Ours", "o"));
+        let theirs = vec![
+            snippet("t0", "First", "0"),
+            base[0].clone(),
+            snippet("t1", "After a", "1"),
+            base[1].clone(),
+        ];
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(ids(&merged.snippets), ["t0", "a", "t1", "b", "o"]);
+    }
+
+    #[test]
+    fn unchanged_secrets_merge_cleanly() {
+        let mut base = vec![snippet("a", "Token", "")];
+        base[0].secret = true;
+        base[0].sealed = Some("sealed-1".to_string());
+        let mut ours = base.clone();
+        ours[0].title = "API token".to_string();
+        let mut theirs = base.clone();
+        theirs[0].tags = vec!["api".to_string()];
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.snippets[0].sealed.as_deref(), Some("sealed-1"));
+
+        // different ciphertext on both sides is a conflict on the code
+        theirs[0].sealed = Some("sealed-2".to_string());
+        ours[0].sealed = Some("sealed-3".to_string());
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(
+            merged.conflicts[0].theirs.sealed.as_deref(),
+            Some("sealed-2")
+        );
+    }
+
+    #[test]
+    fn newest_check_wins() {
+        let check = |at| CheckResult {
+            at,
+            passed: true,
+            diagnostics: String::new(),
+            hash: String::new(),
+        };
+        let base = vec![snippet("a", "A", "a")];
+        let mut ours = base.clone();
+        ours[0].check = Some(check(1));
+        let mut theirs = base.clone();
+        theirs[0].check = Some(check(2));
+        let merged = merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.snippets[0].check.as_ref().unwrap().at, 2);
+    }
+}
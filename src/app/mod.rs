@@ -1,26 +1,96 @@
+mod audit;
+pub mod cheatsheet;
+pub mod check;
+mod clipboard;
 mod codesnippet;
+mod crypto;
+mod diff;
+pub mod dirstore;
+mod editor;
+pub mod export;
+mod form;
+pub mod format;
 mod highlight;
+mod history;
+pub mod import;
+mod inbox;
 mod language;
+mod layers;
+mod markdown;
+pub mod merge;
+mod normalize;
+mod picker;
+mod runner;
+pub mod search;
+mod secrets;
+pub mod source;
+mod template;
+pub mod textmate;
+pub mod timestamp;
+pub mod trash;
+mod undo;
+mod vault;
+pub mod vim;
+pub mod vscode;
+pub mod yasnippet;
 
 // export for main.rs
-pub use codesnippet::{SaveSnippet, SnippetList};
+pub use codesnippet::{SaveSnippet, SnippetList, ensure_ids};
+pub use crypto::{Keyring, prompt_passphrase};
+pub use form::parse_tags;
+pub use highlight::Highlighter;
+pub use history::forget_code;
+pub use inbox::watch;
+pub use template::{fill, template_code};
 
+use audit::{AuditEntry, SecretPrompt};
+use clipboard::ClipboardProvider;
 use codesnippet::CodeSnippet;
-use highlight::Highlighter;
-use tui_dialog::{Dialog, centered_rect};
+use form::{FormTarget, SnippetForm};
+use history::HistoryPanel;
+use inbox::{InboxItem, Watcher, inbox_cards};
+use template::{FillAction, FillPrompt};
+use trash::TrashEntry;
 use tui_popup::Popup;
+use undo::UndoStack;
+use vault::PassphrasePrompt;
 
-use std::time::{Duration, Instant};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
 
-use arboard::Clipboard;
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers,
+    },
+    execute,
+};
 use ratatui::{
     layout::Constraint::{Length, Min},
     prelude::*,
-    widgets::{Block, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    widgets::{self, Block, Scrollbar, ScrollbarOrientation, ScrollbarState},
 };
 use tui_widget_list::ListState;
 
+use crate::config::Config;
+
+/// which list is shown in the main area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Library,
+    Inbox,
+    Audit,
+    Trash,
+    History,
+    Import,
+    Pick,
+    Run,
+    Format,
+    Merge,
+}
+
 pub struct CodeCache {
     running: bool,
     scroll_state: ScrollbarState,
@@ -30,30 +100,141 @@ pub struct CodeCache {
     highlighter: Highlighter,
     snippets: Vec<CodeSnippet>,
     save_snippets: Vec<SaveSnippet>,
-    clipboard: Clipboard,
-    dialog: Dialog,
-    dialog_field: String,
-    edit_idx: usize,
+    clipboard: Box<dyn ClipboardProvider>,
     notification: Option<(String, Instant)>,
+    view: View,
+    inbox: Vec<InboxItem>,
+    inbox_state: ListState,
+    watcher: Option<Watcher>,
+    secret_prompt: Option<SecretPrompt>,
+    /// secrets the user chose to keep, so they are not reported again
+    known_secrets: Vec<String>,
+    audit: Vec<AuditEntry>,
+    audit_state: widgets::ListState,
+    keyring: Keyring,
+    passphrase_prompt: Option<PassphrasePrompt>,
+    undo_stack: UndoStack,
+    trash: Vec<TrashEntry>,
+    trash_cards: Vec<CodeSnippet>,
+    trash_state: ListState,
+    show_help: bool,
+    history: Option<HistoryPanel>,
+    form: Option<SnippetForm>,
+    fill_prompt: Option<FillPrompt>,
+    picker: Option<picker::Picker>,
+    /// snippet and filled in code chosen in the picker
+    picked: Option<(SaveSnippet, String)>,
+    import: Vec<import::Candidate>,
+    import_state: widgets::ListState,
+    /// runner command per language
+    runners: BTreeMap<String, String>,
+    confirm_run: bool,
+    run_confirm: Option<runner::RunConfirm>,
+    run: Option<runner::RunPanel>,
+    /// check command per language
+    checks: BTreeMap<String, String>,
+    /// formatter command per language
+    formatters: BTreeMap<String, String>,
+    format_on_paste: bool,
+    format: Option<format::FormatPanel>,
+    format_job: Option<format::FormatJob>,
+    check_job: Option<check::CheckJob>,
+    /// spaces per tab in pasted indentation, 0 keeps tabs
+    tab_width: usize,
+    paste_prompt: Option<normalize::PastePrompt>,
+    conflict_panel: Option<merge::ConflictPanel>,
+    /// library directory reloaded when its files change
+    dir_watch: Option<dirstore::DirWatch>,
+    /// only snippets of this library are listed
+    library_filter: Option<String>,
+    /// how snippets taken from files compare to them, by id, checked on a timer
+    source_status: HashMap<String, source::Status>,
+    sources_checked_at: Instant,
+    /// ids of snippets put back into the library, still in the trash file until it is saved
+    restored: Vec<String>,
 }
 
 impl CodeCache {
-    pub fn new(snippets: Vec<SaveSnippet>) -> Self {
-        CodeCache {
+    pub fn new(snippets: Vec<SaveSnippet>, config: &Config, keyring: Keyring) -> Self {
+        let purge_error = if config.trash_retention_days > 0 {
+            trash::purge(config.trash_retention_days).err()
+        } else {
+            None
+        };
+        let mut highlighter = Highlighter::new();
+        let theme_error = highlighter.set_theme(&config.theme).err();
+        let mut codecache = CodeCache {
             running: true,
             scroll_state: ScrollbarState::default(),
             list_state: ListState::default(),
             last_move: Instant::now() - Duration::from_secs(1),
             last_move_direction: String::new(),
-            highlighter: Highlighter::new(),
-            snippets: convert_snippets(&snippets),
+            highlighter,
+            snippets: Vec::new(),
             save_snippets: snippets,
-            clipboard: Clipboard::new().expect("failed to initialize clipboard"),
-            dialog: new_dialog(),
-            dialog_field: String::new(),
+            clipboard: clipboard::from_config(config),
             notification: None,
-            edit_idx: 0,
+            view: View::Library,
+            inbox: inbox::load(),
+            inbox_state: ListState::default(),
+            watcher: None,
+            secret_prompt: None,
+            known_secrets: Vec::new(),
+            audit: Vec::new(),
+            audit_state: widgets::ListState::default(),
+            keyring,
+            passphrase_prompt: None,
+            undo_stack: UndoStack::default(),
+            trash: Vec::new(),
+            trash_cards: Vec::new(),
+            trash_state: ListState::default(),
+            show_help: false,
+            history: None,
+            form: None,
+            fill_prompt: None,
+            picker: None,
+            picked: None,
+            import: Vec::new(),
+            import_state: widgets::ListState::default(),
+            runners: runner::runners(&config.runners),
+            confirm_run: config.confirm_run,
+            run_confirm: None,
+            run: None,
+            checks: check::checks(&config.checks),
+            formatters: format::formatters(&config.formatters),
+            format_on_paste: config.format_on_paste,
+            format: None,
+            format_job: None,
+            check_job: None,
+            tab_width: config.tab_width,
+            paste_prompt: None,
+            conflict_panel: None,
+            dir_watch: None,
+            library_filter: None,
+            restored: Vec::new(),
+            source_status: HashMap::new(),
+            sources_checked_at: Instant::now(),
+        };
+        codecache.check_sources();
+        codecache.refresh_cards();
+        if let Some(err) = theme_error {
+            codecache.notify(format!("Config: {err}"));
+        }
+        if let Some(err) = purge_error {
+            codecache.notify(format!("Could not save the trash: {err}"));
+        }
+        let conflicts = codecache.conflicts().len();
+        if conflicts > 0 {
+            codecache.notify(format!(
+                "{conflicts} snippet(s) have merge conflicts, press m to resolve them"
+            ));
         }
+        codecache
+    }
+
+    /// rebuilds the cards after the library changed
+    fn refresh_cards(&mut self) {
+        self.snippets = convert_snippets(&self.save_snippets, &self.source_status);
     }
 
     pub fn notify(&mut self, msg: impl Into<String>) {
@@ -62,14 +243,34 @@ impl CodeCache {
 
     pub fn run(&mut self) -> Vec<SaveSnippet> {
         let mut terminal = ratatui::init();
-        while self.running {
-            terminal
-                .draw(|frame| self.draw(frame))
-                .expect("failed to draw frame");
-            self.handle_events();
-        }
+        // terminal paste works even when there is no clipboard we can read
+        let _ = execute!(std::io::stdout(), EnableBracketedPaste);
+        let snippets = loop {
+            while self.running {
+                terminal
+                    .draw(|frame| self.draw(frame))
+                    .expect("failed to draw frame");
+                self.handle_events();
+            }
+            match self.sealed_snippets() {
+                Ok(snippets) => break snippets,
+                Err(err) => self.unlock_to_quit(err),
+            }
+        };
+        let _ = execute!(std::io::stdout(), DisableBracketedPaste);
         ratatui::restore();
-        self.save_snippets.clone()
+        // team libraries are read-only, only the personal snippets are saved
+        let snippets = snippets
+            .into_iter()
+            .filter(|s| s.library.is_none())
+            .collect();
+        self.forget_secrets();
+        snippets
+    }
+
+    /// keyring of this session, needed to write an encrypted library
+    pub fn keyring(&mut self) -> &mut Keyring {
+        &mut self.keyring
     }
 
     fn draw(&mut self, frame: &mut Frame) {
@@ -109,35 +310,110 @@ impl CodeCache {
             .begin_style(arrow_up_style)
             .end_style(arrow_down_style);
 
-        let selected = self.list_state.selected.unwrap_or(0);
-        self.scroll_state = ScrollbarState::new(self.snippets.len()).position(selected);
+        let visible = self.visible();
+        let (selected, len) = match self.view {
+            View::Library => (self.list_state.selected, visible.len()),
+            View::Inbox => (self.inbox_state.selected, self.inbox.len()),
+            View::Audit => (self.audit_state.selected(), self.audit.len()),
+            View::Trash => (self.trash_state.selected, self.trash.len()),
+            View::History => (None, 0),
+            View::Import => (self.import_state.selected(), self.import.len()),
+            View::Pick | View::Run => (None, 0),
+            View::Format | View::Merge => (None, 0),
+        };
+        self.scroll_state = ScrollbarState::new(len).position(selected.unwrap_or(0));
 
         frame.render_widget(
             Block::new()
-                .title(format!("CodeCache v{}", env!("CARGO_PKG_VERSION")))
+                .title(format!(
+                    "CodeCache v{} (clipboard: {}{})",
+                    env!("CARGO_PKG_VERSION"),
+                    self.clipboard.name(),
+                    if self.watcher.is_some() {
+                        ", capturing"
+                    } else {
+                        ""
+                    }
+                ))
                 .title_alignment(ratatui::layout::Alignment::Center)
                 .title_style(Style::new().fg(Color::Rgb(251, 73, 52)).bold()),
             title_area,
         );
+        let status = match self.view {
+            View::Library => format!(
+                "{}{} snippet(s) stored ({} lines) - press v to paste from clipboard, n to write a new one, d to delete selected, c to copy selected, e to edit, q to quit, ? for all keys",
+                self.library_filter
+                    .as_ref()
+                    .map(|library| format!("{library} library: {} of ", visible.len()))
+                    .unwrap_or_default(),
+                self.snippets.len(),
+                self.save_snippets
+                    .iter()
+                    .map(|s| s.code.lines().count())
+                    .sum::<usize>()
+            ),
+            View::Inbox => format!(
+                "{} clip(s) in inbox - press enter to add to library, d to discard, c to copy, w to toggle capture, esc to go back",
+                self.inbox.len()
+            ),
+            View::Audit => format!(
+                "{} snippet(s) with possible secrets - press enter to show snippet, r to replace secrets with placeholders, esc to go back",
+                self.audit.len()
+            ),
+            View::Trash => format!(
+                "{} snippet(s) in trash - press enter to restore, d to delete permanently, esc to go back",
+                self.trash.len()
+            ),
+            View::History => "press enter to restore the selected version, s to toggle side by side diff, pgup/pgdown to scroll, esc to go back".to_string(),
+            View::Import => "press space to check or uncheck, a to toggle all, enter to import the checked snippets, esc to cancel".to_string(),
+            View::Pick => "type to search, ↑/↓ to move, enter to print the selected snippet, esc to cancel".to_string(),
+            View::Format => "press space to check or uncheck, a to toggle all, s to toggle side by side diff, enter to apply the checked changes, esc to cancel".to_string(),
+            View::Merge => "press o to keep ours, t to keep theirs, b to keep both, s to toggle side by side diff, pgup/pgdown to scroll, esc to go back".to_string(),
+            View::Run => "ctrl+c to stop, r to run again, s to store the output with the snippet, pgup/pgdown to scroll, esc to go back".to_string(),
+        };
         frame.render_widget(
             Block::new()
-                .title(format!(
-                    "{} snippet(s) stored ({} lines) - press v to paste from clipboard, d to delete selected, c to copy selected, q to quit, e to edit",
-                    self.snippets.len(),
-                    self.save_snippets.iter().map(|s| s.code.lines().count()).sum::<usize>()
-                ))
+                .title(status)
                 .title_alignment(ratatui::layout::Alignment::Center)
                 .title_style(Style::default().fg(Color::Cyan)),
             status_area,
         );
-        frame.render_widget(
-            SnippetList {
-                state: &mut self.list_state,
-                items: self.snippets.clone(),
-                highlighter: &self.highlighter,
-            },
-            main_area,
-        );
+        match self.view {
+            View::Library => frame.render_widget(
+                SnippetList {
+                    state: &mut self.list_state,
+                    items: visible
+                        .iter()
+                        .map(|&idx| self.snippets[idx].clone())
+                        .collect(),
+                    highlighter: &self.highlighter,
+                },
+                main_area,
+            ),
+            View::Inbox => frame.render_widget(
+                SnippetList {
+                    state: &mut self.inbox_state,
+                    items: inbox_cards(&self.inbox),
+                    highlighter: &self.highlighter,
+                },
+                main_area,
+            ),
+            View::Audit => self.draw_audit(frame, main_area),
+            View::History => self.draw_history(frame, main_area),
+            View::Import => self.draw_import(frame, main_area),
+            View::Pick => self.draw_pick(frame, main_area),
+            View::Run => self.draw_run(frame, main_area),
+            View::Format => self.draw_format(frame, main_area),
+            View::Merge => self.draw_conflicts(frame, main_area),
+            View::Trash => frame.render_widget(
+                SnippetList {
+                    state: &mut self.trash_state,
+                    items: self.trash_cards.clone(),
+                    highlighter: &self.highlighter,
+                },
+                main_area,
+            ),
+        }
         frame.render_stateful_widget(
             scrollbar,
             main_area.inner(Margin {
@@ -147,24 +423,32 @@ impl CodeCache {
             &mut self.scroll_state,
         );
 
-        if self.dialog.open {
-            let dialog_area = centered_rect(frame.area(), 60, 10, 0, 0);
-            frame.render_widget(self.dialog.clone(), dialog_area);
-        } else {
-            // clear area if its closed
-            let dialog_area = centered_rect(frame.area(), 60, 10, 0, 0);
-            frame.render_widget(Block::new(), dialog_area);
+        self.draw_form(frame);
+        self.draw_fill_prompt(frame);
+        self.draw_run_confirm(frame);
+        self.draw_paste_prompt(frame);
+
+        if self.show_help {
+            frame.render_widget(
+                Popup::new(HELP)
+                    .title("Keys")
+                    .style(Style::default().fg(Color::LightBlue)),
+                frame.area(),
+            );
         }
 
-        if let Some((msg, shown_at)) = &self.notification {
-            if shown_at.elapsed() < Duration::from_secs(2) {
-                frame.render_widget(
-                    &Popup::new(msg.as_str())
-                        .title("Info")
-                        .style(Style::default().fg(Color::LightBlue)),
-                    frame.area(),
-                );
-            }
+        self.draw_secret_prompt(frame);
+        self.draw_passphrase_prompt(frame);
+
+        if let Some((msg, shown_at)) = &self.notification
+            && shown_at.elapsed() < Duration::from_secs(2)
+        {
+            frame.render_widget(
+                Popup::new(msg.as_str())
+                    .title("Info")
+                    .style(Style::default().fg(Color::LightBlue)),
+                frame.area(),
+            );
         }
     }
 
@@ -172,152 +456,340 @@ impl CodeCache {
         if event::poll(Duration::from_millis(16)).expect("failed to poll event") {
             match event::read().expect("failed to read event") {
                 Event::Key(key) if key.kind == KeyEventKind::Press => {
-                    if self.dialog.open {
-                        self.dialog.key_action(&key.code);
-                        if self.dialog.submitted {
-                            let input = self.dialog.submitted_input.clone();
-                            if !input.is_empty() {
-                                if self.dialog_field == "title" {
-                                    if let Some(item) = self.save_snippets.get_mut(self.edit_idx) {
-                                        item.title = input;
-                                    }
-                                } else if self.dialog_field == "desc" {
-                                    if let Some(item) = self.save_snippets.get_mut(self.edit_idx) {
-                                        item.desc = input;
-                                    }
-                                } else if self.dialog_field == "lang" {
-                                    if let Some(item) = self.save_snippets.get_mut(self.edit_idx) {
-                                        item.lang = input;
-                                    }
-                                }
-                            }
-                            self.snippets = convert_snippets(&self.save_snippets);
-                            self.dialog = new_dialog();
-                            if self.dialog_field == "title" {
-                                self.dialog_field = "desc".to_string();
-                                self.dialog.open = true;
-                                self.dialog = self.dialog.title_top("Enter Description");
-                            } else if self.dialog_field == "desc" {
-                                self.dialog_field = "lang".to_string();
-                                self.dialog.open = true;
-                                self.dialog = self.dialog.title_top(
-                                    "Enter programming language extension (\"rs\" for rust)",
-                                );
-                            } else {
-                                self.dialog_field = String::new();
-                            }
-                        }
+                    if self.passphrase_prompt.is_some() {
+                        self.handle_passphrase_key(key.code);
+                    } else if self.secret_prompt.is_some() {
+                        self.handle_secret_prompt_key(key.code);
+                    } else if self.form.is_some() {
+                        self.handle_form_key(key);
+                    } else if self.fill_prompt.is_some() {
+                        self.handle_fill_key(key);
+                    } else if self.paste_prompt.is_some() {
+                        self.handle_paste_prompt_key(key.code);
+                    } else if self.run_confirm.is_some() {
+                        self.handle_run_confirm_key(key.code);
+                    } else if self.show_help {
+                        self.show_help = false;
                     } else {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
-                            KeyCode::Down | KeyCode::PageDown => {
-                                self.list_state.next();
-                                self.scroll_state.next();
-                                self.last_move = Instant::now();
-                                self.last_move_direction = "down".to_string();
-                            }
-                            KeyCode::Up | KeyCode::PageUp => {
-                                self.list_state.previous();
-                                self.scroll_state.prev();
-                                self.last_move = Instant::now();
-                                self.last_move_direction = "up".to_string();
-                            }
-                            KeyCode::Char('v') | KeyCode::Char('V') => {
-                                match self.clipboard.get_text() {
-                                    Ok(clipboard_text) => {
-                                        // clean up
-                                        if clipboard_text
-                                            .find(|c: char| {
-                                                c.is_ascii()
-                                                    && !c.is_whitespace()
-                                                    && !c.is_control()
-                                            })
-                                            .is_none()
-                                        {
-                                            return;
-                                        }
-                                        let cleaned: String = clipboard_text
-                                            .chars()
-                                            .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
-                                            .collect();
-                                        let trimmed = cleaned.trim_ascii_start();
-                                        if !trimmed.is_empty() {
-                                            self.save_snippets.push(SaveSnippet {
-                                                title: String::new(),
-                                                desc: String::new(),
-                                                code: trimmed.to_string(),
-                                                lang: "txt".to_string(),
-                                            });
-                                            self.snippets = convert_snippets(&self.save_snippets);
-                                            self.dialog.open = true;
-                                            self.dialog = self.dialog.title_top("Enter Title");
-                                            self.dialog_field = "title".to_string();
-                                            self.edit_idx =
-                                                self.save_snippets.len().saturating_sub(1);
-                                        } else {
-                                            self.notify("Clipboard is empty");
-                                        }
-                                    }
-                                    Err(_) => {
-                                        self.notify("Failed to access clipboard");
-                                    }
-                                }
-                            }
-                            KeyCode::Char('d') | KeyCode::Char('D') => {
-                                if let Some(idx) = self.list_state.selected {
-                                    self.save_snippets.remove(idx);
-                                    self.snippets.remove(idx);
-                                    if self.save_snippets.is_empty() {
-                                        self.list_state.select(None);
-                                    } else if idx >= self.save_snippets.len() {
-                                        self.list_state.select(Some(idx - 1));
-                                    }
-                                }
-                            }
-                            KeyCode::Char('c') | KeyCode::Char('C') => {
-                                if let Some(idx) = self.list_state.selected
-                                    && let Some(snippet) = self.save_snippets.get(idx)
-                                {
-                                    if self.clipboard.set_text(snippet.code.clone()).is_err() {
-                                        self.notify("Failed to copy code to clipboard");
-                                    }
-                                }
-                            }
-                            KeyCode::Char('e') | KeyCode::Char('E') => {
-                                if let Some(idx) = self.list_state.selected {
-                                    self.edit_idx = idx;
-                                    self.dialog.open = true;
-                                    self.dialog = self.dialog.title_top("Enter Title");
-                                    self.dialog_field = "title".to_string();
-                                }
-                            }
-                            // TODO: add editing of snippets
-                            _ => {}
+                        match self.view {
+                            View::Library => self.handle_library_key(key),
+                            View::Inbox => self.handle_inbox_key(key.code),
+                            View::Audit => self.handle_audit_key(key.code),
+                            View::Trash => self.handle_trash_key(key.code),
+                            View::History => self.handle_history_key(key.code),
+                            View::Import => self.handle_import_key(key.code),
+                            View::Pick => self.handle_pick_key(key),
+                            View::Run => self.handle_run_key(key),
+                            View::Format => self.handle_format_key(key.code),
+                            View::Merge => self.handle_conflict_key(key.code),
                         }
                     }
                 }
+                Event::Paste(text)
+                    if self.secret_prompt.is_none() && self.passphrase_prompt.is_none() =>
+                {
+                    if self.form.is_some() {
+                        self.paste_into_form(&text);
+                    } else if self.fill_prompt.is_some() {
+                        self.paste_into_fill_prompt(&text);
+                    } else if self.view == View::Pick {
+                        self.paste_into_picker(&text);
+                    } else {
+                        self.paste(&text);
+                    }
+                }
                 _ => {}
             }
         }
+
+        if let Some(watcher) = &mut self.watcher
+            && let Some(text) = watcher.poll(self.clipboard.as_mut())
+        {
+            self.capture(&text);
+        }
+        self.poll_run();
+        self.poll_check();
+        self.poll_format();
+        self.poll_dir();
+        self.poll_sources();
+    }
+
+    fn handle_library_key(&mut self, key: KeyEvent) {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
+            KeyCode::Char('?') => self.show_help = true,
+            KeyCode::Down | KeyCode::Up if shift => {
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.move_snippet(idx, key.code == KeyCode::Down);
+                }
+            }
+            KeyCode::Char('r') if ctrl => self.redo(),
+            KeyCode::Char('u') | KeyCode::Char('U') => self.undo(),
+            KeyCode::Char('t') | KeyCode::Char('T') => self.open_trash(),
+            KeyCode::Char('h') | KeyCode::Char('H') => {
+                if let Some(idx) = self.selected() {
+                    self.open_history(idx);
+                }
+            }
+            KeyCode::Down | KeyCode::PageDown => {
+                self.list_state.next();
+                self.scroll_state.next();
+                self.last_move = Instant::now();
+                self.last_move_direction = "down".to_string();
+            }
+            KeyCode::Up | KeyCode::PageUp => {
+                self.list_state.previous();
+                self.scroll_state.prev();
+                self.last_move = Instant::now();
+                self.last_move_direction = "up".to_string();
+            }
+            KeyCode::Char('v') | KeyCode::Char('V') => match self.clipboard.get_text() {
+                Ok(clipboard_text) => self.paste(&clipboard_text),
+                Err(err) => {
+                    self.notify(format!("Failed to access clipboard: {err}"));
+                }
+            },
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.delete(idx);
+                }
+            }
+            KeyCode::Char('c') | KeyCode::Char('C') => {
+                if let Some(idx) = self.selected()
+                    && let Some(snippet) = self.save_snippets.get(idx)
+                {
+                    if snippet.is_locked() {
+                        self.notify("Reveal the snippet with x before copying it");
+                    } else {
+                        let code = template_code(snippet).into_owned();
+                        self.use_template(&code, FillAction::Copy);
+                    }
+                }
+            }
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                if let Some(idx) = self.selected() {
+                    self.run_snippet(idx);
+                }
+            }
+            KeyCode::Char('f') if ctrl => {
+                // team snippets are read-only
+                let all = self
+                    .visible()
+                    .into_iter()
+                    .filter(|&idx| self.save_snippets[idx].library.is_none())
+                    .collect();
+                self.format_snippets(all);
+            }
+            KeyCode::Char('f') | KeyCode::Char('F') => {
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.format_snippet(idx);
+                }
+            }
+            KeyCode::Char('k') | KeyCode::Char('K') => {
+                // the result is stored with the snippet, team snippets are read-only
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.check_snippet(idx);
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') => {
+                self.open_form(FormTarget::New, &SaveSnippet::new("", ""));
+            }
+            KeyCode::Char('e') | KeyCode::Char('E') => {
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.start_edit(idx);
+                }
+            }
+            KeyCode::Char('o') | KeyCode::Char('O') => {
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.refresh_from_source(idx);
+                }
+            }
+            KeyCode::Char('p') | KeyCode::Char('P') => {
+                if let Some(idx) = self.selected() {
+                    self.fork(idx);
+                }
+            }
+            KeyCode::Char('l') | KeyCode::Char('L') => self.cycle_library_filter(),
+            KeyCode::Char('m') | KeyCode::Char('M') => self.open_conflicts(),
+            KeyCode::Char('w') | KeyCode::Char('W') => self.toggle_capture(),
+            KeyCode::Char('i') | KeyCode::Char('I') => self.open_inbox(),
+            KeyCode::Char('a') | KeyCode::Char('A') => self.open_audit(),
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                if let Some(idx) = self.selected()
+                    && !self.read_only(idx)
+                {
+                    self.toggle_secret(idx);
+                }
+            }
+            KeyCode::Char('x') | KeyCode::Char('X') => {
+                if let Some(idx) = self.selected() {
+                    self.toggle_reveal(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// opens the form to edit all fields of a snippet
+    fn start_edit(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        if snippet.is_locked() {
+            self.notify("Reveal the snippet with x before editing it");
+            return;
+        }
+        // secrets that are already in the snippet were seen before, don't warn about them again
+        self.known_secrets.extend(audit::secret_values(&snippet));
+        self.open_form(FormTarget::Edit(idx), &snippet);
+    }
+
+    /// copies text without the clipboard watcher picking it up again
+    fn copy(&mut self, text: &str) {
+        match self.clipboard.set_text(text) {
+            Ok(()) => {
+                if let Some(watcher) = &mut self.watcher {
+                    watcher.ignore(text);
+                }
+            }
+            Err(err) => self.notify(format!("Failed to copy code to clipboard: {err}")),
+        }
+    }
+
+    /// adds pasted text as a new snippet and asks for its details
+    fn paste(&mut self, text: &str) {
+        // clean up
+        if text
+            .find(|c: char| c.is_ascii() && !c.is_whitespace() && !c.is_control())
+            .is_none()
+        {
+            self.notify("Clipboard is empty");
+            return;
+        }
+        // line endings are normalized before control characters go
+        let cleaned: String = normalize::normalize(text, self.tab_width)
+            .chars()
+            .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+            .collect();
+        if !cleaned.trim().is_empty() {
+            self.paste_code(cleaned);
+        } else {
+            self.notify("Clipboard is empty");
+        }
     }
 }
 
+const HELP: &str = "v       paste from clipboard
+n       write a new snippet
+c       copy selected, filling in template placeholders
+r       run selected
+k       check selected with the compiler or linter of its language
+f       format selected
+ctrl+f  format all snippets
+e       edit selected
+d       move selected to trash
+u       undo
+ctrl+r  redo
+shift+↑ move selected up
+shift+↓ move selected down
+t       open trash
+h       show history of selected
+o       refresh selected from its source file
+m       resolve merge conflicts
+p       fork selected team snippet into your personal library
+l       show all libraries, the personal one or one team library
+w       toggle clipboard capture
+i       open inbox
+a       audit secrets
+s       mark selected as secret
+x       reveal or hide secret
+q       quit";
+
 /// converts Vec<SaveSnippet> to Vec<CodeSnippet>
-fn convert_snippets(snippets: &[SaveSnippet]) -> Vec<CodeSnippet> {
+fn convert_snippets(
+    snippets: &[SaveSnippet],
+    source_status: &HashMap<String, source::Status>,
+) -> Vec<CodeSnippet> {
+    // the source badge only tells something once team libraries are mounted
+    let layered = snippets.iter().any(|snip| snip.library.is_some());
     snippets
         .iter()
         .map(|snip| {
-            CodeSnippet::new(
-                snip.title.clone(),
-                snip.desc.clone(),
-                snip.code.clone(),
-                snip.lang.clone(),
-            )
+            let card = if snip.is_locked() {
+                CodeSnippet::new(
+                    format!("🔒 {}", snip.title),
+                    snip.desc.clone(),
+                    "secret snippet, press x to reveal".to_string(),
+                    "txt".to_string(),
+                )
+            } else {
+                CodeSnippet::new(
+                    snip.title.clone(),
+                    snip.desc.clone(),
+                    snip.code.clone(),
+                    snip.lang.clone(),
+                )
+            }
+            .with_tags(&snip.tags);
+            let card = match &snip.check {
+                Some(result) if !snip.is_locked() => {
+                    let (badge, failed) = check::badge(result, &snip.code);
+                    let diagnostic = result.diagnostics.lines().next().map(str::to_string);
+                    card.with_check(badge, failed, diagnostic)
+                }
+                _ => card,
+            };
+            let card = match (&snip.library, &snip.forked_from) {
+                (Some(library), _) => card.with_library(library.clone()),
+                (None, Some(team)) if layered => {
+                    card.with_library(format!("{}, forked from {team}", snip.library_name()))
+                }
+                (None, _) if layered => card.with_library(snip.library_name().to_string()),
+                _ => card,
+            };
+            let card = if snip.conflict.is_some() {
+                card.with_conflict()
+            } else {
+                card
+            };
+            let card = match &snip.last_run {
+                Some(run) => card.with_last_run(run.summary(), run.exit != Some(0)),
+                None => card,
+            };
+            match &snip.source {
+                Some(source) if !snip.is_locked() => {
+                    let status = source_status
+                        .get(&snip.id)
+                        .cloned()
+                        .unwrap_or(source::Status::Fresh);
+                    let stale = status != source::Status::Fresh;
+                    let label = if stale {
+                        format!(
+                            "{} ({}, press o to refresh)",
+                            source.describe(),
+                            status.label()
+                        )
+                    } else {
+                        source.describe()
+                    };
+                    card.with_source(label, stale)
+                }
+                _ => card,
+            }
         })
         .collect()
 }
-
-/// creates a new dialog with custom options
-fn new_dialog() -> Dialog {
-    Dialog::default().style(Style::default().fg(Color::LightBlue))
-}
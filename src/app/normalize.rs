@@ -0,0 +1,306 @@
+use std::sync::LazyLock;
+
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use regex::Regex;
+use tui_popup::Popup;
+
+use crate::app::{CodeCache, SaveSnippet, form::FormTarget, language::guess_lang};
+
+/// `12 | code`, `12: code` or `12  code` as copied from editors, diffs and web pages
+static GUTTER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(\d+)(?:\s*[|:│]\s?|\s+|$)").expect("invalid gutter regex"));
+
+/// cleans up pasted text: unix line endings, tabs expanded to `tab_width` spaces (0 keeps them),
+/// no trailing whitespace, no blank lines around it and the common indentation removed
+pub fn normalize(text: &str, tab_width: usize) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| expand_indent(line, tab_width).trim_end().to_string())
+        .collect();
+    let start = lines.iter().position(|line| !line.is_empty());
+    let end = lines.iter().rposition(|line| !line.is_empty());
+    let (Some(start), Some(end)) = (start, end) else {
+        return String::new();
+    };
+    let lines = &lines[start..=end];
+    dedent(lines)
+}
+
+/// replaces tabs in the indentation of a line, tabs after the first character stay
+fn expand_indent(line: &str, tab_width: usize) -> String {
+    if tab_width == 0 {
+        return line.to_string();
+    }
+    let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+    let mut column = 0;
+    let mut expanded = String::new();
+    for c in line[..indent_len].chars() {
+        let width = if c == '\t' {
+            tab_width - column % tab_width
+        } else {
+            1
+        };
+        expanded.push_str(&" ".repeat(width));
+        column += width;
+    }
+    expanded.push_str(&line[indent_len..]);
+    expanded
+}
+
+/// removes the indentation all non-empty lines share, keeping their relative indentation
+fn dedent(lines: &[String]) -> String {
+    let indent =
+        |line: &str| line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string();
+    let common = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| indent(line))
+        .reduce(|common, indent| {
+            common
+                .chars()
+                .zip(indent.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        })
+        .unwrap_or_default();
+    lines
+        .iter()
+        .map(|line| line.strip_prefix(common.as_str()).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// something copied along with the code that can be stripped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefix {
+    LineNumbers,
+    /// `$ ` shell prompts, lines without one are command output
+    ShellPrompt,
+    /// `>>> ` and `... ` of the python console, lines without one are output
+    PythonPrompt,
+}
+
+impl Prefix {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Prefix::LineNumbers => "line numbers",
+            Prefix::ShellPrompt => "shell prompts ($) and command output",
+            Prefix::PythonPrompt => "python prompts (>>>) and output",
+        }
+    }
+}
+
+/// finds line numbers or prompts in front of every line of pasted code
+pub fn detect_prefix(code: &str) -> Option<Prefix> {
+    let lines: Vec<&str> = code
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let first = lines.first()?;
+    if first.starts_with(">>> ") || *first == ">>>" {
+        return Some(Prefix::PythonPrompt);
+    }
+    if first.starts_with("$ ") {
+        return Some(Prefix::ShellPrompt);
+    }
+    // counting lines, blank ones included, so a single number is not enough
+    let numbers: Vec<u64> = code
+        .lines()
+        .map(|line| GUTTER.captures(line).and_then(|caps| caps[1].parse().ok()))
+        .collect::<Option<Vec<u64>>>()?;
+    let consecutive = numbers.windows(2).all(|pair| pair[1] == pair[0] + 1);
+    (numbers.len() >= 2 && consecutive).then_some(Prefix::LineNumbers)
+}
+
+/// removes a detected prefix, dropping output lines of prompts
+pub fn strip_prefix(code: &str, prefix: Prefix) -> String {
+    if prefix == Prefix::LineNumbers {
+        return strip_line_numbers(code);
+    }
+    let mut stripped: Vec<&str> = Vec::new();
+    // a shell command continued with a backslash has no prompt on the next line
+    let mut continued = false;
+    for line in code.lines() {
+        if prefix == Prefix::ShellPrompt {
+            if let Some(command) = line.strip_prefix("$ ") {
+                stripped.push(command);
+            } else if continued {
+                stripped.push(line);
+            } else {
+                continue;
+            }
+            continued = line.ends_with('\\');
+        } else {
+            let command = [">>> ", "... "]
+                .iter()
+                .find_map(|prompt| line.strip_prefix(prompt))
+                .or_else(|| (line == ">>>" || line == "...").then_some(""));
+            stripped.extend(command);
+        }
+    }
+    normalize(&stripped.join("\n"), 0)
+}
+
+/// removes a gutter of `12 | code`, `12: code` or numbers followed by spaces
+fn strip_line_numbers(code: &str) -> String {
+    let rests: Vec<&str> = code
+        .lines()
+        .map(|line| {
+            line.trim_start()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+        })
+        .collect();
+    let separated = rests
+        .iter()
+        .all(|rest| rest.trim().is_empty() || rest.trim_start().starts_with(['|', ':', '│']));
+    let lines: Vec<&str> = if separated {
+        rests
+            .iter()
+            .map(|rest| {
+                let rest = rest.trim_start();
+                let rest = rest.strip_prefix(['|', ':', '│']).unwrap_or(rest);
+                rest.strip_prefix(' ').unwrap_or(rest)
+            })
+            .collect()
+    } else {
+        // with only spaces between number and code, the space all lines share is the separator
+        let indent = |line: &str| line.len() - line.trim_start().len();
+        let common = rests
+            .iter()
+            .filter(|rest| !rest.trim().is_empty())
+            .map(|rest| indent(rest))
+            .min()
+            .unwrap_or_default();
+        rests
+            .iter()
+            .map(|rest| &rest[common.min(rest.len())..])
+            .collect()
+    };
+    normalize(&lines.join("\n"), 0)
+}
+
+/// offers to strip line numbers or prompts from pasted code
+pub struct PastePrompt {
+    code: String,
+    prefix: Prefix,
+}
+
+impl CodeCache {
+    /// opens the form for pasted code, asking first if it has line numbers or prompts
+    pub(super) fn paste_code(&mut self, code: String) {
+        match detect_prefix(&code) {
+            Some(prefix) => self.paste_prompt = Some(PastePrompt { code, prefix }),
+            None => self.open_pasted(&code),
+        }
+    }
+
+    fn open_pasted(&mut self, code: &str) {
+        let lang = guess_lang(code);
+        let snippet = SaveSnippet::new(self.format_pasted(code, &lang), lang);
+        self.open_form(FormTarget::New, &snippet);
+    }
+
+    pub(super) fn handle_paste_prompt_key(&mut self, key: KeyCode) {
+        let Some(prompt) = self.paste_prompt.take() else {
+            return;
+        };
+        match key {
+            KeyCode::Char('s') | KeyCode::Char('S') | KeyCode::Enter => {
+                let code = strip_prefix(&prompt.code, prompt.prefix);
+                self.open_pasted(&code);
+            }
+            KeyCode::Char('k') | KeyCode::Char('K') => self.open_pasted(&prompt.code),
+            KeyCode::Esc => {}
+            _ => self.paste_prompt = Some(prompt),
+        }
+    }
+
+    pub(super) fn draw_paste_prompt(&self, frame: &mut Frame) {
+        let Some(prompt) = &self.paste_prompt else {
+            return;
+        };
+        let body = format!(
+            "The pasted code seems to include {}.\n\ns or enter to strip them, k to keep the code as it is, esc to cancel",
+            prompt.prefix.describe()
+        );
+        frame.render_widget(
+            Popup::new(body)
+                .title("Paste")
+                .style(Style::default().fg(Color::LightBlue)),
+            frame.area(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_pasted_text() {
+        let text = "\r\n\n    fn main() {   \r\n    \tprintln!(\"a\tb\");\r\n\r\n    }\n\n";
+        assert_eq!(
+            normalize(text, 4),
+            "fn main() {\n    println!(\"a\tb\");\n\n}"
+        );
+        assert_eq!(normalize("  \n\t\n", 4), "");
+    }
+
+    #[test]
+    fn tab_width_zero_keeps_tabs() {
+        assert_eq!(normalize("\t\tif x:\n\t\t\tpass", 0), "if x:\n\tpass");
+        // mixed indentation only loses what all lines share
+        assert_eq!(normalize("\t  a\n\tb", 0), "  a\nb");
+    }
+
+    #[test]
+    fn expands_tabs_to_the_next_stop() {
+        assert_eq!(expand_indent("  \tx\ty", 4), "    x\ty");
+        assert_eq!(expand_indent("\t\tx", 2), "    x");
+    }
+
+    #[test]
+    fn detects_prefixes() {
+        assert_eq!(
+            detect_prefix("1 | fn main() {\n2 |     x();\n3 | }"),
+            Some(Prefix::LineNumbers)
+        );
+        assert_eq!(detect_prefix("$ ls\nfile.txt"), Some(Prefix::ShellPrompt));
+        assert_eq!(detect_prefix(">>> 1 + 1\n2"), Some(Prefix::PythonPrompt));
+        // a single number or a gap in the numbering is code
+        assert_eq!(detect_prefix("42 is the answer"), None);
+        assert_eq!(detect_prefix("1: a\n3: b"), None);
+        assert_eq!(detect_prefix("let x = 1;"), None);
+    }
+
+    #[test]
+    fn strips_line_numbers() {
+        let code = " 9 │ if x {\n10 │     y();\n11 │ }";
+        assert_eq!(
+            strip_prefix(code, Prefix::LineNumbers),
+            "if x {\n    y();\n}"
+        );
+        let code = "1  def f():\n2      return 1\n3";
+        assert_eq!(
+            strip_prefix(code, Prefix::LineNumbers),
+            "def f():\n    return 1"
+        );
+    }
+
+    #[test]
+    fn strips_prompts_and_output() {
+        let shell = "$ docker build \\\n  -t app .\nSending context\n$ docker run app\nhello";
+        assert_eq!(
+            strip_prefix(shell, Prefix::ShellPrompt),
+            "docker build \\\n  -t app .\ndocker run app"
+        );
+        let python = ">>> for i in range(2):\n...     print(i)\n...\n0\n1";
+        assert_eq!(
+            strip_prefix(python, Prefix::PythonPrompt),
+            "for i in range(2):\n    print(i)"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs::File;
+
+use crossterm::{
+    cursor::Show,
+    event::{DisableBracketedPaste, EnableBracketedPaste, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    layout::Constraint::{Fill, Length, Percentage},
+    prelude::*,
+    widgets::{Block, List, ListItem, ListState as PickState, Paragraph},
+};
+
+use crate::app::{
+    CodeCache, SaveSnippet, View,
+    editor::Editor,
+    search::Query,
+    template::{self, FillAction},
+};
+
+/// search box and matching snippets of `codecache pick`
+pub struct Picker {
+    query: Editor,
+    /// indices of the matching snippets, best first
+    matches: Vec<usize>,
+    state: PickState,
+    /// print the code without asking for placeholder values
+    raw: bool,
+}
+
+impl CodeCache {
+    /// runs the picker on /dev/tty, so it works while stdout is captured,
+    /// and returns the chosen snippet with its filled in code
+    pub fn pick(
+        &mut self,
+        query: &str,
+        raw: bool,
+    ) -> Result<Option<(SaveSnippet, String)>, String> {
+        let tty = File::options()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|err| format!("/dev/tty: {err}"))?;
+        enable_raw_mode().map_err(|err| format!("failed to set up terminal: {err}"))?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(tty))
+            .map_err(|err| format!("failed to set up terminal: {err}"))?;
+        let _ = execute!(
+            terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableBracketedPaste
+        );
+
+        let mut query_editor = Editor::single_line(query);
+        query_editor.end_of_text();
+        self.picker = Some(Picker {
+            query: query_editor,
+            matches: Vec::new(),
+            state: PickState::default(),
+            raw,
+        });
+        self.update_matches();
+        self.view = View::Pick;
+        while self.running {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .expect("failed to draw frame");
+            self.handle_events();
+        }
+
+        let _ = execute!(
+            terminal.backend_mut(),
+            DisableBracketedPaste,
+            LeaveAlternateScreen,
+            Show
+        );
+        let _ = disable_raw_mode();
+        self.forget_secrets();
+        Ok(self.picked.take())
+    }
+
+    /// filters the library with the search, snippets matching in the title first
+    fn update_matches(&mut self) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        let query = Query::parse(&picker.query.text());
+        let mut matches: Vec<usize> = (0..self.save_snippets.len())
+            .filter(|&i| query.matches(&self.save_snippets[i]))
+            .collect();
+        matches.sort_by_key(|&i| !query.matches_title(&self.save_snippets[i]));
+        picker.matches = matches;
+        picker
+            .state
+            .select((!picker.matches.is_empty()).then_some(0));
+    }
+
+    pub(super) fn handle_pick_key(&mut self, key: KeyEvent) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.running = false,
+            KeyCode::Char('c') if ctrl => self.running = false,
+            KeyCode::Down | KeyCode::PageDown => picker.state.select_next(),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => picker.state.select_next(),
+            KeyCode::Up | KeyCode::PageUp => picker.state.select_previous(),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => picker.state.select_previous(),
+            KeyCode::Enter => {
+                let Some(idx) = picker
+                    .state
+                    .selected()
+                    .and_then(|i| picker.matches.get(i).copied())
+                else {
+                    return;
+                };
+                let raw = picker.raw;
+                let snippet = &self.save_snippets[idx];
+                if snippet.is_locked() {
+                    self.toggle_reveal(idx);
+                    return;
+                }
+                if raw {
+                    let code = snippet.code.clone();
+                    self.finish_template(&code, FillAction::Print(idx));
+                } else {
+                    let code = template::template_code(snippet).into_owned();
+                    self.use_template(&code, FillAction::Print(idx));
+                }
+            }
+            _ => {
+                if picker.query.handle_key(key) {
+                    self.update_matches();
+                }
+            }
+        }
+    }
+
+    pub(super) fn paste_into_picker(&mut self, text: &str) {
+        if let Some(picker) = &mut self.picker {
+            picker
+                .query
+                .insert_str(text.lines().next().unwrap_or_default());
+            self.update_matches();
+        }
+    }
+
+    /// ends the picker with the code of a snippet
+    pub(super) fn print_snippet(&mut self, idx: usize, code: &str) {
+        if let Some(snippet) = self.save_snippets.get(idx) {
+            self.picked = Some((snippet.clone(), code.to_string()));
+        }
+        self.running = false;
+    }
+
+    pub(super) fn draw_pick(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        let [query_area, area] = Layout::vertical([Length(3), Fill(1)]).areas(area);
+        let [list_area, preview_area] = Layout::horizontal([Percentage(40), Fill(1)]).areas(area);
+
+        let border = Style::default().fg(Color::Rgb(124, 111, 100));
+        picker.query.render(
+            frame,
+            query_area,
+            Block::bordered()
+                .title("Search (tag:, #, lang:)")
+                .border_style(Style::default().fg(Color::Rgb(250, 189, 47))),
+            true,
+            None,
+        );
+
+        let items: Vec<ListItem> = picker
+            .matches
+            .iter()
+            .map(|&i| {
+                let snippet = &self.save_snippets[i];
+                let lock = if snippet.is_locked() { "🔒 " } else { "" };
+                let mut spans = vec![
+                    Span::styled(format!("{lock}{}", snippet.title), Style::default().bold()),
+                    Span::raw(format!(" ({})", snippet.lang)),
+                ];
+                if !snippet.tags.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" #{}", snippet.tags.join(" #")),
+                        Style::default().fg(Color::Rgb(120, 112, 108)),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!(
+                        "{} of {}",
+                        picker.matches.len(),
+                        self.save_snippets.len()
+                    ))
+                    .title_alignment(Alignment::Center)
+                    .border_style(border),
+            )
+            .style(Style::default().fg(Color::Rgb(250, 189, 47)))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::Rgb(254, 128, 25))
+                    .fg(Color::Rgb(28, 28, 32)),
+            );
+        frame.render_stateful_widget(list, list_area, &mut picker.state);
+
+        let Some(snippet) = picker
+            .state
+            .selected()
+            .and_then(|i| picker.matches.get(i))
+            .map(|&i| &self.save_snippets[i])
+        else {
+            frame.render_widget(Block::bordered().border_style(border), preview_area);
+            return;
+        };
+        let mut text = Text::default();
+        if !snippet.desc.is_empty() {
+            text.push_line(Line::styled(
+                snippet.desc.clone(),
+                Style::default().fg(Color::Rgb(120, 112, 108)),
+            ));
+            text.push_line(Line::default());
+        }
+        if snippet.is_locked() {
+            text.push_line(Line::raw("secret, press enter to reveal it"));
+        } else {
+            match self.highlighter.highlight(&snippet.code, &snippet.lang) {
+                Ok(code) | Err(code) => text.extend(code),
+            }
+        }
+        let placeholders = template::placeholders(&template::template_code(snippet)).len();
+        let title = if placeholders > 0 {
+            format!("{} ({placeholders} placeholder(s))", snippet.title)
+        } else {
+            snippet.title.clone()
+        };
+        frame.render_widget(
+            Paragraph::new(text).block(
+                Block::bordered()
+                    .title(title)
+                    .title_alignment(Alignment::Center)
+                    .border_style(border),
+            ),
+            preview_area,
+        );
+    }
+}
@@ -0,0 +1,622 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ansi_to_tui::IntoText;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Constraint::{Fill, Length, Percentage},
+    prelude::*,
+    widgets::{Block, Clear, Paragraph},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{
+    CodeCache, View,
+    codesnippet::new_id,
+    template::{self, FillAction},
+    timestamp,
+};
+
+/// runners for languages the config does not name.
+/// the code is passed as last argument, or written to `{file}` if the command has it.
+const DEFAULT_RUNNERS: [(&str, &str); 12] = [
+    ("sh", "bash -c"),
+    ("bash", "bash -c"),
+    ("zsh", "zsh -c"),
+    ("fish", "fish -c"),
+    ("py", "python3 {file}"),
+    ("js", "node {file}"),
+    ("rb", "ruby {file}"),
+    ("pl", "perl {file}"),
+    ("lua", "lua {file}"),
+    ("php", "php {file}"),
+    ("go", "go run {file}"),
+    ("rs", "cargo run --quiet --manifest-path {dir}/Cargo.toml"),
+];
+
+/// stored output is cut to its last lines so the library stays small
+const STORED_LINES: usize = 200;
+
+/// the panel keeps the last lines of output, a chatty process would fill the memory otherwise
+const SHOWN_LINES: usize = 10_000;
+
+/// output of the last run, stored with the snippet on request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastRun {
+    pub at: u64,
+    /// `None` if the process was killed by a signal
+    pub exit: Option<i32>,
+    pub output: String,
+}
+
+impl LastRun {
+    /// exit code, age and last line of output for the snippet card
+    pub fn summary(&self) -> String {
+        let exit = match self.exit {
+            Some(code) => format!("exit code {code}"),
+            None => "killed".to_string(),
+        };
+        let mut summary = format!("{exit}, {}", timestamp::ago(self.at));
+        if let Some(line) = self
+            .output
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+        {
+            summary.push_str(&format!(": {}", line.trim()));
+        }
+        summary
+    }
+}
+
+/// the configured runners on top of the defaults
+pub fn runners(config: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut runners: BTreeMap<String, String> = DEFAULT_RUNNERS
+        .iter()
+        .map(|(lang, runner)| (lang.to_string(), runner.to_string()))
+        .collect();
+    runners.extend(config.clone());
+    runners
+}
+
+/// asks before running a snippet
+pub struct RunConfirm {
+    idx: usize,
+    code: String,
+}
+
+/// a running or finished snippet and its output
+pub struct RunPanel {
+    idx: usize,
+    code: String,
+    runner: String,
+    /// output as it was printed and as it is drawn, parsed once when it arrives
+    lines: VecDeque<(String, Line<'static>)>,
+    child: Option<Child>,
+    /// lines of output and whether they came from stderr
+    output: Receiver<(String, bool)>,
+    exit: Option<Option<i32>>,
+    started: Instant,
+    took: Duration,
+    /// temporary directory with the code, removed once the process ended
+    dir: Option<PathBuf>,
+    /// lines scrolled up from the end of the output
+    scroll: u16,
+}
+
+/// a fresh temporary directory for the files of one run, only accessible by the user.
+/// its name is random and it must not exist yet, so no one else can prepare it.
+pub(super) fn temp_dir() -> Result<PathBuf, String> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    loop {
+        let dir = std::env::temp_dir().join(format!("codecache-run-{}", new_id()));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("{}: {err}", dir.display())),
+        }
+    }
+}
+
+/// writes the code where the runner expects it, rust snippets become a cargo project
+fn write_code(dir: &Path, code: &str, lang: &str) -> Result<PathBuf, String> {
+    let write = |path: &Path, text: &str| {
+        fs::write(path, text).map_err(|err| format!("{}: {err}", path.display()))
+    };
+    if lang != "rs" {
+        let file = dir.join(format!("snippet.{lang}"));
+        write(&file, code)?;
+        return Ok(file);
+    }
+    write(
+        &dir.join("Cargo.toml"),
+        "[package]\nname = \"snippet\"\nversion = \"0.1.0\"\nedition = \"2024\"\n\n[dependencies]\n",
+    )?;
+    fs::create_dir_all(dir.join("src")).map_err(|err| format!("{}: {err}", dir.display()))?;
+    // statements without a main function are run as its body
+    let code = if code.contains("fn main") {
+        code.to_string()
+    } else {
+        format!("fn main() {{\n{code}\n}}\n")
+    };
+    let file = dir.join("src").join("main.rs");
+    write(&file, &code)?;
+    Ok(file)
+}
+
+/// starts the runner, returning the process and the temporary directory it uses
+fn spawn(
+    runner: &str,
+    code: &str,
+    lang: &str,
+    output: Sender<(String, bool)>,
+) -> Result<(Child, Option<PathBuf>), String> {
+    let mut args =
+        shell_words::split(runner).map_err(|err| format!("invalid runner {runner:?}: {err}"))?;
+    if args.is_empty() {
+        return Err(format!("no runner for {lang}"));
+    }
+    let uses_files = lang == "rs"
+        || args
+            .iter()
+            .any(|a| a.contains("{file}") || a.contains("{dir}"));
+    let dir = if uses_files { Some(temp_dir()?) } else { None };
+    if let Some(dir) = &dir {
+        let file = write_code(dir, code, lang)?;
+        for arg in &mut args {
+            *arg = arg
+                .replace("{file}", &file.to_string_lossy())
+                .replace("{dir}", &dir.to_string_lossy());
+        }
+    } else {
+        args.push(code.to_string());
+    }
+
+    let mut command = Command::new(&args[0]);
+    command
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // its own process group, so stopping it also stops what it started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    // keeps build artifacts between runs, so only the first one compiles dependencies
+    if lang == "rs"
+        && let Some(cache) = dirs::cache_dir()
+    {
+        command.env(
+            "CARGO_TARGET_DIR",
+            cache.join("codecache").join("run-target"),
+        );
+    }
+    let mut child = command.spawn().map_err(|err| {
+        if let Some(dir) = &dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+        format!("failed to start {}: {err}", args[0])
+    })?;
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, false, output.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, true, output);
+    }
+    Ok((child, dir))
+}
+
+/// sends the lines of a pipe to the panel until it closes
+fn forward(pipe: impl Read + Send + 'static, stderr: bool, output: Sender<(String, bool)>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        while let Ok(n) = reader.read_until(b'\n', &mut line) {
+            if n == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line)
+                .trim_end_matches(['\n', '\r'])
+                .to_string();
+            if output.send((text, stderr)).is_err() {
+                break;
+            }
+            line.clear();
+        }
+    });
+}
+
+/// kills the process and everything in its process group
+pub(super) fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = i32::try_from(child.id()) {
+        // SAFETY: kill only sends a signal, the group was made for the child by process_group(0)
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+impl RunPanel {
+    fn is_running(&self) -> bool {
+        self.exit.is_none()
+    }
+
+    /// collects new output and notices when the process ended. processes it started
+    /// may keep the pipes open after it exited, so the exit status is checked every time.
+    fn poll(&mut self) {
+        while let Ok((line, stderr)) = self.output.try_recv() {
+            self.push(line, stderr);
+        }
+        let Some(child) = &mut self.child else {
+            return;
+        };
+        if let Ok(Some(status)) = child.try_wait() {
+            self.exit = Some(status.code());
+            self.took = self.started.elapsed();
+            self.child = None;
+            if let Some(dir) = self.dir.take() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    fn push(&mut self, line: String, stderr: bool) {
+        let shown = match line.into_text() {
+            Ok(colored) if !stderr => Line::from(
+                colored
+                    .lines
+                    .into_iter()
+                    .flat_map(|line| line.spans)
+                    .collect::<Vec<_>>(),
+            ),
+            _ if stderr => Line::styled(line.clone(), Style::default().fg(Color::Rgb(251, 73, 52))),
+            _ => Line::raw(line.clone()),
+        };
+        if self.lines.len() == SHOWN_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((line, shown));
+    }
+
+    fn kill(&mut self) {
+        if let Some(child) = &mut self.child {
+            kill(child);
+        }
+    }
+
+    fn last_run(&self) -> LastRun {
+        let skip = self.lines.len().saturating_sub(STORED_LINES);
+        LastRun {
+            at: timestamp::now(),
+            exit: self.exit.flatten(),
+            output: self
+                .lines
+                .range(skip..)
+                .map(|(line, _)| line.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl Drop for RunPanel {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            kill(&mut child);
+            let _ = child.wait();
+        }
+        if let Some(dir) = self.dir.take() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+impl CodeCache {
+    /// fills in the template of a snippet and runs it, asking first if configured
+    pub(super) fn run_snippet(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        if snippet.is_locked() {
+            self.notify("Reveal the snippet with x before running it");
+            return;
+        }
+        if !self.runners.contains_key(&snippet.lang) {
+            self.notify(format!(
+                "No runner for {}, add one under [runners] in the config",
+                snippet.lang
+            ));
+            return;
+        }
+        let code = template::template_code(snippet).into_owned();
+        self.use_template(&code, FillAction::Run(idx));
+    }
+
+    /// called with the filled in code
+    pub(super) fn confirm_run(&mut self, idx: usize, code: &str) {
+        if self.confirm_run {
+            self.run_confirm = Some(RunConfirm {
+                idx,
+                code: code.to_string(),
+            });
+        } else {
+            self.start_run(idx, code.to_string());
+        }
+    }
+
+    fn start_run(&mut self, idx: usize, code: String) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        let Some(runner) = self.runners.get(&snippet.lang).cloned() else {
+            return;
+        };
+        // ends the previous run first
+        self.run = None;
+        let (sender, output) = mpsc::channel();
+        match spawn(&runner, &code, &snippet.lang, sender) {
+            Ok((child, dir)) => {
+                self.run = Some(RunPanel {
+                    idx,
+                    code,
+                    runner,
+                    lines: VecDeque::new(),
+                    child: Some(child),
+                    output,
+                    exit: None,
+                    started: Instant::now(),
+                    took: Duration::ZERO,
+                    dir,
+                    scroll: 0,
+                });
+                self.view = View::Run;
+            }
+            Err(err) => self.notify(err),
+        }
+    }
+
+    /// picks up output of the running snippet, called on every tick
+    pub(super) fn poll_run(&mut self) {
+        if let Some(panel) = &mut self.run {
+            panel.poll();
+        }
+    }
+
+    pub(super) fn handle_run_confirm_key(&mut self, key: KeyCode) {
+        let Some(confirm) = self.run_confirm.take() else {
+            return;
+        };
+        match key {
+            KeyCode::Enter | KeyCode::Char('y') | KeyCode::Char('Y') => {
+                self.start_run(confirm.idx, confirm.code)
+            }
+            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {}
+            _ => self.run_confirm = Some(confirm),
+        }
+    }
+
+    pub(super) fn handle_run_key(&mut self, key: KeyEvent) {
+        let Some(panel) = &mut self.run else {
+            self.view = View::Library;
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => panel.kill(),
+            KeyCode::Esc => {
+                self.run = None;
+                self.view = View::Library;
+            }
+            KeyCode::Char('r') | KeyCode::Char('R') if !panel.is_running() => {
+                let (idx, code) = (panel.idx, panel.code.clone());
+                self.start_run(idx, code);
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') if !panel.is_running() => {
+                let idx = panel.idx;
+                let last_run = panel.last_run();
+                self.store_last_run(idx, last_run);
+            }
+            KeyCode::Up => panel.scroll = panel.scroll.saturating_add(1),
+            KeyCode::Down => panel.scroll = panel.scroll.saturating_sub(1),
+            KeyCode::PageUp => panel.scroll = panel.scroll.saturating_add(10),
+            KeyCode::PageDown => panel.scroll = panel.scroll.saturating_sub(10),
+            KeyCode::End => panel.scroll = 0,
+            _ => {}
+        }
+    }
+
+    /// keeps the output with the snippet, except for secrets which are stored encrypted
+    fn store_last_run(&mut self, idx: usize, last_run: LastRun) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        if snippet.secret {
+            self.notify("The output of secret snippets is not stored");
+            return;
+        }
+        if self.read_only(idx) {
+            return;
+        }
+        self.save_snippets[idx].last_run = Some(last_run);
+        self.refresh_cards();
+        self.notify("Stored the output with the snippet");
+    }
+
+    pub(super) fn draw_run_confirm(&self, frame: &mut Frame) {
+        let Some(confirm) = &self.run_confirm else {
+            return;
+        };
+        let Some(snippet) = self.save_snippets.get(confirm.idx) else {
+            return;
+        };
+        let runner = self.runners.get(&snippet.lang).cloned().unwrap_or_default();
+        let mut code: Vec<&str> = confirm.code.lines().take(12).collect();
+        if confirm.code.lines().count() > code.len() {
+            code.push("…");
+        }
+        let body = format!(
+            "Run {} with `{runner}`?\n\n{}\n\nenter to run, esc to cancel",
+            snippet.title,
+            code.join("\n")
+        );
+        let [_, area, _] =
+            Layout::horizontal([Fill(1), Percentage(60), Fill(1)]).areas(frame.area());
+        let height = body.lines().count() as u16 + 2;
+        let [_, area, _] = Layout::vertical([Fill(1), Length(height), Fill(1)]).areas(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(body).block(
+                Block::bordered()
+                    .title("Run snippet")
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::default().fg(Color::Rgb(251, 73, 52))),
+            ),
+            area,
+        );
+    }
+
+    pub(super) fn draw_run(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(panel) = &mut self.run else {
+            return;
+        };
+        let title = self
+            .save_snippets
+            .get(panel.idx)
+            .map(|snippet| snippet.title.clone())
+            .unwrap_or_default();
+        let (status, color) = match panel.exit {
+            None => (
+                format!(" running for {}s ", panel.started.elapsed().as_secs()),
+                Color::Rgb(250, 189, 47),
+            ),
+            Some(Some(0)) => (
+                format!(" exit code 0 after {:.1}s ", panel.took.as_secs_f32()),
+                Color::Rgb(184, 187, 38),
+            ),
+            Some(Some(code)) => (
+                format!(" exit code {code} after {:.1}s ", panel.took.as_secs_f32()),
+                Color::Rgb(251, 73, 52),
+            ),
+            Some(None) => (" killed ".to_string(), Color::Rgb(251, 73, 52)),
+        };
+
+        let block = Block::bordered()
+            .title(format!("{title}: {}", panel.runner))
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::styled(status, Style::default().fg(color)).centered())
+            .border_style(Style::default().fg(Color::Rgb(124, 111, 100)));
+        let height = usize::from(block.inner(area).height);
+        // follows the end of the output unless scrolled up, only the visible lines are drawn
+        let max_scroll = panel.lines.len().saturating_sub(height);
+        panel.scroll = panel
+            .scroll
+            .min(u16::try_from(max_scroll).unwrap_or(u16::MAX));
+        let top = max_scroll - usize::from(panel.scroll);
+        let text: Text = panel
+            .lines
+            .range(top..)
+            .take(height)
+            .map(|(_, line)| line.clone())
+            .collect::<Vec<_>>()
+            .into();
+        frame.render_widget(Paragraph::new(text).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::RecvTimeoutError;
+
+    use super::*;
+
+    #[test]
+    fn temp_dirs_are_fresh_and_private() {
+        let first = temp_dir().unwrap();
+        let second = temp_dir().unwrap();
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&first).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        for dir in [first, second] {
+            fs::remove_dir(dir).unwrap();
+        }
+    }
+
+    fn run(code: &str) -> RunPanel {
+        let (sender, output) = mpsc::channel();
+        let (child, dir) = spawn("bash -c", code, "sh", sender).unwrap();
+        RunPanel {
+            idx: 0,
+            code: code.to_string(),
+            runner: "bash -c".to_string(),
+            lines: VecDeque::new(),
+            child: Some(child),
+            output,
+            exit: None,
+            started: Instant::now(),
+            took: Duration::ZERO,
+            dir,
+            scroll: 0,
+        }
+    }
+
+    /// polls until the condition holds, failing after a few seconds
+    fn wait_for(panel: &mut RunPanel, done: impl Fn(&RunPanel) -> bool) {
+        let started = Instant::now();
+        while !done(panel) {
+            assert!(started.elapsed() < Duration::from_secs(5), "timed out");
+            thread::sleep(Duration::from_millis(20));
+            panel.poll();
+        }
+    }
+
+    #[test]
+    fn ends_while_a_background_process_keeps_the_output_open() {
+        let mut panel = run("sleep 3 & echo started");
+        wait_for(&mut panel, |panel| panel.exit.is_some());
+        assert_eq!(panel.exit, Some(Some(0)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_stops_the_whole_process_group() {
+        let mut panel = run("sleep 30 & echo started; wait");
+        wait_for(&mut panel, |panel| !panel.lines.is_empty());
+        panel.kill();
+        // the pipes only close once the background sleep is gone too
+        let started = Instant::now();
+        while panel.output.recv_timeout(Duration::from_millis(50))
+            != Err(RecvTimeoutError::Disconnected)
+        {
+            assert!(started.elapsed() < Duration::from_secs(5), "timed out");
+        }
+        wait_for(&mut panel, |panel| panel.exit.is_some());
+        assert_eq!(panel.exit, Some(None));
+    }
+
+    #[test]
+    fn keeps_the_last_lines_of_output() {
+        let mut panel = run("true");
+        for i in 0..SHOWN_LINES + 5 {
+            panel.push(format!("\x1b[31mline {i}\x1b[0m"), false);
+        }
+        assert_eq!(panel.lines.len(), SHOWN_LINES);
+        assert_eq!(panel.lines[0].1.to_string(), "line 5");
+        let last_run = panel.last_run();
+        assert_eq!(last_run.output.lines().count(), STORED_LINES);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::app::SaveSnippet;
+
+/// a search like `docker cleanup tag:ops lang:sh lib:team`.
+/// plain words must all appear in the title, description, tags or code.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    words: Vec<String>,
+    tags: Vec<String>,
+    langs: Vec<String>,
+    libraries: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Self {
+        let mut query = Query::default();
+        for word in text.split_whitespace() {
+            let word = word.to_lowercase();
+            if let Some(tag) = word.strip_prefix("tag:").or(word.strip_prefix('#')) {
+                query.tags.push(tag.to_string());
+            } else if let Some(lang) = word.strip_prefix("lang:") {
+                query.langs.push(lang.to_string());
+            } else if let Some(library) = word.strip_prefix("lib:") {
+                query.libraries.push(library.to_string());
+            } else {
+                query.words.push(word);
+            }
+        }
+        query
+    }
+
+    /// limits the query to snippets with this tag
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.trim_start_matches('#').to_lowercase());
+        self
+    }
+
+    /// limits the query to snippets in this language
+    pub fn with_lang(mut self, lang: &str) -> Self {
+        self.langs.push(lang.to_lowercase());
+        self
+    }
+
+    /// whether all plain words appear in the title, used to rank those snippets first
+    pub fn matches_title(&self, snippet: &SaveSnippet) -> bool {
+        let title = snippet.title.to_lowercase();
+        self.words.iter().all(|word| title.contains(word))
+    }
+
+    pub fn matches(&self, snippet: &SaveSnippet) -> bool {
+        if !self.tags.iter().all(|tag| snippet.tags.contains(tag)) {
+            return false;
+        }
+        if !self.langs.is_empty() && !self.langs.contains(&snippet.lang.to_lowercase()) {
+            return false;
+        }
+        if !self.libraries.is_empty()
+            && !self
+                .libraries
+                .contains(&snippet.library_name().to_lowercase())
+        {
+            return false;
+        }
+        let haystack = format!(
+            "{}\n{}\n{}\n{}",
+            snippet.title,
+            snippet.desc,
+            snippet.tags.join(" "),
+            snippet.code
+        )
+        .to_lowercase();
+        self.words.iter().all(|word| haystack.contains(word))
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::app::{
+    CodeCache, SaveSnippet, highlight::Highlighter, history, language::guess_lang, undo::Change,
+};
+
+/// file a snippet was taken from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    pub path: PathBuf,
+    /// first and last line, 1-based and inclusive, `None` for the whole file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<(usize, usize)>,
+    /// sha256 of the whole file when the snippet was taken
+    pub hash: String,
+}
+
+/// how the source file compares to the snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Fresh,
+    /// the code is still in the file, but at other lines
+    Moved((usize, usize)),
+    /// the lines were edited since the snippet was taken
+    Changed,
+    /// the file was moved or deleted
+    Missing,
+}
+
+impl Status {
+    pub fn label(&self) -> String {
+        match self {
+            Status::Fresh => "up to date".to_string(),
+            Status::Moved((start, end)) => format!("moved to lines {start}-{end}"),
+            Status::Changed => "changed".to_string(),
+            Status::Missing => "file missing".to_string(),
+        }
+    }
+}
+
+impl Source {
+    /// path with the home directory shortened to `~`, plus the line range
+    pub fn describe(&self) -> String {
+        let path = dirs::home_dir()
+            .and_then(|home| self.path.strip_prefix(home).ok())
+            .map(|rest| Path::new("~").join(rest))
+            .unwrap_or_else(|| self.path.clone());
+        match self.lines {
+            Some((start, end)) if start == end => format!("{}:{start}", path.display()),
+            Some((start, end)) => format!("{}:{start}-{end}", path.display()),
+            None => path.display().to_string(),
+        }
+    }
+
+    /// compares the file on disk with the code of the snippet
+    pub fn status(&self, code: &str) -> Status {
+        if hash(&self.path).is_some_and(|hash| hash == self.hash) {
+            return Status::Fresh;
+        }
+        let Some((_, content)) = read(&self.path) else {
+            return Status::Missing;
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        if extract(&lines, self.lines).as_deref() == Some(code) {
+            return Status::Fresh;
+        }
+        let Some((start, end)) = self.lines else {
+            return Status::Changed;
+        };
+        // look for the code elsewhere, e.g. after lines were added above it
+        let len = end - start + 1;
+        (0..lines.len().saturating_sub(len - 1))
+            .find(|i| lines[*i..*i + len].join("\n") == code)
+            .map(|i| Status::Moved((i + 1, i + len)))
+            .unwrap_or(Status::Changed)
+    }
+}
+
+/// file hashes by path, so unchanged files are not hashed again on every redraw
+static HASHES: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, String)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// sha256 of a file's text as hex
+pub fn file_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// hash of a file, which is only read again when it was modified since it was last hashed
+fn hash(path: &Path) -> Option<String> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let hashes = HASHES.lock().expect("hash cache poisoned");
+    if let Some((cached_at, hash)) = hashes.get(path)
+        && *cached_at == modified
+    {
+        return Some(hash.clone());
+    }
+    drop(hashes);
+    read(path).map(|(hash, _)| hash)
+}
+
+/// reads a file and returns its hash and text
+fn read(path: &Path) -> Option<(String, String)> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let content = fs::read_to_string(path).ok()?;
+    let hash = file_hash(&content);
+    if let Some(modified) = modified {
+        let mut hashes = HASHES.lock().expect("hash cache poisoned");
+        hashes.insert(path.to_path_buf(), (modified, hash.clone()));
+    }
+    Some((hash, content))
+}
+
+/// the given lines of a file, or all of it
+fn extract(lines: &[&str], range: Option<(usize, usize)>) -> Option<String> {
+    match range {
+        Some((start, end)) if start >= 1 && start <= end && end <= lines.len() => {
+            Some(lines[start - 1..end].join("\n"))
+        }
+        Some(_) => None,
+        None => Some(lines.join("\n")),
+    }
+}
+
+/// splits `path:40-72` or `path:40` into a path and a line range
+fn parse_target(target: &str) -> Result<(PathBuf, Option<(usize, usize)>), String> {
+    if let Some((path, range)) = target.rsplit_once(':')
+        && !range.is_empty()
+        && range.chars().all(|c| c.is_ascii_digit() || c == '-')
+    {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let parse = |n: &str| {
+            n.parse::<usize>()
+                .map_err(|_| format!("invalid line range \"{range}\""))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start == 0 || end < start {
+            return Err(format!("invalid line range \"{range}\""));
+        }
+        return Ok((PathBuf::from(path), Some((start, end))));
+    }
+    Ok((PathBuf::from(target), None))
+}
+
+/// creates a snippet from a file, or some of its lines with `path:start-end`
+pub fn from_file(target: &str) -> Result<SaveSnippet, String> {
+    let (path, range) = parse_target(target)?;
+    let path = fs::canonicalize(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let (hash, content) =
+        read(&path).ok_or_else(|| format!("{} is not a readable text file", path.display()))?;
+    let lines: Vec<&str> = content.lines().collect();
+    let code = extract(&lines, range)
+        .ok_or_else(|| format!("{} only has {} line(s)", path.display(), lines.len()))?;
+    if code.trim().is_empty() {
+        return Err("nothing to add, the selected lines are empty".to_string());
+    }
+
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let lang = if !ext.is_empty() && Highlighter::new().knows(&ext) {
+        ext
+    } else {
+        guess_lang(&code)
+    };
+    let mut snippet = SaveSnippet::new(code, lang);
+    let source = Source {
+        path,
+        lines: range,
+        hash,
+    };
+    snippet.title = source
+        .path
+        .file_name()
+        .map(|name| match range {
+            Some((start, end)) if start == end => format!("{}:{start}", name.to_string_lossy()),
+            Some((start, end)) => format!("{}:{start}-{end}", name.to_string_lossy()),
+            None => name.to_string_lossy().to_string(),
+        })
+        .unwrap_or_default();
+    snippet.source = Some(source);
+    Ok(snippet)
+}
+
+/// creates a snippet from piped text, titled after its first line
+pub fn from_text(text: &str) -> Result<SaveSnippet, String> {
+    let code = text.trim_end();
+    if code.trim().is_empty() {
+        return Err("nothing to add, stdin was empty".to_string());
+    }
+    let mut snippet = SaveSnippet::new(code, guess_lang(code));
+    snippet.title = code
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+        .chars()
+        .take(60)
+        .collect();
+    Ok(snippet)
+}
+
+/// new version of a snippet with its code and source taken from the file again
+pub fn refreshed(snippet: &SaveSnippet) -> Result<SaveSnippet, String> {
+    let source = snippet
+        .source
+        .as_ref()
+        .ok_or("snippet has no source file")?;
+    let status = source.status(&snippet.code);
+    let (hash, content) =
+        read(&source.path).ok_or_else(|| format!("{} was moved or deleted", source.describe()))?;
+    let lines: Vec<&str> = content.lines().collect();
+    let range = match status {
+        Status::Moved(range) => Some(range),
+        _ => source.lines,
+    };
+    let code = extract(&lines, range)
+        .ok_or_else(|| format!("{} is shorter than the snippet now", source.path.display()))?;
+
+    let mut after = snippet.clone();
+    after.code = code;
+    after.source = Some(Source {
+        path: source.path.clone(),
+        lines: range,
+        hash,
+    });
+    history::revise(snippet, &mut after);
+    Ok(after)
+}
+
+/// how often source files are compared with their snippets while the app runs
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+impl CodeCache {
+    /// compares every snippet with its source file, cards only show the last result
+    pub(super) fn check_sources(&mut self) {
+        self.source_status = self
+            .save_snippets
+            .iter()
+            .filter(|snippet| !snippet.is_locked())
+            .filter_map(|snippet| {
+                let status = snippet.source.as_ref()?.status(&snippet.code);
+                Some((snippet.id.clone(), status))
+            })
+            .collect();
+        self.sources_checked_at = Instant::now();
+    }
+
+    /// compares a snippet with its source file again after it was changed
+    pub(super) fn check_source(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        match &snippet.source {
+            Some(source) if !snippet.is_locked() => {
+                let status = source.status(&snippet.code);
+                self.source_status.insert(snippet.id.clone(), status);
+            }
+            _ => {
+                self.source_status.remove(&snippet.id);
+            }
+        }
+    }
+
+    /// picks up edits to source files made while the app runs
+    pub(super) fn poll_sources(&mut self) {
+        if self.sources_checked_at.elapsed() < CHECK_INTERVAL {
+            return;
+        }
+        let before = self.source_status.clone();
+        self.check_sources();
+        if self.source_status != before {
+            self.refresh_cards();
+        }
+    }
+
+    /// updates the selected snippet from its source file
+    pub(super) fn refresh_from_source(&mut self, idx: usize) {
+        let Some(before) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        if before.is_locked() {
+            self.notify("Reveal the secret snippet first (x)");
+            return;
+        }
+        let Some(source) = &before.source else {
+            self.notify("This snippet was not taken from a file");
+            return;
+        };
+        let status = source.status(&before.code);
+        if status == Status::Fresh {
+            self.notify("Already up to date");
+            return;
+        }
+        match refreshed(&before) {
+            Ok(after) => {
+                self.change(Change::update(idx, before, after));
+                self.notify(match status {
+                    Status::Moved(_) => "Updated the line range of the snippet",
+                    _ => "Updated the snippet from its source file",
+                });
+            }
+            Err(err) => self.notify(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmodified_files_are_not_read_again() {
+        let path = std::env::temp_dir().join(format!("codecache-source-{}.sh", std::process::id()));
+        fs::write(&path, "echo one\necho two\n").unwrap();
+        let (hash, _) = read(&path).unwrap();
+        assert_eq!(super::hash(&path), Some(hash));
+
+        // a hit in the cache is trusted as long as the modification time matches
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        HASHES
+            .lock()
+            .unwrap()
+            .insert(path.clone(), (modified, "cached".to_string()));
+        assert_eq!(super::hash(&path).as_deref(), Some("cached"));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(super::hash(&path), None);
+    }
+
+    #[test]
+    fn status_follows_the_file() {
+        let path = std::env::temp_dir().join(format!("codecache-status-{}.sh", std::process::id()));
+        fs::write(&path, "echo one\necho two\n").unwrap();
+        let snippet = from_file(&format!("{}:2", path.display())).unwrap();
+        let source = snippet.source.clone().unwrap();
+        assert_eq!(source.status(&snippet.code), Status::Fresh);
+
+        fs::write(&path, "echo zero\necho one\necho two\n").unwrap();
+        assert_eq!(source.status(&snippet.code), Status::Moved((3, 3)));
+        fs::write(&path, "echo one\necho three\n").unwrap();
+        assert_eq!(source.status(&snippet.code), Status::Changed);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(source.status(&snippet.code), Status::Missing);
+    }
+}
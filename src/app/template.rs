@@ -0,0 +1,552 @@
+use std::{borrow::Cow, sync::LazyLock};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Constraint::{Fill, Length, Percentage},
+    prelude::*,
+    widgets::{Block, Clear},
+};
+use regex::Regex;
+
+use crate::app::{CodeCache, SaveSnippet, editor::Editor};
+
+/// `{{name}}` or `{{name:default}}` in the code of a template snippet, or a `\{{` escape
+/// that stands for literal braces and has no name
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\\\{\{|\{\{([A-Za-z0-9_]+)(?::([^{}]*))?\}\}").expect("invalid placeholder regex")
+});
+
+/// the code of a snippet as a template, in plain snippets every `{{` is escaped
+/// so Mustache or Jinja code is never asked to be filled in
+pub fn template_code(snippet: &SaveSnippet) -> Cow<'_, str> {
+    if snippet.template {
+        Cow::Borrowed(&snippet.code)
+    } else {
+        Cow::Owned(escape_braces(&snippet.code))
+    }
+}
+
+/// escapes every `{{`, turning plain code into a template without placeholders
+pub fn escape_braces(code: &str) -> String {
+    code.replace("{{", "\\{{")
+}
+
+/// makes an imported snippet a template if its code has placeholders,
+/// plain code loses the escapes of its braces
+pub fn mark_template(snippet: &mut SaveSnippet) {
+    snippet.template = !placeholders(&snippet.code).is_empty();
+    if !snippet.template {
+        snippet.code = fill(&snippet.code, &[]);
+    }
+}
+
+/// a value asked for when a template snippet is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub name: String,
+    pub default: Option<String>,
+}
+
+/// placeholders in the order they first appear, each name once.
+/// a default given on any occurrence counts for all of them.
+pub fn placeholders(code: &str) -> Vec<Placeholder> {
+    let mut found: Vec<Placeholder> = Vec::new();
+    for caps in PLACEHOLDER.captures_iter(code) {
+        let Some(name) = caps.get(1).map(|m| m.as_str().to_string()) else {
+            continue;
+        };
+        let default = caps.get(2).map(|m| m.as_str().to_string());
+        match found.iter_mut().find(|p| p.name == name) {
+            Some(existing) => {
+                if existing.default.is_none() {
+                    existing.default = default;
+                }
+            }
+            None => found.push(Placeholder { name, default }),
+        }
+    }
+    found
+}
+
+/// replaces every placeholder with its value, unknown names keep their default
+pub fn fill(code: &str, values: &[(String, String)]) -> String {
+    PLACEHOLDER
+        .replace_all(code, |caps: &regex::Captures| {
+            let Some(name) = caps.get(1) else {
+                return "{{".to_string();
+            };
+            values
+                .iter()
+                .find(|(n, _)| n == name.as_str())
+                .map(|(_, value)| value.clone())
+                .or_else(|| caps.get(2).map(|m| m.as_str().to_string()))
+                .unwrap_or_default()
+        })
+        .into_owned()
+}
+
+/// a piece of template code, either plain text or a placeholder
+pub enum Part<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder),
+    /// `\{{`, literal braces
+    Escape,
+}
+
+/// splits code into the text between placeholders and the placeholders themselves, in order
+pub fn parts(code: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut last = 0;
+    for caps in PLACEHOLDER.captures_iter(code) {
+        let whole = caps.get(0).expect("match has a whole group");
+        parts.push(Part::Text(&code[last..whole.start()]));
+        parts.push(match caps.get(1) {
+            Some(name) => Part::Placeholder(Placeholder {
+                name: name.as_str().to_string(),
+                default: caps.get(2).map(|m| m.as_str().to_string()),
+            }),
+            None => Part::Escape,
+        });
+        last = whole.end();
+    }
+    parts.push(Part::Text(&code[last..]));
+    parts
+}
+
+/// prefixes of the variables editors fill in themselves, like `$TM_FILENAME`
+const VARIABLES: [&str; 10] = [
+    "TM_",
+    "CLIPBOARD",
+    "CURRENT_",
+    "RELATIVE_FILEPATH",
+    "WORKSPACE_",
+    "LINE_COMMENT",
+    "BLOCK_COMMENT_",
+    "RANDOM",
+    "UUID",
+    "CURSOR_",
+];
+
+fn is_variable(name: &str) -> bool {
+    VARIABLES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// flavours of the `$1` / `${1:default}` snippet syntax editors share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// VS Code, Sublime Text and TextMate, with variables like `$TM_FILENAME`
+    TextMate,
+    /// UltiSnips, SnipMate and yasnippet, where `$NAME` is plain text and backticks run code
+    Plain,
+}
+
+/// turns tabstops, placeholders and variables of an editor snippet into codecache placeholders,
+/// literal `{{` is escaped. `$0` only marks the final cursor position and is dropped.
+pub fn from_tabstops(body: &str, dialect: Dialect) -> String {
+    let chars: Vec<char> = body.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (text, next) = convert(&chars, i, dialect);
+        out.push_str(&text);
+        i = next;
+    }
+    out
+}
+
+/// converts the element at `i`, returns its text and the index after it
+fn convert(chars: &[char], i: usize, dialect: Dialect) -> (String, usize) {
+    match chars[i] {
+        '\\' if matches!(chars.get(i + 1), Some('$' | '}' | '\\')) => {
+            (chars[i + 1].to_string(), i + 2)
+        }
+        '\\' if dialect == Dialect::Plain && chars.get(i + 1) == Some(&'`') => {
+            ("`".to_string(), i + 2)
+        }
+        '{' if chars.get(i + 1) == Some(&'{') => ("\\{{".to_string(), i + 2),
+        '$' => {
+            // $1 or $NAME
+            let name: String = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .collect();
+            if !name.is_empty() && is_tabstop(&name, dialect) {
+                return (placeholder(&name, None), i + 1 + name.chars().count());
+            }
+            if chars.get(i + 1) == Some(&'{') {
+                let name: String = chars[i + 2..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .collect();
+                // ${HOME} in a shell snippet stays as it is
+                if is_tabstop(&name, dialect) || name == "VISUAL" {
+                    return convert_braced(chars, i + 2, dialect);
+                }
+            }
+            ("$".to_string(), i + 1)
+        }
+        c => (c.to_string(), i + 1),
+    }
+}
+
+/// numbered tabstops, plus variables in the TextMate dialect
+fn is_tabstop(name: &str, dialect: Dialect) -> bool {
+    dialect == Dialect::TextMate || name.chars().all(|c| c.is_ascii_digit())
+}
+
+/// converts the inside of `${...}` starting at `start`, nested placeholders become their defaults
+fn convert_braced(chars: &[char], start: usize, dialect: Dialect) -> (String, usize) {
+    let name: String = chars[start..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+        .collect();
+    let mut i = start + name.chars().count();
+    let mut default: Option<String> = None;
+    match chars.get(i) {
+        Some(':') => {
+            i += 1;
+            let mut text = String::new();
+            while i < chars.len() && chars[i] != '}' {
+                let (part, next) = convert(chars, i, dialect);
+                // a nested placeholder only contributes its default text
+                text.push_str(&fill(&part, &[]));
+                i = next;
+            }
+            // yasnippet mirrors like ${1:$(upcase yas-text)} have no default of their own
+            default = (!text.starts_with("$(")).then_some(text);
+        }
+        // a choice, the first option becomes the default
+        Some('|') => {
+            let rest: String = chars[i + 1..].iter().collect();
+            let options = rest.split("|}").next().unwrap_or_default();
+            default = options.split(',').next().map(str::to_string);
+            i += 1 + options.chars().count() + 1;
+        }
+        _ => {
+            // transforms like ${1/(.*)/$1/} are not supported, keep the plain placeholder
+            while i < chars.len() && chars[i] != '}' {
+                i += 1;
+            }
+        }
+    }
+    (placeholder(&name, default.as_deref()), i + 1)
+}
+
+fn placeholder(name: &str, default: Option<&str>) -> String {
+    match (name, default) {
+        ("0", None) | ("", _) => String::new(),
+        ("0", Some(default)) => default.to_string(),
+        (name, Some(default)) => format!("{{{{{name}:{}}}}}", default.replace(['{', '}'], "")),
+        (name, None) => format!("{{{{{name}}}}}"),
+    }
+}
+
+/// turns codecache placeholders into numbered editor placeholders, escaping literal `$`
+pub fn to_tabstops(code: &str, dialect: Dialect) -> String {
+    let is_variable = |name: &str| dialect == Dialect::TextMate && is_variable(name);
+    let placeholders = placeholders(code);
+    // numeric names keep their number, the others get the next free ones in order
+    let mut taken: Vec<usize> = placeholders
+        .iter()
+        .filter_map(|p| p.name.parse().ok())
+        .collect();
+    let mut numbers: Vec<(String, usize)> = Vec::new();
+    for p in placeholders.iter().filter(|p| !is_variable(&p.name)) {
+        let number = p.name.parse().unwrap_or_else(|_| {
+            let next = (1..)
+                .find(|n| !taken.contains(n))
+                .expect("there is a free number");
+            taken.push(next);
+            next
+        });
+        numbers.push((p.name.clone(), number));
+    }
+
+    let mut out = String::new();
+    let mut seen: Vec<String> = Vec::new();
+    for part in parts(code) {
+        let p = match part {
+            Part::Text(text) => {
+                let text = text.replace('\\', "\\\\").replace('$', "\\$");
+                match dialect {
+                    Dialect::TextMate => out.push_str(&text),
+                    Dialect::Plain => out.push_str(&text.replace('`', "\\`")),
+                }
+                continue;
+            }
+            Part::Placeholder(p) => p,
+            Part::Escape => {
+                out.push_str("{{");
+                continue;
+            }
+        };
+        // variables go back as they came, the editor fills them in
+        if is_variable(&p.name) {
+            match &p.default {
+                Some(default) => {
+                    out.push_str(&format!("${{{}:{}}}", p.name, escape(default, dialect)));
+                }
+                None => out.push_str(&format!("${{{}}}", p.name)),
+            }
+            continue;
+        }
+        let number = numbers
+            .iter()
+            .find(|(name, _)| *name == p.name)
+            .map(|(_, n)| *n)
+            .unwrap_or(1);
+        // only the first occurrence needs the text, the others mirror it
+        if seen.contains(&p.name) {
+            out.push_str(&format!("${number}"));
+            continue;
+        }
+        seen.push(p.name.clone());
+        let text = p.default.clone().or_else(|| {
+            let is_number = p.name.parse::<usize>().is_ok();
+            (!is_number).then(|| p.name.clone())
+        });
+        match text {
+            Some(text) => out.push_str(&format!("${{{number}:{}}}", escape(&text, dialect))),
+            None => out.push_str(&format!("${number}")),
+        }
+    }
+    out
+}
+
+/// escapes text inside a placeholder
+fn escape(text: &str, dialect: Dialect) -> String {
+    let text = text.replace('$', "\\$").replace('}', "\\}");
+    match dialect {
+        Dialect::TextMate => text,
+        Dialect::Plain => text.replace('`', "\\`"),
+    }
+}
+
+/// what happens with the code once all placeholders are filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillAction {
+    Copy,
+    /// print the snippet at this index to stdout, used by `codecache pick`
+    Print(usize),
+    /// run the snippet at this index
+    Run(usize),
+}
+
+/// asks for the value of each placeholder before a template snippet is used
+pub struct FillPrompt {
+    code: String,
+    action: FillAction,
+    fields: Vec<(String, Editor)>,
+    focus: usize,
+}
+
+impl FillPrompt {
+    fn values(&self) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .map(|(name, editor)| (name.clone(), editor.text()))
+            .collect()
+    }
+}
+
+impl CodeCache {
+    /// runs the action right away for plain snippets, or asks for placeholder values first.
+    /// takes the code from `template_code`.
+    pub(super) fn use_template(&mut self, code: &str, action: FillAction) {
+        let fields: Vec<(String, Editor)> = placeholders(code)
+            .into_iter()
+            .map(|p| {
+                let mut editor = Editor::single_line(&p.default.unwrap_or_default());
+                editor.end_of_text();
+                (p.name, editor)
+            })
+            .collect();
+        if fields.is_empty() {
+            self.finish_template(&fill(code, &[]), action);
+            return;
+        }
+        self.fill_prompt = Some(FillPrompt {
+            code: code.to_string(),
+            action,
+            fields,
+            focus: 0,
+        });
+    }
+
+    pub(super) fn finish_template(&mut self, code: &str, action: FillAction) {
+        match action {
+            FillAction::Copy => self.copy(code),
+            FillAction::Print(idx) => self.print_snippet(idx, code),
+            FillAction::Run(idx) => self.confirm_run(idx, code),
+        }
+    }
+
+    pub(super) fn handle_fill_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = &mut self.fill_prompt else {
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.fill_prompt = None,
+            KeyCode::Tab | KeyCode::Down => prompt.focus = (prompt.focus + 1) % prompt.fields.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                prompt.focus = (prompt.focus + prompt.fields.len() - 1) % prompt.fields.len();
+            }
+            KeyCode::Char('s') if ctrl => self.submit_fill(),
+            KeyCode::Enter if prompt.focus + 1 < prompt.fields.len() => prompt.focus += 1,
+            KeyCode::Enter => self.submit_fill(),
+            _ => {
+                prompt.fields[prompt.focus].1.handle_key(key);
+            }
+        }
+    }
+
+    fn submit_fill(&mut self) {
+        let Some(prompt) = self.fill_prompt.take() else {
+            return;
+        };
+        let code = fill(&prompt.code, &prompt.values());
+        self.finish_template(&code, prompt.action);
+    }
+
+    pub(super) fn paste_into_fill_prompt(&mut self, text: &str) {
+        if let Some(prompt) = &mut self.fill_prompt {
+            prompt.fields[prompt.focus].1.insert_str(text);
+        }
+    }
+
+    pub(super) fn draw_fill_prompt(&mut self, frame: &mut Frame) {
+        let Some(prompt) = &mut self.fill_prompt else {
+            return;
+        };
+        let height = prompt.fields.len() as u16 * 3 + 3;
+        let [_, area, _] =
+            Layout::horizontal([Fill(1), Percentage(60), Fill(1)]).areas(frame.area());
+        let [_, area, _] = Layout::vertical([Fill(1), Length(height), Fill(1)]).areas(area);
+        frame.render_widget(Clear, area);
+
+        let outer = Block::bordered()
+            .title("Fill in the template")
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(" enter: next / done, esc: cancel ").centered())
+            .border_style(Style::default().fg(Color::LightBlue));
+        let inner = outer.inner(area);
+        frame.render_widget(outer, area);
+
+        let rows = Layout::vertical(prompt.fields.iter().map(|_| Length(3))).split(inner);
+        let focus = prompt.focus;
+        for (i, ((name, editor), row)) in prompt.fields.iter_mut().zip(rows.iter()).enumerate() {
+            let color = if i == focus {
+                Color::Rgb(250, 189, 47)
+            } else {
+                Color::Rgb(124, 111, 100)
+            };
+            let block = Block::bordered()
+                .title(name.as_str())
+                .border_style(Style::default().fg(color));
+            editor.render(frame, *row, block, i == focus, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_and_defaults() {
+        let code = "ssh {{user:root}}@{{host}} -p {{port}} && echo {{host:localhost}}";
+        assert_eq!(
+            placeholders(code),
+            [
+                Placeholder {
+                    name: "user".to_string(),
+                    default: Some("root".to_string()),
+                },
+                Placeholder {
+                    name: "host".to_string(),
+                    default: Some("localhost".to_string()),
+                },
+                Placeholder {
+                    name: "port".to_string(),
+                    default: None,
+                },
+            ]
+        );
+        let values = [("host".to_string(), "example.org".to_string())];
+        assert_eq!(
+            fill(code, &values),
+            "ssh root@example.org -p  && echo example.org"
+        );
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        let code = r"<p>\{{ user.name }}</p> {{greeting:hi}}";
+        assert_eq!(placeholders(code).len(), 1);
+        assert_eq!(fill(code, &[]), "<p>{{ user.name }}</p> hi");
+    }
+
+    #[test]
+    fn plain_snippets_have_no_placeholders() {
+        let mut snippet = SaveSnippet::new(r"Hello {{name}}, \{{ not a tag", "html");
+        assert!(placeholders(&template_code(&snippet)).is_empty());
+        assert_eq!(fill(&template_code(&snippet), &[]), snippet.code);
+        snippet.template = true;
+        assert_eq!(fill(&template_code(&snippet), &[]), "Hello , {{ not a tag");
+    }
+
+    #[test]
+    fn tabstops_become_placeholders() {
+        let body = r"for ${1:i} in ${2|a,b|}; do $TM_FILENAME \$HOME ${3:${4:nested}}; done$0";
+        assert_eq!(
+            from_tabstops(body, Dialect::TextMate),
+            "for {{1:i}} in {{2:a}}; do {{TM_FILENAME}} $HOME {{3:nested}}; done"
+        );
+        // $NAME is plain text in vim snippets
+        assert_eq!(
+            from_tabstops("echo $HOME ${1:x}", Dialect::Plain),
+            "echo $HOME {{1:x}}"
+        );
+    }
+
+    #[test]
+    fn literal_braces_survive_import() {
+        let mut snippet = SaveSnippet::new(from_tabstops("{{ $1 }}", Dialect::TextMate), "html");
+        mark_template(&mut snippet);
+        assert!(snippet.template);
+        assert_eq!(snippet.code, r"\{{ {{1}} }}");
+
+        let mut snippet = SaveSnippet::new(from_tabstops("{{ name }}", Dialect::TextMate), "html");
+        mark_template(&mut snippet);
+        assert!(!snippet.template);
+        assert_eq!(snippet.code, "{{ name }}");
+    }
+
+    #[test]
+    fn placeholders_become_tabstops() {
+        assert_eq!(
+            to_tabstops(
+                "cp {{src}} {{dest:out}} && ls {{src}} $PWD {{TM_FILENAME}}",
+                Dialect::TextMate
+            ),
+            r"cp ${1:src} ${2:out} && ls $1 \$PWD ${TM_FILENAME}"
+        );
+        assert_eq!(
+            to_tabstops(r"echo `date` {{2}} {{x}} \{{", Dialect::Plain),
+            r"echo \`date\` $2 ${1:x} {{"
+        );
+    }
+
+    #[test]
+    fn tabstops_round_trip() {
+        for body in [
+            "fn ${1:name}(${2:args}) {\n\t$3\n}",
+            r"echo \$HOME ${1:a} $1",
+            "{{ ${1:x} }}",
+        ] {
+            let code = from_tabstops(body, Dialect::TextMate);
+            assert_eq!(to_tabstops(&code, Dialect::TextMate), body);
+        }
+    }
+}
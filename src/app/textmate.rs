@@ -0,0 +1,321 @@
+use std::{collections::BTreeMap, path::Path};
+
+use quick_xml::{Reader, escape::unescape, events::Event};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{
+    SaveSnippet, export,
+    highlight::Highlighter,
+    import::Candidate,
+    language::guess_lang,
+    source::file_hash,
+    template::{self, Dialect},
+};
+
+/// a TextMate `.tmSnippet` property list
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TmSnippet {
+    content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tab_trigger: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+}
+
+/// the snippet of a Sublime Text `.sublime-snippet` file
+pub fn parse_sublime(
+    text: &str,
+    path: &Path,
+    highlighter: &Highlighter,
+) -> Result<Vec<Candidate>, String> {
+    let invalid = |err: &dyn std::fmt::Display| {
+        format!("{} is not a Sublime Text snippet: {err}", path.display())
+    };
+    let mut reader = Reader::from_str(text);
+    let mut fields: BTreeMap<String, String> = BTreeMap::new();
+    let mut element: Option<String> = None;
+    loop {
+        match reader.read_event().map_err(|err| invalid(&err))? {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+                element = (name != "snippet").then_some(name);
+            }
+            Event::End(_) => element = None,
+            Event::Text(text) => {
+                if let Some(name) = &element {
+                    let raw = text.decode().map_err(|err| invalid(&err))?;
+                    let text = unescape(&raw).map_err(|err| invalid(&err))?;
+                    fields.entry(name.clone()).or_default().push_str(&text);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(name) = &element {
+                    let text = data.decode().map_err(|err| invalid(&err))?;
+                    fields.entry(name.clone()).or_default().push_str(&text);
+                }
+            }
+            Event::GeneralRef(entity) => {
+                if let Some(name) = &element {
+                    let raw = entity.decode().map_err(|err| invalid(&err))?;
+                    let entity = format!("&{raw};");
+                    let text = unescape(&entity).map_err(|err| invalid(&err))?;
+                    fields.entry(name.clone()).or_default().push_str(&text);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let Some(content) = fields.remove("content") else {
+        return Err(invalid(&"no <content> element"));
+    };
+    let snippet = snippet(
+        &content,
+        fields.remove("description"),
+        fields.remove("tabTrigger"),
+        fields.remove("scope"),
+        path,
+        highlighter,
+    );
+    Ok(vec![Candidate::new(snippet, path.display().to_string())])
+}
+
+/// the snippet of a TextMate `.tmSnippet` file
+pub fn parse_tm(
+    text: &str,
+    path: &Path,
+    highlighter: &Highlighter,
+) -> Result<Vec<Candidate>, String> {
+    let tm: TmSnippet = plist::from_bytes(text.as_bytes())
+        .map_err(|err| format!("{} is not a TextMate snippet: {err}", path.display()))?;
+    let snippet = snippet(
+        &tm.content,
+        tm.name,
+        tm.tab_trigger,
+        tm.scope,
+        path,
+        highlighter,
+    );
+    Ok(vec![Candidate::new(snippet, path.display().to_string())])
+}
+
+/// builds a snippet from the fields both formats share, titled after the file if it has no name
+fn snippet(
+    content: &str,
+    title: Option<String>,
+    trigger: Option<String>,
+    scope: Option<String>,
+    path: &Path,
+    highlighter: &Highlighter,
+) -> SaveSnippet {
+    let code = template::from_tabstops(content.trim_matches('\n'), Dialect::TextMate)
+        .trim_end()
+        .to_string();
+    // only the first selector counts, e.g. "source.python" of "source.python, source.cython"
+    let lang = scope
+        .as_deref()
+        .and_then(|scope| scope.split([',', ' ']).find(|s| !s.is_empty()))
+        .and_then(|scope| highlighter.extension_for_scope(scope))
+        .unwrap_or_else(|| guess_lang(&code));
+    let mut snippet = SaveSnippet::new(code, lang);
+    template::mark_template(&mut snippet);
+    snippet.title = title
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+    snippet.trigger = trigger
+        .map(|trigger| trigger.trim().to_string())
+        .filter(|trigger| !trigger.is_empty());
+    snippet
+}
+
+/// scope selector for a language, e.g. "source.rust"
+fn scope(lang: &str, highlighter: &Highlighter) -> String {
+    highlighter
+        .scope_for(lang)
+        .unwrap_or_else(|| format!("source.{lang}"))
+}
+
+/// snippets as Sublime Text `.sublime-snippet` files, one per snippet.
+/// the title becomes the description shown in the completion list.
+pub fn export_sublime(
+    snippets: &[&SaveSnippet],
+    highlighter: &Highlighter,
+) -> BTreeMap<String, String> {
+    let names = export::file_names(snippets);
+    snippets
+        .iter()
+        .zip(names)
+        .map(|(snippet, name)| {
+            // "]]>" would end the CDATA section early, so it is split across two
+            let content =
+                template::to_tabstops(&template::template_code(snippet), Dialect::TextMate)
+                    .replace("]]>", "]]]]><![CDATA[>");
+            let mut xml = format!("<snippet>\n    <content><![CDATA[\n{content}\n]]></content>\n");
+            if let Some(trigger) = &snippet.trigger {
+                xml.push_str(&format!(
+                    "    <tabTrigger>{}</tabTrigger>\n",
+                    export::escape(trigger)
+                ));
+            }
+            xml.push_str(&format!(
+                "    <scope>{}</scope>\n    <description>{}</description>\n</snippet>\n",
+                export::escape(&scope(&snippet.lang, highlighter)),
+                export::escape(&snippet.title)
+            ));
+            (format!("{name}.sublime-snippet"), xml)
+        })
+        .collect()
+}
+
+/// snippets as TextMate `.tmSnippet` files, one per snippet
+pub fn export_tm(snippets: &[&SaveSnippet], highlighter: &Highlighter) -> BTreeMap<String, String> {
+    let names = export::file_names(snippets);
+    snippets
+        .iter()
+        .zip(names)
+        .map(|(snippet, name)| {
+            // TextMate wants a uuid, derived from the snippet so exports stay stable
+            let hash = file_hash(&format!("{}\n{}", snippet.title, snippet.code));
+            let uuid = format!(
+                "{}-{}-{}-{}-{}",
+                &hash[..8],
+                &hash[8..12],
+                &hash[12..16],
+                &hash[16..20],
+                &hash[20..32]
+            )
+            .to_uppercase();
+            let tm = TmSnippet {
+                content: template::to_tabstops(
+                    &template::template_code(snippet),
+                    Dialect::TextMate,
+                ),
+                name: Some(snippet.title.clone()),
+                tab_trigger: snippet.trigger.clone(),
+                scope: Some(scope(&snippet.lang, highlighter)),
+                uuid: Some(uuid),
+            };
+            let mut xml = Vec::new();
+            plist::to_writer_xml(&mut xml, &tm).expect("failed to serialize snippet");
+            (
+                format!("{name}.tmSnippet"),
+                String::from_utf8_lossy(&xml).to_string() + "\n",
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn python_snippet() -> SaveSnippet {
+        let mut snippet =
+            SaveSnippet::new("def {{name:main}}():\n    return {{value}} ]]> \\n", "py");
+        snippet.title = "Function & return".to_string();
+        snippet.trigger = Some("def".to_string());
+        snippet.template = true;
+        snippet
+    }
+
+    #[test]
+    fn parses_sublime_snippets() {
+        let text = r#"<snippet>
+    <content><![CDATA[
+for ${1:item} in ${2:items}:
+    ${0:pass}
+]]></content>
+    <tabTrigger>for</tabTrigger>
+    <scope>source.python, source.cython</scope>
+    <description>For &amp; loop</description>
+</snippet>"#;
+        let highlighter = Highlighter::new();
+        let candidates =
+            parse_sublime(text, Path::new("for.sublime-snippet"), &highlighter).unwrap();
+        let snippet = &candidates[0].snippet;
+        assert_eq!(snippet.title, "For & loop");
+        assert_eq!(snippet.code, "for {{1:item}} in {{2:items}}:\n    pass");
+        assert_eq!(snippet.lang, "py");
+        assert_eq!(snippet.trigger.as_deref(), Some("for"));
+        assert!(snippet.template);
+    }
+
+    #[test]
+    fn untitled_snippets_are_named_after_the_file() {
+        let text = "<snippet><content>{{ name }}</content></snippet>";
+        let candidates = parse_sublime(
+            text,
+            Path::new("hello.sublime-snippet"),
+            &Highlighter::new(),
+        )
+        .unwrap();
+        assert_eq!(candidates[0].snippet.title, "hello");
+        assert_eq!(candidates[0].snippet.code, "{{ name }}");
+        assert!(!candidates[0].snippet.template);
+        assert!(parse_sublime("<snippet/>", Path::new("x"), &Highlighter::new()).is_err());
+    }
+
+    #[test]
+    fn sublime_round_trip() {
+        let highlighter = Highlighter::new();
+        let snippet = python_snippet();
+        let files = export_sublime(&[&snippet], &highlighter);
+        let (name, xml) = files.iter().next().unwrap();
+        assert_eq!(name, "function-return.sublime-snippet");
+        assert!(xml.contains("<scope>source.python</scope>"));
+        assert!(xml.contains("<description>Function &amp; return</description>"));
+
+        let candidates = parse_sublime(xml, Path::new(name), &highlighter).unwrap();
+        let imported = &candidates[0].snippet;
+        assert_eq!(imported.title, snippet.title);
+        assert_eq!(
+            imported.code,
+            "def {{1:main}}():\n    return {{2:value}} ]]> \\n"
+        );
+        assert_eq!(imported.lang, "py");
+        assert_eq!(imported.trigger, snippet.trigger);
+    }
+
+    #[test]
+    fn tm_round_trip() {
+        let highlighter = Highlighter::new();
+        let mut snippet = python_snippet();
+        snippet.template = false;
+        let files = export_tm(&[&snippet], &highlighter);
+        let (name, plist) = files.iter().next().unwrap();
+        assert_eq!(name, "function-return.tmSnippet");
+        // the uuid only depends on the snippet
+        assert_eq!(export_tm(&[&snippet], &highlighter)[name], *plist);
+
+        let candidates = parse_tm(plist, Path::new(name), &highlighter).unwrap();
+        let imported = &candidates[0].snippet;
+        assert_eq!(imported.title, snippet.title);
+        assert_eq!(imported.code, snippet.code);
+        assert_eq!(imported.lang, "py");
+        assert_eq!(imported.trigger, snippet.trigger);
+        assert!(!imported.template);
+    }
+
+    #[test]
+    fn exported_files_are_unique() {
+        let highlighter = Highlighter::new();
+        let snippets = ["Deploy 2", "Deploy", "Deploy"].map(|title| {
+            let mut snippet = python_snippet();
+            snippet.title = title.to_string();
+            snippet
+        });
+        let snippets: Vec<&SaveSnippet> = snippets.iter().collect();
+        assert_eq!(export_sublime(&snippets, &highlighter).len(), 3);
+        assert_eq!(export_tm(&snippets, &highlighter).len(), 3);
+    }
+}
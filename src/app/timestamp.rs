@@ -0,0 +1,20 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// current time as seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// formats a timestamp relative to now, e.g. "5m ago"
+pub fn ago(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
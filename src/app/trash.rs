@@ -0,0 +1,156 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{
+        CodeCache, SaveSnippet, View, codesnippet::CodeSnippet, ensure_ids, timestamp, undo::Change,
+    },
+    config,
+};
+
+/// a deleted snippet waiting to be restored or purged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub snippet: SaveSnippet,
+    pub deleted_at: u64,
+}
+
+fn trash_path() -> PathBuf {
+    config::data_path("trash.json")
+}
+
+pub fn load() -> Vec<TrashEntry> {
+    let trash_file = fs::read_to_string(trash_path()).unwrap_or("[]".to_string());
+    let mut entries: Vec<TrashEntry> =
+        serde_json::from_str(&trash_file).unwrap_or_else(|_| Vec::new());
+    // snippets trashed before they had ids get the same ones on every load
+    let mut snippets: Vec<SaveSnippet> = entries.iter().map(|e| e.snippet.clone()).collect();
+    ensure_ids(&mut snippets);
+    for (entry, snippet) in entries.iter_mut().zip(snippets) {
+        entry.snippet.id = snippet.id;
+    }
+    entries
+}
+
+pub fn save(entries: &[TrashEntry]) -> Result<(), String> {
+    let trash_str = serde_json::to_string_pretty(entries).expect("failed to save trash to file");
+    let path = trash_path();
+    fs::write(&path, trash_str).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// adds a deleted snippet to the trash, replacing an older copy of it
+pub fn put(snippet: SaveSnippet) -> Result<(), String> {
+    let mut entries = load();
+    entries.retain(|entry| entry.snippet.id != snippet.id);
+    entries.push(TrashEntry {
+        snippet,
+        deleted_at: timestamp::now(),
+    });
+    save(&entries)
+}
+
+/// removes snippets that are back in the library from the trash, called once the
+/// library was saved so a crash in between can't lose them
+pub fn take(ids: &[String]) -> Result<(), String> {
+    let mut entries = load();
+    let before = entries.len();
+    entries.retain(|entry| !ids.contains(&entry.snippet.id));
+    if entries.len() < before {
+        save(&entries)?;
+    }
+    Ok(())
+}
+
+/// permanently deletes entries older than the given number of days, returns how many
+pub fn purge(days: u64) -> Result<usize, String> {
+    let mut entries = load();
+    let cutoff = timestamp::now().saturating_sub(days * 86400);
+    let before = entries.len();
+    entries.retain(|entry| entry.deleted_at >= cutoff);
+    let purged = before - entries.len();
+    if purged > 0 {
+        save(&entries)?;
+    }
+    Ok(purged)
+}
+
+fn trash_cards(entries: &[TrashEntry]) -> Vec<CodeSnippet> {
+    let mut snippets: Vec<SaveSnippet> = entries.iter().map(|e| e.snippet.clone()).collect();
+    for (snippet, entry) in snippets.iter_mut().zip(entries) {
+        let title = if snippet.title.is_empty() {
+            "untitled"
+        } else {
+            &snippet.title
+        };
+        snippet.title = format!("{title} (deleted {})", timestamp::ago(entry.deleted_at));
+    }
+    super::convert_snippets(&snippets, &HashMap::new())
+}
+
+impl CodeCache {
+    /// snippets restored from the trash in this session, to take out of it after saving
+    pub fn restored(&self) -> Vec<String> {
+        self.restored.clone()
+    }
+
+    /// the trash without the snippets already restored in this session
+    fn load_trash(&self) -> Vec<TrashEntry> {
+        let mut entries = load();
+        entries.retain(|entry| !self.restored.contains(&entry.snippet.id));
+        entries
+    }
+
+    pub(super) fn open_trash(&mut self) {
+        self.trash = self.load_trash();
+        self.trash_cards = trash_cards(&self.trash);
+        self.view = View::Trash;
+        if self.trash_state.selected.is_none() && !self.trash.is_empty() {
+            self.trash_state.select(Some(0));
+        }
+    }
+
+    pub(super) fn handle_trash_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.running = false,
+            KeyCode::Esc | KeyCode::Char('t') | KeyCode::Char('T') => self.view = View::Library,
+            KeyCode::Down | KeyCode::PageDown => self.trash_state.next(),
+            KeyCode::Up | KeyCode::PageUp => self.trash_state.previous(),
+            KeyCode::Enter | KeyCode::Char('r') | KeyCode::Char('R') => {
+                if let Some(entry) = self.trash_state.selected.and_then(|i| self.trash.get(i)) {
+                    let snippet = entry.snippet.clone();
+                    let idx = self.save_snippets.len();
+                    self.change(Change::Insert { idx, snippet });
+                    self.notify("Restored snippet, press u to undo");
+                    self.refresh_trash();
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if let Some(idx) = self.trash_state.selected
+                    && idx < self.trash.len()
+                {
+                    let id = self.trash.remove(idx).snippet.id;
+                    let mut entries = load();
+                    entries.retain(|entry| entry.snippet.id != id);
+                    match save(&entries) {
+                        Ok(()) => self.notify("Deleted permanently"),
+                        Err(err) => self.notify(format!("Could not save the trash: {err}")),
+                    }
+                    self.refresh_trash();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn refresh_trash(&mut self) {
+        self.trash = self.load_trash();
+        self.trash_cards = trash_cards(&self.trash);
+        match self.trash_state.selected {
+            _ if self.trash.is_empty() => self.trash_state.select(None),
+            Some(idx) if idx >= self.trash.len() => self.trash_state.select(Some(idx - 1)),
+            _ => {}
+        }
+    }
+}
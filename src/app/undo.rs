@@ -0,0 +1,233 @@
+use zeroize::Zeroize;
+
+use crate::app::{
+    CodeCache, SaveSnippet, history, trash,
+    vault::{self, Unlock},
+};
+
+/// a single change to the library that can be reverted
+#[derive(Debug, Clone)]
+pub enum Change {
+    Insert {
+        idx: usize,
+        snippet: SaveSnippet,
+    },
+    Remove {
+        idx: usize,
+        snippet: SaveSnippet,
+    },
+    Update {
+        idx: usize,
+        before: Box<SaveSnippet>,
+        after: Box<SaveSnippet>,
+    },
+    Move {
+        from: usize,
+        to: usize,
+    },
+    /// several changes that are undone together, e.g. an import
+    Batch(Vec<Change>),
+}
+
+impl Change {
+    pub fn update(idx: usize, before: SaveSnippet, after: SaveSnippet) -> Self {
+        Change::Update {
+            idx,
+            before: Box::new(before),
+            after: Box::new(after),
+        }
+    }
+
+    fn inverse(self) -> Self {
+        match self {
+            Change::Insert { idx, snippet } => Change::Remove { idx, snippet },
+            Change::Remove { idx, snippet } => Change::Insert { idx, snippet },
+            Change::Update { idx, before, after } => Change::Update {
+                idx,
+                before: after,
+                after: before,
+            },
+            Change::Move { from, to } => Change::Move { from: to, to: from },
+            Change::Batch(changes) => {
+                Change::Batch(changes.into_iter().rev().map(Change::inverse).collect())
+            }
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Change::Insert { .. } => "add",
+            Change::Remove { .. } => "delete",
+            Change::Update { .. } => "edit",
+            Change::Move { .. } => "move",
+            Change::Batch(changes)
+                if changes.iter().all(|c| matches!(c, Change::Update { .. })) =>
+            {
+                "edits"
+            }
+            Change::Batch(changes)
+                if changes.iter().all(|c| matches!(c, Change::Insert { .. })) =>
+            {
+                "import"
+            }
+            Change::Batch(_) => "changes",
+        }
+    }
+}
+
+/// undo and redo stacks for the current session
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl UndoStack {
+    pub fn record(&mut self, change: Change) {
+        self.undo.push(change);
+        self.redo.clear();
+    }
+}
+
+impl CodeCache {
+    /// applies a change to the library and keeps the trash in sync with deletions
+    fn apply(&mut self, change: &Change) {
+        self.apply_one(change);
+        self.refresh_cards();
+    }
+
+    fn apply_one(&mut self, change: &Change) {
+        match change {
+            Change::Insert { idx, snippet } => {
+                let idx = (*idx).min(self.save_snippets.len());
+                self.save_snippets.insert(idx, snippet.clone());
+                self.check_source(idx);
+                // it leaves the trash once the library is saved
+                self.restored.push(snippet.id.clone());
+                self.select(idx);
+            }
+            Change::Remove { idx, snippet } => {
+                if *idx < self.save_snippets.len() {
+                    self.save_snippets.remove(*idx);
+                }
+                self.restored.retain(|id| *id != snippet.id);
+                if let Err(err) = trash::put(self.seal_for_trash(snippet.clone())) {
+                    self.notify(format!("Could not save the trash: {err}"));
+                }
+                self.fix_selection(*idx);
+            }
+            Change::Update { idx, after, .. } => {
+                if let Some(item) = self.save_snippets.get_mut(*idx) {
+                    *item = (**after).clone();
+                }
+                self.check_source(*idx);
+                self.select(*idx);
+            }
+            Change::Move { from, to } => {
+                if *from < self.save_snippets.len() && *to < self.save_snippets.len() {
+                    let snippet = self.save_snippets.remove(*from);
+                    self.save_snippets.insert(*to, snippet);
+                    self.select(*to);
+                }
+            }
+            Change::Batch(changes) => {
+                for change in changes {
+                    self.apply_one(change);
+                }
+            }
+        }
+    }
+
+    /// applies a new change and makes it undoable
+    pub(super) fn change(&mut self, change: Change) {
+        self.apply(&change);
+        self.undo_stack.record(change);
+    }
+
+    pub(super) fn undo(&mut self) {
+        let Some(change) = self.undo_stack.undo.pop() else {
+            self.notify("Nothing to undo");
+            return;
+        };
+        // a failure while applying it replaces this message
+        self.notify(format!("Undid {}", change.describe()));
+        self.apply(&change.clone().inverse());
+        self.undo_stack.redo.push(change);
+    }
+
+    pub(super) fn redo(&mut self) {
+        let Some(change) = self.undo_stack.redo.pop() else {
+            self.notify("Nothing to redo");
+            return;
+        };
+        self.notify(format!("Redid {}", change.describe()));
+        self.apply(&change);
+        self.undo_stack.undo.push(change);
+    }
+
+    /// moves a snippet to the trash
+    pub(super) fn delete(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        // the trash only takes a secret encrypted, which needs the passphrase again
+        if snippet.secret
+            && !snippet.code.is_empty()
+            && snippet.sealed.is_none()
+            && !self.keyring.is_unlocked()
+        {
+            self.ask_passphrase(Unlock::Delete(idx));
+            return;
+        }
+        self.notify("Moved to trash, press u to undo");
+        self.change(Change::Remove { idx, snippet });
+    }
+
+    /// swaps a snippet with its neighbour
+    pub(super) fn move_snippet(&mut self, idx: usize, down: bool) {
+        // swaps with the neighbour in the list, which skips snippets the filter hides
+        let visible = self.visible();
+        let Some(position) = visible.iter().position(|&i| i == idx) else {
+            return;
+        };
+        let neighbour = if down {
+            visible.get(position + 1)
+        } else {
+            position.checked_sub(1).and_then(|p| visible.get(p))
+        };
+        let Some(&to) = neighbour else {
+            return;
+        };
+        match self.save_snippets[to].library.clone() {
+            Some(library) => self.notify(format!(
+                "Snippets can't move past the read-only {library} library"
+            )),
+            None => self.change(Change::Move { from: idx, to }),
+        }
+    }
+
+    /// keeps the selection in range after a snippet at `idx` was removed
+    pub(super) fn fix_selection(&mut self, idx: usize) {
+        let visible = self.visible();
+        let position = visible
+            .iter()
+            .position(|&i| i >= idx)
+            .unwrap_or(visible.len().saturating_sub(1));
+        self.list_state
+            .select((!visible.is_empty()).then_some(position));
+    }
+
+    /// secrets must never reach the trash file in cleartext
+    fn seal_for_trash(&mut self, mut snippet: SaveSnippet) -> SaveSnippet {
+        if snippet.secret {
+            history::forget_code(&mut snippet);
+        }
+        if snippet.secret && !snippet.code.is_empty() {
+            if let Ok(sealed) = vault::seal_code(&mut self.keyring, &snippet) {
+                snippet.sealed = Some(sealed);
+            }
+            snippet.code.zeroize();
+        }
+        snippet
+    }
+}
@@ -0,0 +1,235 @@
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use tui_popup::Popup;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::app::{
+    CodeCache, Keyring, SaveSnippet, history,
+    undo::{Change, UndoStack},
+};
+
+/// what to do once the passphrase has been entered
+#[derive(Debug, Clone, Copy)]
+pub enum Unlock {
+    Reveal(usize),
+    MarkSecret(usize),
+    Delete(usize),
+    /// secrets revealed before the keyring was locked need it again to be saved
+    Quit,
+}
+
+/// encrypts the revealed code of a secret. unchanged code keeps its ciphertext, fresh nonces
+/// would otherwise make the same secret differ on both sides of every merge.
+pub(super) fn seal_code(keyring: &mut Keyring, snippet: &SaveSnippet) -> Result<String, String> {
+    if let Some(sealed) = &snippet.sealed
+        && keyring.open(sealed).is_ok_and(|code| *code == snippet.code)
+    {
+        return Ok(sealed.clone());
+    }
+    keyring.seal(&snippet.code)
+}
+
+/// masked input for the session passphrase
+pub struct PassphrasePrompt {
+    input: Zeroizing<String>,
+    then: Unlock,
+}
+
+impl CodeCache {
+    /// marks the selected snippet as secret, or turns a revealed secret back into a normal one
+    pub(super) fn toggle_secret(&mut self, idx: usize) {
+        let Some(before) = self.save_snippets.get(idx).cloned() else {
+            return;
+        };
+        let mut after = before.clone();
+        if before.secret {
+            if before.is_locked() {
+                self.notify("Reveal the snippet with x before unmarking it");
+                return;
+            }
+            after.secret = false;
+            after.sealed = None;
+            self.change(Change::update(idx, before, after));
+            self.notify("Snippet is no longer secret");
+        } else if self.keyring.is_unlocked() {
+            after.secret = true;
+            history::forget_code(&mut after);
+            self.change(Change::update(idx, before, after));
+            self.notify("Snippet will be stored encrypted");
+        } else {
+            self.ask_passphrase(Unlock::MarkSecret(idx));
+        }
+    }
+
+    /// decrypts a secret snippet for display, or hides it again
+    pub(super) fn toggle_reveal(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get(idx) else {
+            return;
+        };
+        if !snippet.secret {
+            return;
+        }
+        if !snippet.is_locked() {
+            self.hide(idx);
+        } else if self.keyring.is_unlocked() {
+            self.reveal(idx);
+        } else {
+            self.ask_passphrase(Unlock::Reveal(idx));
+        }
+    }
+
+    fn reveal(&mut self, idx: usize) {
+        let Some(sealed) = self.save_snippets.get(idx).and_then(|s| s.sealed.clone()) else {
+            return;
+        };
+        match self.keyring.open(&sealed) {
+            Ok(code) => {
+                self.save_snippets[idx].code = code.to_string();
+                self.check_source(idx);
+                self.refresh_cards();
+            }
+            // the passphrase was checked when it was entered, other secrets still need it
+            Err(err) => self.notify(format!("Could not reveal snippet: {err}")),
+        }
+    }
+
+    fn hide(&mut self, idx: usize) {
+        let Some(snippet) = self.save_snippets.get_mut(idx) else {
+            return;
+        };
+        match seal_code(&mut self.keyring, snippet) {
+            Ok(sealed) => {
+                snippet.sealed = Some(sealed);
+                snippet.code.zeroize();
+                self.refresh_cards();
+            }
+            Err(err) => self.notify(format!("Could not hide snippet: {err}")),
+        }
+    }
+
+    pub(super) fn ask_passphrase(&mut self, then: Unlock) {
+        self.passphrase_prompt = Some(PassphrasePrompt {
+            input: Zeroizing::new(String::new()),
+            then,
+        });
+    }
+
+    pub(super) fn handle_passphrase_key(&mut self, key: KeyCode) {
+        let Some(prompt) = &mut self.passphrase_prompt else {
+            return;
+        };
+        match key {
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Esc => self.passphrase_prompt = None,
+            KeyCode::Enter => {
+                let Some(prompt) = self.passphrase_prompt.take() else {
+                    return;
+                };
+                if prompt.input.is_empty() {
+                    return;
+                }
+                self.keyring.unlock(prompt.input);
+                if !self.verify_passphrase() {
+                    self.keyring.lock();
+                    self.notify("Wrong passphrase");
+                    return;
+                }
+                match prompt.then {
+                    Unlock::Reveal(idx) => self.reveal(idx),
+                    Unlock::MarkSecret(idx) => self.toggle_secret(idx),
+                    Unlock::Delete(idx) => self.delete(idx),
+                    Unlock::Quit => self.running = false,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// checks the passphrase against an existing secret, any passphrase is fine for the first one
+    fn verify_passphrase(&mut self) -> bool {
+        let Some(sealed) = self.save_snippets.iter().find_map(|s| s.sealed.clone()) else {
+            return true;
+        };
+        self.keyring.open(&sealed).is_ok()
+    }
+
+    pub(super) fn draw_passphrase_prompt(&self, frame: &mut Frame) {
+        let Some(prompt) = &self.passphrase_prompt else {
+            return;
+        };
+        let masked = "*".repeat(prompt.input.chars().count());
+        let body = format!("{masked:<30}\n\nenter to unlock, esc to cancel");
+        frame.render_widget(
+            Popup::new(body)
+                .title("Passphrase")
+                .style(Style::default().fg(Color::LightBlue)),
+            frame.area(),
+        );
+    }
+
+    /// copy of the library with every secret encrypted, as it is written to disk
+    /// fails if a secret can't be sealed, its edits would otherwise be lost
+    pub(super) fn sealed_snippets(&mut self) -> Result<Vec<SaveSnippet>, String> {
+        let mut snippets = self.save_snippets.clone();
+        for snippet in snippets
+            .iter_mut()
+            .filter(|s| s.secret && !s.code.is_empty())
+        {
+            match seal_code(&mut self.keyring, snippet) {
+                Ok(sealed) => {
+                    snippet.sealed = Some(sealed);
+                    snippet.code.zeroize();
+                }
+                Err(err) => {
+                    return Err(format!(
+                        "Could not encrypt \"{}\" ({err}), enter the passphrase to save it",
+                        snippet.title
+                    ));
+                }
+            }
+        }
+        Ok(snippets)
+    }
+
+    /// asks for the passphrase again when the library can't be saved without it
+    pub(super) fn unlock_to_quit(&mut self, err: String) {
+        self.running = true;
+        self.notify(err);
+        self.ask_passphrase(Unlock::Quit);
+    }
+
+    /// wipes decrypted secret code from memory, including the copies kept for undo and in the form
+    pub(super) fn forget_secrets(&mut self) {
+        for snippet in self.save_snippets.iter_mut().filter(|s| s.secret) {
+            snippet.code.zeroize();
+        }
+        self.undo_stack = UndoStack::default();
+        self.form = None;
+        self.fill_prompt = None;
+        self.refresh_cards();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_code_keeps_its_ciphertext() {
+        let mut keyring = Keyring::new();
+        keyring.unlock(Zeroizing::new("correct horse".to_string()));
+        let mut snippet = SaveSnippet::new("export TOKEN=hunter2", "sh");
+        snippet.secret = true;
+        let sealed = seal_code(&mut keyring, &snippet).unwrap();
+        snippet.sealed = Some(sealed.clone());
+        assert_eq!(seal_code(&mut keyring, &snippet).unwrap(), sealed);
+
+        snippet.code = "export TOKEN=hunter3".to_string();
+        let resealed = seal_code(&mut keyring, &snippet).unwrap();
+        assert_ne!(resealed, sealed);
+        assert_eq!(keyring.open(&resealed).unwrap().as_str(), snippet.code);
+    }
+}
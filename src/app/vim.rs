@@ -0,0 +1,472 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::app::{
+    SaveSnippet, dirstore, export,
+    highlight::Highlighter,
+    import::Candidate,
+    language::guess_lang,
+    template::{self, Dialect},
+};
+
+/// vim filetypes that differ from the file extension codecache stores.
+/// the first entry of an extension is used for export.
+const FILETYPES: [(&str, &str); 20] = [
+    ("rs", "rust"),
+    ("py", "python"),
+    ("js", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescriptreact"),
+    ("jsx", "javascriptreact"),
+    ("rb", "ruby"),
+    ("md", "markdown"),
+    ("pl", "perl"),
+    ("hs", "haskell"),
+    ("yml", "yaml"),
+    ("txt", "text"),
+    ("kt", "kotlin"),
+    ("ex", "elixir"),
+    ("erl", "erlang"),
+    ("ml", "ocaml"),
+    ("clj", "clojure"),
+    ("sh", "sh"),
+    ("sh", "bash"),
+    ("sh", "zsh"),
+];
+
+/// vim filetype for a codecache language
+pub(super) fn filetype(lang: &str) -> String {
+    FILETYPES
+        .iter()
+        .find(|(ext, _)| *ext == lang)
+        .map(|(_, filetype)| filetype.to_string())
+        // it names the exported file, which has to stay in the export directory
+        .unwrap_or_else(|| dirstore::extension(lang))
+}
+
+/// codecache language for a vim filetype, None for `all` and unknown ones
+pub(super) fn language_for_filetype(filetype: &str, highlighter: &Highlighter) -> Option<String> {
+    let filetype = filetype.trim().to_lowercase();
+    if let Some((ext, _)) = FILETYPES.iter().find(|(_, known)| *known == filetype) {
+        return Some(ext.to_string());
+    }
+    highlighter.extension_for(&filetype)
+}
+
+/// filetype of a snippet file: `python.snippets`, `python_django.snippets`,
+/// `python-django.snippets` or any file in a `python/` directory
+fn language_of(path: &Path, highlighter: &Highlighter) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    let filetype = stem.split(['_', '-']).next().unwrap_or_default();
+    language_for_filetype(filetype, highlighter).or_else(|| {
+        let dir = path.parent()?.file_name()?.to_string_lossy().to_string();
+        language_for_filetype(&dir, highlighter)
+    })
+}
+
+/// snippets of a vim `.snippets` file, in UltiSnips or SnipMate syntax
+pub fn parse(text: &str, path: &Path, highlighter: &Highlighter) -> Vec<Candidate> {
+    let lang = language_of(path, highlighter);
+    // only UltiSnips closes its snippets
+    let entries = if text.lines().any(|line| line.trim_end() == "endsnippet") {
+        parse_ultisnips(text)
+    } else {
+        parse_snipmate(text)
+    };
+    entries
+        .into_iter()
+        .map(|entry| {
+            let origin = format!("{}:{}", path.display(), entry.line);
+            Candidate::new(entry.into_snippet(lang.clone()), origin)
+        })
+        .collect()
+}
+
+/// the snippet of a SnipMate `.snippet` file, named after its trigger
+pub fn parse_single(text: &str, path: &Path, highlighter: &Highlighter) -> Vec<Candidate> {
+    let trigger = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let entry = Entry {
+        line: 1,
+        trigger: trigger.clone(),
+        title: trigger,
+        comments: Vec::new(),
+        body: text.lines().map(str::to_string).collect(),
+    };
+    let lang = language_of(path, highlighter);
+    vec![Candidate::new(
+        entry.into_snippet(lang),
+        path.display().to_string(),
+    )]
+}
+
+/// a snippet as found in a vim snippet file
+struct Entry {
+    line: usize,
+    trigger: String,
+    title: String,
+    /// comment lines right above the snippet
+    comments: Vec<String>,
+    body: Vec<String>,
+}
+
+impl Entry {
+    fn into_snippet(self, lang: Option<String>) -> SaveSnippet {
+        let body = self.body.join("\n");
+        let code = template::from_tabstops(&body, Dialect::Plain)
+            .trim_end()
+            .to_string();
+        let lang = lang.unwrap_or_else(|| guess_lang(&code));
+        let mut snippet = SaveSnippet::new(code, lang);
+        template::mark_template(&mut snippet);
+        snippet.title = if self.title.is_empty() {
+            self.trigger.clone()
+        } else {
+            self.title
+        };
+        snippet.desc = self.comments.join("\n");
+        snippet.trigger = (!self.trigger.is_empty()).then_some(self.trigger);
+        snippet
+    }
+}
+
+fn parse_ultisnips(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut comments: Vec<String> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((n, line)) = lines.next() {
+        if let Some(comment) = line.strip_prefix('#') {
+            comments.push(comment.trim().to_string());
+        } else if let Some(header) = line.strip_prefix("snippet ") {
+            let (trigger, title) = ultisnips_header(header);
+            let body = lines
+                .by_ref()
+                .map(|(_, line)| line)
+                .take_while(|line| line.trim_end() != "endsnippet")
+                .map(str::to_string)
+                .collect();
+            entries.push(Entry {
+                line: n + 1,
+                trigger,
+                title,
+                comments: std::mem::take(&mut comments),
+                body,
+            });
+        } else if line.starts_with("global ") {
+            // python helpers, nothing to import
+            lines
+                .by_ref()
+                .find(|(_, line)| line.trim_end() == "endglobal");
+            comments.clear();
+        } else if !["context ", "pre_expand ", "post_expand ", "post_jump "]
+            .iter()
+            .any(|action| line.starts_with(action))
+        {
+            // priority, extends, clearsnippets and blank lines end a comment
+            comments.clear();
+        }
+    }
+    entries
+}
+
+/// splits `trigger "description" options` of an UltiSnips snippet line
+fn ultisnips_header(header: &str) -> (String, String) {
+    let mut rest = header.trim();
+    let mut options = "";
+    if let Some((before, last)) = rest.rsplit_once(char::is_whitespace)
+        && !last.contains('"')
+        && before.trim_end().ends_with('"')
+    {
+        options = last;
+        rest = before.trim_end();
+    }
+    let mut title = String::new();
+    if rest.len() > 1
+        && rest.ends_with('"')
+        && let Some(start) = rest[..rest.len() - 1].rfind('"')
+        && !rest[..start].trim().is_empty()
+    {
+        title = rest[start + 1..rest.len() - 1].to_string();
+        rest = rest[..start].trim_end();
+    }
+    // triggers with spaces or regular expressions are wrapped in a pair of the same character
+    let mut trigger = rest.to_string();
+    let mut chars = rest.chars();
+    if let (Some(first), Some(last)) = (chars.next(), chars.next_back())
+        && first == last
+        && !first.is_alphanumeric()
+        && (rest.contains(char::is_whitespace) || options.contains('r'))
+    {
+        trigger = rest[first.len_utf8()..rest.len() - last.len_utf8()].to_string();
+    }
+    (trigger, title)
+}
+
+fn parse_snipmate(text: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut comments: Vec<String> = Vec::new();
+    let mut in_body = false;
+    for (n, line) in text.lines().enumerate() {
+        if in_body && let Some(entry) = entries.last_mut() {
+            if let Some(body) = line.strip_prefix('\t') {
+                entry.body.push(body.to_string());
+                continue;
+            }
+            // blank lines belong to the body if it goes on after them
+            if line.trim().is_empty() {
+                entry.body.push(String::new());
+                continue;
+            }
+            in_body = false;
+            while entry.body.last().is_some_and(|line| line.is_empty()) {
+                entry.body.pop();
+            }
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            comments.push(comment.trim().to_string());
+        } else if let Some(header) = line.strip_prefix("snippet ") {
+            let (trigger, title) = header
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or((header.trim(), ""));
+            entries.push(Entry {
+                line: n + 1,
+                trigger: trigger.to_string(),
+                title: title.trim().to_string(),
+                comments: std::mem::take(&mut comments),
+                body: Vec::new(),
+            });
+            in_body = true;
+        } else {
+            comments.clear();
+        }
+    }
+    entries
+}
+
+/// trigger of a snippet for formats that need one, made from the title if it has none
+pub(super) fn trigger(snippet: &SaveSnippet) -> String {
+    snippet
+        .trigger
+        .clone()
+        .unwrap_or_else(|| export::slug(&snippet.title))
+}
+
+/// snippets grouped into one `<filetype>.snippets` file per language
+fn by_filetype<'a>(snippets: &[&'a SaveSnippet]) -> BTreeMap<String, Vec<&'a SaveSnippet>> {
+    let mut files: BTreeMap<String, Vec<&SaveSnippet>> = BTreeMap::new();
+    for snippet in snippets {
+        let name = format!("{}.snippets", filetype(&snippet.lang));
+        files.entry(name).or_default().push(snippet);
+    }
+    files
+}
+
+fn comments(desc: &str) -> String {
+    desc.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| format!("# {line}\n"))
+        .collect()
+}
+
+/// snippets as UltiSnips files, the title is the description and the description a comment
+pub fn export_ultisnips(snippets: &[&SaveSnippet]) -> BTreeMap<String, String> {
+    by_filetype(snippets)
+        .into_iter()
+        .map(|(name, snippets)| {
+            let mut text = String::new();
+            for snippet in snippets {
+                let mut trigger = trigger(snippet);
+                if trigger.contains(char::is_whitespace) {
+                    trigger = format!("!{trigger}!");
+                }
+                text.push_str(&comments(&snippet.desc));
+                text.push_str(&format!(
+                    "snippet {trigger} \"{}\"\n{}\nendsnippet\n\n",
+                    snippet.title.replace('"', "'"),
+                    template::to_tabstops(&template::template_code(snippet), Dialect::Plain)
+                ));
+            }
+            (name, text)
+        })
+        .collect()
+}
+
+/// snippets as SnipMate files with tab indented bodies
+pub fn export_snipmate(snippets: &[&SaveSnippet]) -> BTreeMap<String, String> {
+    by_filetype(snippets)
+        .into_iter()
+        .map(|(name, snippets)| {
+            let mut text = String::new();
+            for snippet in snippets {
+                let body: String =
+                    template::to_tabstops(&template::template_code(snippet), Dialect::Plain)
+                        .lines()
+                        .map(|line| format!("\t{line}\n"))
+                        .collect();
+                text.push_str(&comments(&snippet.desc));
+                text.push_str(&format!(
+                    "snippet {} {}\n{body}\n",
+                    trigger(snippet).replace(char::is_whitespace, "-"),
+                    snippet.title
+                ));
+            }
+            (name, text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippets(candidates: Vec<Candidate>) -> Vec<SaveSnippet> {
+        candidates.into_iter().map(|c| c.snippet).collect()
+    }
+
+    #[test]
+    fn parses_ultisnips() {
+        let text = r#"priority -50
+
+global !p
+def helper(): pass
+endglobal
+
+# prints a value
+snippet pr "Print value" b
+print(${1:value}, `!p snip.rv = "x"`)
+endsnippet
+
+snippet "a b" "Spaced trigger" r
+{{ name }} $HOME
+endsnippet
+"#;
+        let snippets = snippets(parse(
+            text,
+            Path::new("python.snippets"),
+            &Highlighter::new(),
+        ));
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].title, "Print value");
+        assert_eq!(snippets[0].trigger.as_deref(), Some("pr"));
+        assert_eq!(snippets[0].desc, "prints a value");
+        assert_eq!(
+            snippets[0].code,
+            r#"print({{1:value}}, `!p snip.rv = "x"`)"#
+        );
+        assert_eq!(snippets[0].lang, "py");
+        assert!(snippets[0].template);
+        assert_eq!(snippets[1].trigger.as_deref(), Some("a b"));
+        assert_eq!(snippets[1].code, "{{ name }} $HOME");
+        assert!(!snippets[1].template);
+    }
+
+    #[test]
+    fn ultisnips_headers() {
+        assert_eq!(
+            ultisnips_header(r#"fn "Function" b"#),
+            ("fn".to_string(), "Function".to_string())
+        );
+        assert_eq!(
+            ultisnips_header(r#"!a b! "Spaced""#),
+            ("a b".to_string(), "Spaced".to_string())
+        );
+        assert_eq!(
+            ultisnips_header(r#""^\s*if" "If" r"#),
+            (r"^\s*if".to_string(), "If".to_string())
+        );
+        assert_eq!(
+            ultisnips_header("todo"),
+            ("todo".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn parses_snipmate() {
+        let text = "# a loop\nsnippet for for loop\n\tfor ${1:i} in $2; do\n\n\t\t${3}\n\tdone\n\n\nsnippet x\n\techo x\n";
+        let snippets = snippets(parse(
+            text,
+            Path::new("sh/loops.snippets"),
+            &Highlighter::new(),
+        ));
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].title, "for loop");
+        assert_eq!(snippets[0].desc, "a loop");
+        assert_eq!(
+            snippets[0].code,
+            "for {{1:i}} in {{2}}; do\n\n\t{{3}}\ndone"
+        );
+        assert_eq!(snippets[0].lang, "sh");
+        assert_eq!(snippets[1].title, "x");
+        assert_eq!(snippets[1].code, "echo x");
+    }
+
+    #[test]
+    fn single_snippet_files_are_named_after_the_trigger() {
+        let snippets = snippets(parse_single(
+            "puts ${1:x}\n",
+            Path::new("ruby/pt.snippet"),
+            &Highlighter::new(),
+        ));
+        assert_eq!(snippets[0].title, "pt");
+        assert_eq!(snippets[0].trigger.as_deref(), Some("pt"));
+        assert_eq!(snippets[0].code, "puts {{1:x}}");
+        assert_eq!(snippets[0].lang, "rb");
+    }
+
+    fn library() -> Vec<SaveSnippet> {
+        let mut print = SaveSnippet::new("print({{value}}, `date`, $x)", "py");
+        print.title = "Print \"it\"".to_string();
+        print.desc = "prints a value".to_string();
+        print.trigger = Some("p r".to_string());
+        print.template = true;
+        let mut jinja = SaveSnippet::new("{% for x in xs %}\n  {{ x }}\n{% endfor %}", "py");
+        jinja.title = "Jinja loop".to_string();
+        vec![print, jinja]
+    }
+
+    #[test]
+    fn ultisnips_round_trip() {
+        let library = library();
+        let files = export_ultisnips(&library.iter().collect::<Vec<_>>());
+        let text = &files["python.snippets"];
+        assert!(text.contains("snippet !p r! \"Print 'it'\"\nprint(${1:value}, \\`date\\`, \\$x)"));
+
+        let snippets = snippets(parse(
+            text,
+            Path::new("python.snippets"),
+            &Highlighter::new(),
+        ));
+        assert_eq!(snippets[0].title, "Print 'it'");
+        assert_eq!(snippets[0].trigger.as_deref(), Some("p r"));
+        assert_eq!(snippets[0].desc, library[0].desc);
+        assert_eq!(snippets[0].code, "print({{1:value}}, `date`, $x)");
+        assert_eq!(snippets[1].code, library[1].code);
+        assert_eq!(snippets[1].trigger.as_deref(), Some("jinja-loop"));
+        assert!(!snippets[1].template);
+    }
+
+    #[test]
+    fn snipmate_round_trip() {
+        let library = library();
+        let files = export_snipmate(&library.iter().collect::<Vec<_>>());
+        let snippets = snippets(parse(
+            &files["python.snippets"],
+            Path::new("python.snippets"),
+            &Highlighter::new(),
+        ));
+        assert_eq!(snippets[0].trigger.as_deref(), Some("p-r"));
+        assert_eq!(snippets[0].code, "print({{1:value}}, `date`, $x)");
+        assert_eq!(snippets[1].title, library[1].title);
+        assert_eq!(snippets[1].code, library[1].code);
+    }
+
+    #[test]
+    fn exported_files_stay_in_the_directory() {
+        assert_eq!(filetype("rs"), "rust");
+        let snippet = SaveSnippet::new("echo", "../../x");
+        let files = export_snipmate(&[&snippet]);
+        assert!(files.contains_key("x.snippets"));
+    }
+}
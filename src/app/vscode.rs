@@ -0,0 +1,273 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde_json::{Map, Value, json};
+
+use crate::app::{
+    SaveSnippet, dirstore, export,
+    highlight::Highlighter,
+    import::Candidate,
+    language::guess_lang,
+    template::{self, Dialect},
+};
+
+/// VS Code language ids that differ from the file extension codecache stores
+const LANGUAGE_IDS: [(&str, &str); 17] = [
+    ("rs", "rust"),
+    ("py", "python"),
+    ("js", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescriptreact"),
+    ("jsx", "javascriptreact"),
+    ("sh", "shellscript"),
+    ("cs", "csharp"),
+    ("rb", "ruby"),
+    ("md", "markdown"),
+    ("txt", "plaintext"),
+    ("yml", "yaml"),
+    ("ps1", "powershell"),
+    ("pl", "perl"),
+    ("kt", "kotlin"),
+    ("hs", "haskell"),
+    ("ex", "elixir"),
+];
+
+/// VS Code language id for a codecache language
+pub fn language_id(lang: &str) -> String {
+    LANGUAGE_IDS
+        .iter()
+        .find(|(ext, _)| *ext == lang)
+        .map(|(_, id)| id.to_string())
+        // it names the exported file, which has to stay in the export directory
+        .unwrap_or_else(|| dirstore::extension(lang))
+}
+
+/// codecache language for a VS Code language id
+fn language_from_id(id: &str, highlighter: &Highlighter) -> Option<String> {
+    let id = id.trim().to_lowercase();
+    if let Some((ext, _)) = LANGUAGE_IDS.iter().find(|(_, known)| *known == id) {
+        return Some(ext.to_string());
+    }
+    highlighter.extension_for(&id)
+}
+
+/// snippets of a VS Code snippet file, either `<language>.json` or a global `.code-snippets`
+pub fn parse(text: &str, path: &Path, highlighter: &Highlighter) -> Result<Vec<Candidate>, String> {
+    let json: Map<String, Value> = serde_json::from_str(&strip_jsonc(text))
+        .map_err(|err| format!("{} is not a VS Code snippet file: {err}", path.display()))?;
+    // language specific files are named after the language and have no scope
+    let file_lang = path
+        .file_stem()
+        .and_then(|stem| language_from_id(&stem.to_string_lossy(), highlighter));
+
+    let mut candidates = Vec::new();
+    for (name, entry) in json {
+        let Some(entry) = entry.as_object() else {
+            continue;
+        };
+        let body = match entry.get("body") {
+            Some(Value::String(line)) => line.clone(),
+            Some(Value::Array(lines)) => lines
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => continue,
+        };
+        let code = template::from_tabstops(&body, Dialect::TextMate)
+            .trim_end()
+            .to_string();
+        let scope_lang = entry
+            .get("scope")
+            .and_then(Value::as_str)
+            .and_then(|scope| {
+                scope
+                    .split(',')
+                    .find_map(|id| language_from_id(id, highlighter))
+            });
+        let lang = scope_lang
+            .or_else(|| file_lang.clone())
+            .unwrap_or_else(|| guess_lang(&code));
+
+        let mut snippet = SaveSnippet::new(code, lang);
+        template::mark_template(&mut snippet);
+        snippet.title = name.clone();
+        snippet.desc = entry
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        snippet.trigger = match entry.get("prefix") {
+            Some(Value::String(prefix)) => Some(prefix.clone()),
+            Some(Value::Array(prefixes)) => {
+                prefixes.first().and_then(Value::as_str).map(str::to_string)
+            }
+            _ => None,
+        };
+        candidates.push(Candidate::new(
+            snippet,
+            format!("{}: {name}", path.display()),
+        ));
+    }
+    Ok(candidates)
+}
+
+/// snippets as VS Code snippet files, one per language id
+pub fn export(snippets: &[&SaveSnippet]) -> BTreeMap<String, String> {
+    let mut files: BTreeMap<String, Map<String, Value>> = BTreeMap::new();
+    for snippet in snippets {
+        let file = files.entry(language_id(&snippet.lang)).or_default();
+        // titles are the keys, so repeated titles get a number
+        let mut key = snippet.title.clone();
+        let mut n = 1;
+        while file.contains_key(&key) {
+            n += 1;
+            key = format!("{} ({n})", snippet.title);
+        }
+        let prefix = snippet
+            .trigger
+            .clone()
+            .unwrap_or_else(|| export::slug(&snippet.title));
+        let body: Vec<String> =
+            template::to_tabstops(&template::template_code(snippet), Dialect::TextMate)
+                .split('\n')
+                .map(str::to_string)
+                .collect();
+        let mut entry = json!({ "prefix": prefix, "body": body });
+        if !snippet.desc.is_empty() {
+            entry["description"] = json!(snippet.desc);
+        }
+        file.insert(key, entry);
+    }
+    files
+        .into_iter()
+        .map(|(id, file)| {
+            let text = serde_json::to_string_pretty(&file).expect("failed to serialize snippets");
+            (format!("{id}.json"), text + "\n")
+        })
+        .collect()
+}
+
+/// removes comments and trailing commas, VS Code snippet files are JSON with comments
+fn strip_jsonc(text: &str) -> String {
+    let without_comments = outside_strings(text, |chars, i, out| {
+        if chars[i] == '/' && chars.get(i + 1) == Some(&'/') {
+            chars[i..]
+                .iter()
+                .position(|c| *c == '\n')
+                .map_or(chars.len(), |n| i + n)
+        } else if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+            (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                .map_or(chars.len(), |j| j + 2)
+        } else {
+            out.push(chars[i]);
+            i + 1
+        }
+    });
+    outside_strings(&without_comments, |chars, i, out| {
+        // a comma followed only by whitespace before the closing bracket is dropped
+        let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+        if chars[i] != ',' || !matches!(next, Some('}' | ']')) {
+            out.push(chars[i]);
+        }
+        i + 1
+    })
+}
+
+/// copies JSON strings as they are and lets `step` handle everything else.
+/// `step` gets the position and returns the position to continue at.
+fn outside_strings(
+    text: &str,
+    mut step: impl FnMut(&[char], usize, &mut String) -> usize,
+) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    let mut in_string = false;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            out.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                out.push(chars[i + 1]);
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+            i += 1;
+        } else if c == '"' {
+            in_string = true;
+            out.push(c);
+            i += 1;
+        } else {
+            i = step(&chars, i, &mut out);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_snippet_files_with_comments() {
+        let text = r#"{
+            // a comment
+            "Print to console": {
+                "prefix": ["log", "console"],
+                "body": ["console.log('${1:hello}');", "$0"],
+                "description": "Log output", /* trailing */
+            },
+            "Handlebars": { "prefix": "hb", "body": "{{name}} // not a comment" },
+        }"#;
+        let candidates = parse(text, Path::new("javascript.json"), &Highlighter::new()).unwrap();
+        let mut snippets: Vec<&SaveSnippet> = candidates.iter().map(|c| &c.snippet).collect();
+        snippets.sort_by_key(|s| std::cmp::Reverse(&s.title));
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].title, "Print to console");
+        assert_eq!(snippets[0].code, "console.log('{{1:hello}}');");
+        assert_eq!(snippets[0].lang, "js");
+        assert_eq!(snippets[0].trigger.as_deref(), Some("log"));
+        assert_eq!(snippets[0].desc, "Log output");
+        assert!(snippets[0].template);
+        assert_eq!(snippets[1].code, "{{name}} // not a comment");
+        assert!(!snippets[1].template);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let mut log = SaveSnippet::new("console.log({{msg:'hi'}}, {{msg}});", "js");
+        log.title = "Log".to_string();
+        log.template = true;
+        let mut mustache = SaveSnippet::new("<b>{{name}}</b> costs $5", "js");
+        mustache.title = "Log".to_string();
+        mustache.trigger = Some("mu".to_string());
+
+        let files = export(&[&log, &mustache]);
+        let text = &files["javascript.json"];
+        assert!(text.contains(r#""console.log(${1:'hi'}, $1);""#));
+        assert!(text.contains(r#""<b>{{name}}</b> costs \\$5""#));
+
+        let candidates = parse(text, Path::new("javascript.json"), &Highlighter::new()).unwrap();
+        let snippets: Vec<&SaveSnippet> = candidates.iter().map(|c| &c.snippet).collect();
+        assert_eq!(snippets[0].title, "Log");
+        assert_eq!(snippets[0].code, "console.log({{1:'hi'}}, {{1}});");
+        assert!(snippets[0].template);
+        assert_eq!(snippets[1].title, "Log (2)");
+        assert_eq!(snippets[1].code, mustache.code);
+        assert_eq!(snippets[1].trigger.as_deref(), Some("mu"));
+        assert!(!snippets[1].template);
+    }
+
+    #[test]
+    fn language_ids() {
+        assert_eq!(language_id("rs"), "rust");
+        assert_eq!(language_id("lua"), "lua");
+        assert_eq!(language_id("../../x"), "x");
+        assert_eq!(
+            language_from_id("ShellScript", &Highlighter::new()).as_deref(),
+            Some("sh")
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::app::{
+    SaveSnippet, export,
+    highlight::Highlighter,
+    import::Candidate,
+    language::guess_lang,
+    template::{self, Dialect},
+    vim,
+};
+
+/// emacs major modes whose name is not `<vim filetype>-mode`.
+/// the first entry of a filetype is used for export.
+const MODES: [(&str, &str); 10] = [
+    ("javascript", "js"),
+    ("javascript", "js2"),
+    ("cpp", "c++"),
+    ("lisp", "emacs-lisp"),
+    ("lisp", "lisp-interaction"),
+    ("tex", "latex"),
+    ("perl", "cperl"),
+    ("html", "mhtml"),
+    ("sh", "sh"),
+    ("sh", "shell-script"),
+];
+
+/// header lines yasnippet understands, other comments become the description
+const DIRECTIVES: [&str; 9] = [
+    "name",
+    "key",
+    "group",
+    "contributor",
+    "condition",
+    "binding",
+    "expand-env",
+    "type",
+    "uuid",
+];
+
+/// whether a file sits in a `<mode>-mode` directory of a yasnippet collection
+pub fn is_snippet_file(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    let in_mode_dir = path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .is_some_and(|dir| dir.to_string_lossy().ends_with("-mode"));
+    !hidden && in_mode_dir
+}
+
+/// emacs major mode for a codecache language
+fn mode(lang: &str) -> String {
+    let filetype = vim::filetype(lang);
+    let mode = MODES
+        .iter()
+        .find(|(known, _)| *known == filetype)
+        .map(|(_, mode)| mode.to_string())
+        .unwrap_or(filetype);
+    format!("{mode}-mode")
+}
+
+/// codecache language for an emacs major mode like `python-mode` or `rust-ts-mode`
+fn language_for_mode(mode: &str, highlighter: &Highlighter) -> Option<String> {
+    let mode = mode.trim_end_matches("-mode").trim_end_matches("-ts");
+    let filetype = MODES
+        .iter()
+        .find(|(_, known)| *known == mode)
+        .map(|(filetype, _)| *filetype)
+        .unwrap_or(mode);
+    vim::language_for_filetype(filetype, highlighter)
+}
+
+/// the snippet of a yasnippet file, the language comes from its mode directory
+pub fn parse(text: &str, path: &Path, highlighter: &Highlighter) -> Vec<Candidate> {
+    let file_name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (header, body) = match text.split_once("\n# --\n") {
+        Some((header, body)) => (header, body),
+        None => match text.strip_suffix("\n# --") {
+            Some(header) => (header, ""),
+            None => ("", text),
+        },
+    };
+
+    let mut directives: BTreeMap<String, String> = BTreeMap::new();
+    let mut comments: Vec<String> = Vec::new();
+    for line in header.lines() {
+        let Some(comment) = line.strip_prefix('#') else {
+            continue;
+        };
+        let comment = comment.trim();
+        // the "-*- mode: snippet -*-" marker for emacs itself
+        if comment.starts_with("-*-") {
+            continue;
+        }
+        match comment.split_once(':') {
+            Some((key, value)) if DIRECTIVES.contains(&key.trim()) => {
+                directives.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            _ => comments.push(comment.to_string()),
+        }
+    }
+    // command snippets are emacs lisp to run, not text to insert
+    if directives.get("type").is_some_and(|kind| kind == "command") {
+        return Vec::new();
+    }
+
+    let code = template::from_tabstops(body, Dialect::Plain)
+        .trim_end()
+        .to_string();
+    let lang = path
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .and_then(|mode| language_for_mode(&mode.to_string_lossy(), highlighter))
+        .unwrap_or_else(|| guess_lang(&code));
+    let mut snippet = SaveSnippet::new(code, lang);
+    template::mark_template(&mut snippet);
+    let trigger = directives
+        .remove("key")
+        .unwrap_or_else(|| file_name.clone());
+    snippet.title = directives.remove("name").unwrap_or(file_name);
+    snippet.desc = comments.join("\n");
+    snippet.trigger = Some(trigger);
+    vec![Candidate::new(snippet, path.display().to_string())]
+}
+
+/// snippets as a yasnippet collection, `<mode>-mode/<name>` per snippet
+pub fn export(snippets: &[&SaveSnippet]) -> BTreeMap<String, String> {
+    let names = export::file_names(snippets);
+    snippets
+        .iter()
+        .zip(names)
+        .map(|(snippet, name)| {
+            let mut text = String::from("# -*- mode: snippet -*-\n");
+            text.push_str(&format!("# name: {}\n", snippet.title));
+            text.push_str(&format!("# key: {}\n", vim::trigger(snippet)));
+            for line in snippet.desc.lines().filter(|line| !line.trim().is_empty()) {
+                text.push_str(&format!("# {line}\n"));
+            }
+            text.push_str("# --\n");
+            text.push_str(&template::to_tabstops(
+                &template::template_code(snippet),
+                Dialect::Plain,
+            ));
+            (format!("{}/{name}", mode(&snippet.lang)), text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_snippets() {
+        let text = "# -*- mode: snippet -*-\n# name: Define function\n# key: def\n# group: definitions\n# a plain comment\n# --\ndef ${1:name}(${2:$$(yas-text)}):\n    ${0:pass}\n";
+        let highlighter = Highlighter::new();
+        let candidates = parse(text, Path::new("python-mode/def"), &highlighter);
+        let snippet = &candidates[0].snippet;
+        assert_eq!(snippet.title, "Define function");
+        assert_eq!(snippet.trigger.as_deref(), Some("def"));
+        assert_eq!(snippet.desc, "a plain comment");
+        assert_eq!(
+            snippet.code,
+            "def {{1:name}}({{2:$$(yas-text)}}):\n    pass"
+        );
+        assert_eq!(snippet.lang, "py");
+        assert!(snippet.template);
+    }
+
+    #[test]
+    fn snippets_without_header_use_the_file_name() {
+        let highlighter = Highlighter::new();
+        let candidates = parse("{{ name }}", Path::new("html-mode/var"), &highlighter);
+        let snippet = &candidates[0].snippet;
+        assert_eq!(snippet.title, "var");
+        assert_eq!(snippet.trigger.as_deref(), Some("var"));
+        assert_eq!(snippet.code, "{{ name }}");
+        assert!(!snippet.template);
+
+        let command = "# type: command\n# --\n(message \"hi\")";
+        assert!(parse(command, Path::new("sh-mode/hi"), &highlighter).is_empty());
+    }
+
+    #[test]
+    fn modes() {
+        let highlighter = Highlighter::new();
+        assert_eq!(mode("js"), "js-mode");
+        assert_eq!(mode("sh"), "sh-mode");
+        assert_eq!(mode("../../x"), "x-mode");
+        assert_eq!(
+            language_for_mode("rust-ts-mode", &highlighter).as_deref(),
+            Some("rs")
+        );
+        assert_eq!(
+            language_for_mode("shell-script-mode", &highlighter).as_deref(),
+            Some("sh")
+        );
+        assert!(is_snippet_file(Path::new("snippets/python-mode/def")));
+        assert!(!is_snippet_file(Path::new(
+            "snippets/python-mode/.yas-parents"
+        )));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut snippet = SaveSnippet::new("(message \"{{text:hi}}\" `x` $y)", "lisp");
+        snippet.title = "Message".to_string();
+        snippet.desc = "shows a message".to_string();
+        snippet.template = true;
+        let files = export(&[&snippet]);
+        let (path, text) = files.iter().next().unwrap();
+        assert_eq!(path, "emacs-lisp-mode/message");
+
+        let candidates = parse(text, Path::new(path), &Highlighter::new());
+        let imported = &candidates[0].snippet;
+        assert_eq!(imported.title, snippet.title);
+        assert_eq!(imported.trigger.as_deref(), Some("message"));
+        assert_eq!(imported.desc, snippet.desc);
+        assert_eq!(imported.code, "(message \"{{1:hi}}\" `x` $y)");
+        assert_eq!(imported.lang, "lisp");
+    }
+
+    #[test]
+    fn exported_files_are_unique() {
+        let snippets = ["Deploy 2", "Deploy", "Deploy"].map(|title| {
+            let mut snippet = SaveSnippet::new("kubectl apply -f .", "sh");
+            snippet.title = title.to_string();
+            snippet
+        });
+        let snippets: Vec<&SaveSnippet> = snippets.iter().collect();
+        let files = export(&snippets);
+        assert_eq!(files.len(), 3);
+        assert!(files.contains_key("sh-mode/deploy-3"));
+    }
+}
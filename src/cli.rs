@@ -0,0 +1,209 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Add a snippet from a file, some of its lines, or stdin
+    Add {
+        /// `path`, `path:40-72` or `-` for stdin, reads stdin if left out
+        target: Option<String>,
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long)]
+        desc: Option<String>,
+        /// file extension of the language, e.g. "rs"
+        #[arg(long)]
+        lang: Option<String>,
+        /// comma separated tags
+        #[arg(long)]
+        tags: Option<String>,
+    },
+    /// Import code blocks from Markdown files, snippets of VS Code, Sublime Text, TextMate, vim
+    /// and emacs, or pet and navi cheatsheets
+    Import {
+        /// files or directories to scan
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// import everything that is not a duplicate without showing the preview
+        #[arg(long)]
+        all: bool,
+    },
+    /// Export snippets to Markdown, a single HTML page, a static site or editor snippet files
+    Export {
+        #[arg(value_enum)]
+        format: ExportFormat,
+        /// output file, or directory for a site and editor snippets; single files go to stdout if left out
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// only export snippets with this tag, can be repeated
+        #[arg(long)]
+        tag: Vec<String>,
+        /// only export snippets matching a search, e.g. "docker tag:ops lang:sh"
+        #[arg(long)]
+        search: Option<String>,
+        /// group sections by tag instead of language
+        #[arg(long)]
+        by_tag: bool,
+    },
+    /// Pick a snippet and print its code, e.g. `$(codecache pick --lang sh)`
+    Pick {
+        /// initial search, e.g. "docker tag:ops"
+        query: Option<String>,
+        /// only show snippets in this language, can be repeated
+        #[arg(long)]
+        lang: Vec<String>,
+        /// only show snippets with this tag, can be repeated
+        #[arg(long)]
+        tag: Vec<String>,
+        /// print the code as it is instead of asking for placeholder values
+        #[arg(long)]
+        raw: bool,
+        /// end the output with a NUL byte instead of a newline
+        #[arg(long, short = '0')]
+        print0: bool,
+        /// print the snippet with its title, language and tags as JSON
+        #[arg(long, conflicts_with = "print0")]
+        json: bool,
+        /// print one line per snippet for launchers like rofi or dmenu instead of showing the picker
+        #[arg(long)]
+        list: bool,
+        /// print the snippet of a line from --list, "-" reads the line from stdin
+        #[arg(long, conflicts_with = "list")]
+        select: Option<String>,
+    },
+    /// Print shell widgets to pick snippets into the command line and save the last command,
+    /// e.g. `eval "$(codecache init bash)"`
+    Init {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Compile or lint snippets with the check command of their language and record the result
+    Check {
+        /// only check snippets in this language, can be repeated
+        #[arg(long)]
+        lang: Vec<String>,
+        /// only check snippets with this tag, can be repeated
+        #[arg(long)]
+        tag: Vec<String>,
+        /// only check snippets matching a search, e.g. "docker tag:ops"
+        #[arg(long)]
+        search: Option<String>,
+    },
+    /// Format snippets with the formatter of their language, showing a diff before applying it
+    Format {
+        /// only format snippets in this language, can be repeated
+        #[arg(long)]
+        lang: Vec<String>,
+        /// only format snippets with this tag, can be repeated
+        #[arg(long)]
+        tag: Vec<String>,
+        /// only format snippets matching a search, e.g. "docker tag:ops"
+        #[arg(long)]
+        search: Option<String>,
+        /// apply the changes without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// only show the diff and exit with 1 if any snippet is not formatted
+        #[arg(long, conflicts_with = "yes")]
+        check: bool,
+    },
+    /// Check which snippets changed in the file they were taken from
+    Sources {
+        /// update stale snippets from their files
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Pull the library from `git_remote`, merge it with local changes snippet by snippet and push it
+    Sync,
+    /// Merge two versions of the library with their common ancestor snippet by snippet,
+    /// also works as git merge driver: `codecache merge %O %A %B`
+    Merge {
+        /// the common ancestor, may be empty
+        base: PathBuf,
+        /// our version, replaced with the result unless --output is given
+        ours: PathBuf,
+        /// their version
+        theirs: PathBuf,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// resolve conflicts right away instead of leaving them for the app
+        #[arg(long, value_enum)]
+        keep: Option<Keep>,
+    },
+    /// Watch the clipboard and collect code-looking clips into the inbox
+    Watch {
+        /// seconds between clipboard polls
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+    },
+    /// List, restore or purge deleted snippets
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TrashAction {
+    /// Show deleted snippets
+    List,
+    /// Move a deleted snippet back into the library
+    Restore {
+        /// number shown by `codecache trash list`
+        number: usize,
+    },
+    /// Permanently delete snippets that were deleted a while ago
+    Purge {
+        /// only purge snippets deleted more than this many days ago
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+    },
+    /// Permanently delete everything in the trash
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Site,
+    /// one `<language>.json` per language, for `.vscode` or the user snippets folder
+    Vscode,
+    /// one `.sublime-snippet` per snippet
+    Sublime,
+    /// one `.tmSnippet` per snippet
+    Textmate,
+    /// one UltiSnips `<filetype>.snippets` per language
+    Ultisnips,
+    /// one SnipMate `<filetype>.snippets` per language
+    Snipmate,
+    /// a yasnippet collection with a directory per major mode
+    Yasnippet,
+    /// a pet `snippet.toml`
+    Pet,
+    /// a navi `.cheat` file
+    Navi,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Keep {
+    Ours,
+    Theirs,
+    /// keep ours and add theirs as a copy tagged "conflict"
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+/// user configuration, read from `config.toml` in the codecache config directory
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// which clipboard backend to use
+    pub clipboard: ClipboardBackend,
+    /// copy command for the `command` backend, e.g. "wl-copy"
+    pub clipboard_copy: Option<String>,
+    /// paste command for the `command` backend, e.g. "wl-paste --no-newline"
+    pub clipboard_paste: Option<String>,
+    /// encrypt the whole snippet library with a passphrase
+    pub encrypt_store: bool,
+    /// days before deleted snippets are purged from the trash, 0 keeps them forever
+    pub trash_retention_days: u64,
+    /// syntect theme used for highlighting in the app and in HTML exports
+    pub theme: String,
+    /// commands that run snippets per language, e.g. `py = "python3 {file}"`
+    pub runners: BTreeMap<String, String>,
+    /// ask before running a snippet
+    pub confirm_run: bool,
+    /// commands that check snippets per language, e.g. `json = "jq empty"`
+    pub checks: BTreeMap<String, String>,
+    /// commands that format snippets per language, reading stdin and writing stdout
+    pub formatters: BTreeMap<String, String>,
+    /// format pasted code with the formatter of its language
+    pub format_on_paste: bool,
+    /// spaces per tab in the indentation of pasted code, 0 keeps tabs
+    pub tab_width: usize,
+    /// keep the library as one file per snippet in this directory instead of `snippets.json`
+    pub library_dir: Option<PathBuf>,
+    /// keep the library in a git repository and commit every save
+    pub git: bool,
+    /// url or path of the repository `codecache sync` pulls from and pushes to
+    pub git_remote: Option<String>,
+    /// branch used for syncing
+    pub git_branch: String,
+    /// read-only team libraries shown next to the personal one
+    pub libraries: Vec<Library>,
+}
+
+/// a read-only library mounted from a file or directory, e.g. a shared git checkout
+#[derive(Debug, Clone, Deserialize)]
+pub struct Library {
+    /// shown on the snippets of the library and used to filter by it
+    pub name: String,
+    /// a `snippets.json` file or a library directory
+    pub path: PathBuf,
+    /// libraries with a higher priority are listed first and win when two have the
+    /// same snippet, the personal library has priority 0
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardBackend {
+    /// pick the best available backend at startup
+    #[default]
+    Auto,
+    Arboard,
+    Osc52,
+    Tmux,
+    WlCopy,
+    Xclip,
+    Xsel,
+    Pbcopy,
+    /// user-defined commands from `clipboard_copy` / `clipboard_paste`
+    Command,
+    /// in-app buffer only, never touches the system clipboard
+    Internal,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clipboard: ClipboardBackend::default(),
+            clipboard_copy: None,
+            clipboard_paste: None,
+            encrypt_store: false,
+            trash_retention_days: 30,
+            theme: "base16-eighties.dark".to_string(),
+            runners: BTreeMap::new(),
+            confirm_run: true,
+            checks: BTreeMap::new(),
+            formatters: BTreeMap::new(),
+            format_on_paste: false,
+            tab_width: 0,
+            library_dir: None,
+            git: false,
+            git_remote: None,
+            git_branch: "main".to_string(),
+            libraries: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// loads the config file, falling back to defaults if it is missing or invalid
+    pub fn load() -> Self {
+        let path = get_config_path();
+        let Ok(config_file) = fs::read_to_string(&path) else {
+            return Config::default();
+        };
+        toml::from_str(&config_file).unwrap_or_else(|err| {
+            eprintln!("ignoring invalid config file {}: {err}", path.display());
+            Config::default()
+        })
+    }
+}
+
+/// returns the path of a file inside the codecache data directory, creating the directory
+pub fn data_path(file_name: &str) -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| {
+        #[cfg(target_os = "windows")]
+        return PathBuf::from(std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string()));
+        #[cfg(not(target_os = "windows"))]
+        return PathBuf::from("~/.local/share");
+    });
+
+    path.push("codecache");
+    fs::create_dir_all(&path).expect("failed to create data directory");
+
+    path.push(file_name);
+    path
+}
+
+/// replaces a leading `~` with the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn get_config_path() -> PathBuf {
+    let mut path = dirs::config_dir().unwrap_or_else(|| {
+        #[cfg(target_os = "windows")]
+        return PathBuf::from(std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string()));
+        #[cfg(not(target_os = "windows"))]
+        return PathBuf::from("~/.config");
+    });
+
+    path.push("codecache");
+    path.push("config.toml");
+    path
+}
@@ -0,0 +1,518 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+use crate::{
+    app::{
+        Keyring, SaveSnippet,
+        merge::{self, Resolution},
+        prompt_passphrase,
+    },
+    config::Config,
+    store,
+};
+
+/// the trash, the inbox and anything else in the data directory stay out of the repository
+const GITIGNORE: &str = "*\n!.gitignore\n!.gitattributes\n!snippets.json\n";
+
+/// the library is merged by `codecache merge` instead of line by line
+const GITATTRIBUTES: &str = "snippets.json merge=codecache\n";
+
+fn run(dir: &Path, args: &[&str]) -> Result<Output, String> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| format!("failed to run git: {err}"))
+}
+
+/// runs git in the library directory and returns what it printed
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = run(dir, args)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.trim().to_string())
+            .unwrap_or_else(|| format!("git {} failed", args[0]));
+        return Err(message);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// whether a git command that answers with its exit code says yes
+fn succeeds(dir: &Path, args: &[&str]) -> bool {
+    run(dir, args).is_ok_and(|output| output.status.success())
+}
+
+/// turns the library directory into a repository if it is not one yet.
+/// a library directory may also live somewhere in an existing checkout, e.g. a team repository.
+fn ensure_repo(path: &Path, branch: &str) -> Result<(), String> {
+    let dir = library_dir(path);
+    if path.is_dir() {
+        if !succeeds(dir, &["rev-parse", "--is-inside-work-tree"]) {
+            git(dir, &["init", "--quiet", "--initial-branch", branch])?;
+        }
+        return Ok(());
+    }
+    if !dir.join(".git").exists() {
+        git(dir, &["init", "--quiet", "--initial-branch", branch])?;
+    }
+    let gitignore = dir.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, GITIGNORE)
+            .map_err(|err| format!("{}: {err}", gitignore.display()))?;
+    }
+    let attributes = dir.join(".gitattributes");
+    if !attributes.exists() {
+        fs::write(&attributes, GITATTRIBUTES)
+            .map_err(|err| format!("{}: {err}", attributes.display()))?;
+        register_driver(dir)?;
+    }
+    Ok(())
+}
+
+/// makes plain `git merge` and `git pull` in the library directory merge snippet by snippet
+fn register_driver(dir: &Path) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|err| format!("failed to find codecache: {err}"))?;
+    let driver = format!(
+        "{} merge %O %A %B",
+        shell_words::quote(&exe.to_string_lossy())
+    );
+    git(
+        dir,
+        &["config", "merge.codecache.name", "codecache snippet merge"],
+    )?;
+    git(dir, &["config", "merge.codecache.driver", &driver])?;
+    Ok(())
+}
+
+/// commits and merges need a name and an email, codecache signs its own if git has none configured
+fn commit_args<'a>(dir: &Path, args: &[&'a str]) -> Vec<&'a str> {
+    let mut full = Vec::new();
+    if !succeeds(dir, &["config", "user.email"]) {
+        full.extend([
+            "-c",
+            "user.name=codecache",
+            "-c",
+            "user.email=codecache@localhost",
+        ]);
+    }
+    full.extend(args);
+    full
+}
+
+/// commits the library if it changed, creating the repository on first use
+pub fn commit(path: &Path, branch: &str, message: &str) -> Result<(), String> {
+    let dir = library_dir(path);
+    ensure_repo(path, branch)?;
+    if path.is_dir() {
+        // only the library directory, whatever else is staged in the checkout stays staged
+        git(dir, &["add", "--all", "--", "."])?;
+        if succeeds(dir, &["diff", "--cached", "--quiet", "--", "."]) {
+            return Ok(());
+        }
+        git(
+            dir,
+            &commit_args(dir, &["commit", "--quiet", "-m", message, "--", "."]),
+        )?;
+        return Ok(());
+    }
+    git(dir, &["add", "--", ".gitignore", ".gitattributes"])?;
+    if path.exists() {
+        git(dir, &["add", "--", "snippets.json"])?;
+    }
+    if succeeds(dir, &["diff", "--cached", "--quiet"]) {
+        return Ok(());
+    }
+    git(
+        dir,
+        &commit_args(dir, &["commit", "--quiet", "-m", message]),
+    )?;
+    Ok(())
+}
+
+/// directory git runs in, the library itself if it is a directory of snippet files
+fn library_dir(path: &Path) -> &Path {
+    if path.is_dir() {
+        return path;
+    }
+    path.parent().unwrap_or(Path::new("."))
+}
+
+/// commit message for a save, "edit: <title>" for a single change
+/// and a summary with one line per snippet for several
+pub fn describe_changes(old: &[SaveSnippet], new: &[SaveSnippet]) -> String {
+    let old_by_id: HashMap<&str, &SaveSnippet> = old.iter().map(|s| (s.id.as_str(), s)).collect();
+    let new_by_id: HashMap<&str, &SaveSnippet> = new.iter().map(|s| (s.id.as_str(), s)).collect();
+    let mut changes = Vec::new();
+    for snippet in new {
+        match old_by_id.get(snippet.id.as_str()) {
+            None => changes.push(format!("add: {}", snippet.title)),
+            Some(before) if *before != snippet => changes.push(format!("edit: {}", snippet.title)),
+            Some(_) => {}
+        }
+    }
+    for snippet in old {
+        if !new_by_id.contains_key(snippet.id.as_str()) {
+            changes.push(format!("delete: {}", snippet.title));
+        }
+    }
+    match changes.len() {
+        0 => "edit: reorder snippets".to_string(),
+        1 => changes.remove(0),
+        count => format!("edit: {count} snippets\n\n{}", changes.join("\n")),
+    }
+}
+
+/// library file as of a commit, empty if it did not exist there
+fn library_at(dir: &Path, rev: &str, keyring: &mut Keyring) -> Result<Vec<SaveSnippet>, String> {
+    let spec = format!("{rev}:snippets.json");
+    if !succeeds(dir, &["cat-file", "-e", &spec]) {
+        return Ok(Vec::new());
+    }
+    store::parse(&git(dir, &["show", &spec])?, keyring)
+        .map_err(|err| format!("snippets.json in {rev}: {err}"))
+}
+
+/// pulls the library from the configured remote, merging it snippet by snippet
+/// with local changes, and pushes the result. returns what happened.
+pub fn sync(path: &Path, config: &Config, keyring: &mut Keyring) -> Result<String, String> {
+    let remote = config
+        .git_remote
+        .as_deref()
+        .ok_or("no remote to sync with, set git_remote in the config to the url of a repository")?;
+    let branch = config.git_branch.as_str();
+    let dir = library_dir(path);
+    ensure_repo(path, branch)?;
+    let is_dir = path.is_dir();
+    if !is_dir {
+        // codecache may have moved since the driver was registered
+        register_driver(dir)?;
+    }
+
+    // an existing origin, e.g. of a checkout the library lives in, is never changed
+    match git(dir, &["remote", "get-url", "origin"]) {
+        Ok(origin) if origin == remote => {}
+        Ok(origin) => {
+            return Err(format!(
+                "origin of {} is {origin}, not git_remote {remote}, change one of them to sync",
+                dir.display()
+            ));
+        }
+        Err(_) => {
+            git(dir, &["remote", "add", "origin", remote])?;
+        }
+    }
+
+    // edits made while git was turned off
+    let head = if succeeds(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]) && !is_dir {
+        library_at(dir, "HEAD", keyring)?
+    } else {
+        Vec::new()
+    };
+    let current = fs::read_to_string(path).unwrap_or_default();
+    let message = if is_dir {
+        "edit: files changed outside codecache".to_string()
+    } else {
+        match store::parse(&current, keyring) {
+            Ok(snippets) if !config.encrypt_store => describe_changes(&head, &snippets),
+            _ => "edit: encrypted library".to_string(),
+        }
+    };
+    commit(path, branch, &message)?;
+
+    git(dir, &["fetch", "--quiet", "origin"])?;
+
+    let upstream = format!("refs/remotes/origin/{branch}");
+    let push_ref = format!("HEAD:refs/heads/{branch}");
+    if !succeeds(dir, &["rev-parse", "--verify", "--quiet", &upstream]) {
+        git(dir, &["push", "--quiet", "origin", &push_ref])?;
+        return Ok(format!("pushed the library to {remote}"));
+    }
+    let ahead = git(dir, &["rev-list", "--count", &format!("{upstream}..HEAD")])?;
+    let behind = git(dir, &["rev-list", "--count", &format!("HEAD..{upstream}")])?;
+    match (ahead.as_str(), behind.as_str()) {
+        ("0", "0") => return Ok("already up to date".to_string()),
+        (_, "0") => {
+            git(dir, &["push", "--quiet", "origin", &push_ref])?;
+            return Ok(format!("pushed {ahead} commit(s)"));
+        }
+        ("0", _) => {
+            git(dir, &["merge", "--quiet", "--ff-only", &upstream])?;
+            return Ok(format!("pulled {behind} commit(s)"));
+        }
+        _ => {}
+    }
+
+    if is_dir {
+        // every snippet is its own file, so git merges them one by one already
+        let message = format!("sync: merge origin/{branch}");
+        let args = ["merge", "--quiet", "--no-ff", "-m", &message, &upstream];
+        git(dir, &commit_args(dir, &args)).map_err(|err| {
+            format!(
+                "{err}, resolve the conflicting files in {} and commit them",
+                dir.display()
+            )
+        })?;
+        git(dir, &["push", "--quiet", "origin", &push_ref])?;
+        return Ok(format!(
+            "merged {behind} remote commit(s) with {ahead} local one(s)"
+        ));
+    }
+
+    // both sides changed, the library is merged by snippet instead of by line
+    // histories that started apart, e.g. two machines that both had a library, have no base
+    let base_rev = git(dir, &["merge-base", "HEAD", &upstream]).ok();
+    let base = match &base_rev {
+        Some(base) => library_at(dir, base, keyring)?,
+        None => Vec::new(),
+    };
+    let ours = store::parse(&current, keyring)?;
+    let theirs = library_at(dir, &upstream, keyring)?;
+    let merged = merge::merge(&base, &ours, &theirs);
+    let conflicts: Vec<String> = merged
+        .conflicts
+        .iter()
+        .map(|conflict| format!("conflict: {}", conflict.ours.title))
+        .collect();
+    let snippets = merged.into_snippets();
+
+    let mut args = vec!["merge", "--quiet", "--no-commit", "--no-ff", "-s", "ours"];
+    if base_rev.is_none() {
+        args.push("--allow-unrelated-histories");
+    }
+    args.push(&upstream);
+    git(dir, &commit_args(dir, &args))?;
+    store::write(path, &snippets, config, keyring)?;
+    git(dir, &["add", "--", "snippets.json"])?;
+    let mut message = format!("sync: merge origin/{branch}");
+    if !conflicts.is_empty() && !config.encrypt_store {
+        message.push_str(&format!("\n\n{}", conflicts.join("\n")));
+    }
+    git(
+        dir,
+        &commit_args(dir, &["commit", "--quiet", "-m", &message]),
+    )?;
+    git(dir, &["push", "--quiet", "origin", &push_ref])?;
+
+    let mut report = format!("merged {behind} remote commit(s) with {ahead} local one(s)");
+    if !conflicts.is_empty() {
+        report.push_str(&format!(
+            ", {} snippet(s) were changed on both sides, open codecache and press m to resolve them",
+            conflicts.len()
+        ));
+    }
+    Ok(report)
+}
+
+/// reads a library given to `codecache merge`, asking for the passphrase once if it is encrypted
+fn read_library(path: &Path, keyring: &mut Keyring) -> Result<Vec<SaveSnippet>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    if store::is_encrypted(&text) && !keyring.is_unlocked() {
+        let passphrase = prompt_passphrase("Passphrase for your snippet library")
+            .ok_or("no passphrase entered")?;
+        keyring.unlock(passphrase);
+    }
+    store::parse(&text, keyring).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// merges three versions of a library file, as `codecache merge` and as git merge driver.
+/// the result replaces `ours` unless an output is given, encrypted if `ours` was.
+/// returns how many conflicts are left for the resolver in the app.
+pub fn merge_files(
+    [base, ours, theirs]: [&Path; 3],
+    output: Option<&Path>,
+    resolution: Option<Resolution>,
+    config: &Config,
+    keyring: &mut Keyring,
+) -> Result<usize, String> {
+    let encrypted = fs::read_to_string(ours).is_ok_and(|text| store::is_encrypted(&text));
+    let merged = merge::merge(
+        &read_library(base, keyring)?,
+        &read_library(ours, keyring)?,
+        &read_library(theirs, keyring)?,
+    );
+    let mut snippets = merged.into_snippets();
+    if let Some(resolution) = resolution {
+        snippets = snippets
+            .iter()
+            .flat_map(|snippet| merge::resolve(snippet, resolution))
+            .collect();
+    }
+    let conflicts = snippets.iter().filter(|s| s.conflict.is_some()).count();
+    let config = Config {
+        encrypt_store: encrypted,
+        ..config.clone()
+    };
+    store::write(output.unwrap_or(ours), &snippets, &config, keyring)?;
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// a bare repository to sync with, in a fresh temporary directory
+    fn remote(name: &str) -> (PathBuf, Config) {
+        let root = env::temp_dir().join(format!("codecache-git-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let bare = root.join("remote.git");
+        fs::create_dir_all(&bare).unwrap();
+        git(
+            &bare,
+            &["init", "--quiet", "--bare", "--initial-branch", "main"],
+        )
+        .unwrap();
+        let config = Config {
+            git: true,
+            git_remote: Some(bare.to_string_lossy().to_string()),
+            ..Config::default()
+        };
+        (root, config)
+    }
+
+    /// a machine with a library file, which is committed on every save
+    fn machine(root: &Path, name: &str) -> PathBuf {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("snippets.json")
+    }
+
+    fn save(path: &Path, snippets: &[SaveSnippet], config: &Config) {
+        store::save(path, snippets, config, &mut Keyring::new()).unwrap();
+    }
+
+    fn load(path: &Path) -> Vec<SaveSnippet> {
+        store::parse(&fs::read_to_string(path).unwrap(), &mut Keyring::new()).unwrap()
+    }
+
+    fn snippet(title: &str, code: &str) -> SaveSnippet {
+        let mut snippet = SaveSnippet::new(code, "sh");
+        snippet.title = title.to_string();
+        snippet
+    }
+
+    #[test]
+    fn pushes_and_fast_forwards() {
+        let (root, config) = remote("forward");
+        let laptop = machine(&root, "laptop");
+        let desktop = machine(&root, "desktop");
+        save(&laptop, &[snippet("Hello", "echo hello")], &config);
+        let sync = |path: &Path| sync(path, &config, &mut Keyring::new());
+
+        assert!(sync(&laptop).unwrap().starts_with("pushed the library"));
+        assert_eq!(sync(&laptop).unwrap(), "already up to date");
+
+        // a second machine starting from the pushed library only pulls
+        let remote = config.git_remote.as_deref().unwrap();
+        git(
+            &root,
+            &["clone", "--quiet", "--branch", "main", remote, "desktop"],
+        )
+        .unwrap();
+        let mut snippets = load(&laptop);
+        snippets.push(snippet("World", "echo world"));
+        save(&laptop, &snippets, &config);
+        assert_eq!(sync(&laptop).unwrap(), "pushed 1 commit(s)");
+        assert_eq!(sync(&desktop).unwrap(), "pulled 1 commit(s)");
+        assert_eq!(load(&desktop), snippets);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn merges_diverged_libraries_by_snippet() {
+        let (root, config) = remote("diverged");
+        let laptop = machine(&root, "laptop");
+        let desktop = machine(&root, "desktop");
+        let original = snippet("Hello", "echo hello");
+        save(&laptop, std::slice::from_ref(&original), &config);
+        sync(&laptop, &config, &mut Keyring::new()).unwrap();
+        let remote = config.git_remote.as_deref().unwrap();
+        git(
+            &root,
+            &["clone", "--quiet", "--branch", "main", remote, "desktop"],
+        )
+        .unwrap();
+
+        let edit = |path: &Path, code: &str, added: &str| {
+            let mut changed = original.clone();
+            changed.code = code.to_string();
+            save(path, &[changed, snippet(added, "true")], &config);
+        };
+        edit(&laptop, "echo hi", "From the laptop");
+        edit(&desktop, "echo hey", "From the desktop");
+        sync(&laptop, &config, &mut Keyring::new()).unwrap();
+        let report = sync(&desktop, &config, &mut Keyring::new()).unwrap();
+        assert!(report.starts_with("merged 1 remote commit(s) with 1 local one(s)"));
+        assert!(report.contains("1 snippet(s) were changed on both sides"));
+
+        let merged = load(&desktop);
+        let titles: Vec<&str> = merged.iter().map(|s| s.title.as_str()).collect();
+        assert!(titles.contains(&"From the laptop") && titles.contains(&"From the desktop"));
+        let hello = merged.iter().find(|s| s.id == original.id).unwrap();
+        assert!(hello.conflict.is_some());
+        // the merge was pushed, so the laptop only pulls it
+        assert_eq!(
+            sync(&laptop, &config, &mut Keyring::new()).unwrap(),
+            "pulled 2 commit(s)"
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn keeps_an_origin_that_points_elsewhere() {
+        let (root, config) = remote("origin");
+        let laptop = machine(&root, "laptop");
+        save(&laptop, &[snippet("Hello", "echo hello")], &config);
+        let dir = laptop.parent().unwrap();
+        git(
+            dir,
+            &["remote", "add", "origin", "https://example.com/other.git"],
+        )
+        .unwrap();
+
+        let err = sync(&laptop, &config, &mut Keyring::new()).unwrap_err();
+        assert!(err.contains("https://example.com/other.git"), "{err}");
+        assert_eq!(
+            git(dir, &["remote", "get-url", "origin"]).unwrap(),
+            "https://example.com/other.git"
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn describes_changes() {
+        let hello = snippet("Hello", "echo hello");
+        let world = snippet("World", "echo world");
+        let mut edited = hello.clone();
+        edited.code = "echo hi".to_string();
+
+        assert_eq!(
+            describe_changes(&[], std::slice::from_ref(&hello)),
+            "add: Hello"
+        );
+        assert_eq!(
+            describe_changes(std::slice::from_ref(&hello), std::slice::from_ref(&edited)),
+            "edit: Hello"
+        );
+        assert_eq!(
+            describe_changes(
+                &[hello.clone(), world.clone()],
+                &[world.clone(), hello.clone()]
+            ),
+            "edit: reorder snippets"
+        );
+        assert_eq!(
+            describe_changes(&[hello, world], &[edited]),
+            "edit: 2 snippets\n\nedit: Hello\ndelete: World"
+        );
+    }
+}
@@ -1,35 +1,570 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::PathBuf,
+    process,
+    time::Duration,
+};
+
+use clap::Parser;
 
 mod app;
+mod cli;
+mod config;
+mod git;
+mod pick;
+mod shell;
+mod store;
+
+use app::{
+    cheatsheet, check, export, format, import, merge, search::Query, source, textmate, timestamp,
+    trash, vim, vscode, yasnippet,
+};
+use cli::{Cli, Command, ExportFormat, Keep, TrashAction};
 
 fn main() {
-    let file_path = get_data_path();
-    let snippets_file = fs::read_to_string(&file_path).unwrap_or("[]".to_string());
-    let snippets: Vec<app::SaveSnippet> =
-        serde_json::from_str(&snippets_file).unwrap_or_else(|_| Vec::new());
+    let cli = Cli::parse();
+    let config = config::Config::load();
 
-    // initialize app
-    let mut codecache = app::CodeCache::new(snippets);
+    let file_path = match &config.library_dir {
+        Some(dir) => {
+            // a directory library is meant to be read in diffs, it is never sealed as a whole
+            if config.encrypt_store {
+                eprintln!(
+                    "encrypt_store does not work with library_dir, mark snippets as secret instead"
+                );
+                process::exit(1);
+            }
+            let dir = config::expand_home(dir);
+            if let Err(err) = fs::create_dir_all(&dir) {
+                eprintln!("{}: {err}", dir.display());
+                process::exit(1);
+            }
+            dir
+        }
+        None => config::data_path("snippets.json"),
+    };
 
-    let snippets = codecache.run();
+    match cli.command {
+        None => {
+            let mut keyring = app::Keyring::new();
+            let snippets = store::load(&file_path, &config, &mut keyring);
+            let snippets = store::with_layers(snippets, &config);
 
-    // save back to file
-    let snippets_str =
-        serde_json::to_string_pretty(&snippets).expect("failed to save snippets to file");
-    fs::write(&file_path, snippets_str).expect("failed to save snippets to file");
-}
+            // initialize app
+            let mut codecache = app::CodeCache::new(snippets, &config, keyring);
+            if file_path.is_dir() {
+                codecache.watch_dir(&file_path);
+            }
+
+            let snippets = codecache.run();
 
-fn get_data_path() -> PathBuf {
-    let mut path = dirs::data_local_dir().unwrap_or_else(|| {
-        #[cfg(target_os = "windows")]
-        return PathBuf::from(std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string()));
-        #[cfg(not(target_os = "windows"))]
-        return PathBuf::from("~/.local/share");
-    });
+            // save back to file
+            if let Err(err) = store::save(&file_path, &snippets, &config, codecache.keyring()) {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+            if let Err(err) = trash::take(&codecache.restored()) {
+                eprintln!("{err}");
+            }
+        }
+        Some(Command::Add {
+            target,
+            title,
+            desc,
+            lang,
+            tags,
+        }) => {
+            let snippet = match target.as_deref() {
+                None | Some("-") => {
+                    if io::stdin().is_terminal() {
+                        eprintln!("nothing to add, pipe code in or name a file like path:40-72");
+                        process::exit(1);
+                    }
+                    let mut text = String::new();
+                    if let Err(err) = io::stdin().read_to_string(&mut text) {
+                        eprintln!("failed to read stdin: {err}");
+                        process::exit(1);
+                    }
+                    source::from_text(&text)
+                }
+                Some(target) => source::from_file(target),
+            };
+            let mut snippet = snippet.unwrap_or_else(|err| {
+                eprintln!("{err}");
+                process::exit(1);
+            });
+            snippet.title = title.unwrap_or(snippet.title);
+            snippet.desc = desc.unwrap_or_default();
+            snippet.lang = lang.unwrap_or(snippet.lang);
+            snippet.tags = app::parse_tags(&tags.unwrap_or_default());
 
-    path.push("codecache");
-    fs::create_dir_all(&path).expect("failed to create data directory");
+            let mut keyring = app::Keyring::new();
+            let mut snippets = store::load(&file_path, &config, &mut keyring);
+            let added = format!("added {} [{}]", snippet.title, snippet.lang);
+            snippets.push(snippet);
+            if let Err(err) = store::save(&file_path, &snippets, &config, &mut keyring) {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+            println!("{added}");
+        }
+        Some(Command::Import { paths, all }) => {
+            let mut candidates = import::scan(&paths).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                process::exit(1);
+            });
+            if candidates.is_empty() {
+                println!("no code blocks found");
+                return;
+            }
+            let mut keyring = app::Keyring::new();
+            let mut snippets = store::load(&file_path, &config, &mut keyring);
+            if all {
+                import::mark_duplicates(&mut candidates, &snippets);
+                let before = snippets.len();
+                snippets.extend(
+                    candidates
+                        .into_iter()
+                        .filter(|c| c.selected)
+                        .map(|c| c.snippet),
+                );
+                println!("imported {} snippet(s)", snippets.len() - before);
+                if let Err(err) = store::save(&file_path, &snippets, &config, &mut keyring) {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+                return;
+            }
+            let mut codecache = app::CodeCache::new(snippets, &config, keyring);
+            codecache.open_import(candidates);
+            let snippets = codecache.run();
+            if let Err(err) = store::save(&file_path, &snippets, &config, codecache.keyring()) {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+            if let Err(err) = trash::take(&codecache.restored()) {
+                eprintln!("{err}");
+            }
+        }
+        Some(Command::Export {
+            format,
+            output,
+            tag,
+            search,
+            by_tag,
+        }) => {
+            let mut keyring = app::Keyring::new();
+            let snippets = store::load(&file_path, &config, &mut keyring);
+            let snippets = store::with_layers(snippets, &config);
+            let query = tag.iter().fold(
+                Query::parse(search.as_deref().unwrap_or_default()),
+                |query, tag| query.with_tag(tag),
+            );
+            // secret snippets never leave the library in cleartext
+            let secret = snippets.iter().filter(|s| s.secret).count();
+            let selected: Vec<&app::SaveSnippet> = snippets
+                .iter()
+                .filter(|s| !s.secret && query.matches(s))
+                .collect();
+            if secret > 0 {
+                eprintln!("skipped {secret} secret snippet(s)");
+            }
+            let mut highlighter = app::Highlighter::new();
+            if let Err(err) = highlighter.set_theme(&config.theme) {
+                eprintln!("{err}");
+            }
+
+            let text = match format {
+                ExportFormat::Markdown => export::markdown(&selected, by_tag, &highlighter),
+                ExportFormat::Html => export::html(&selected, by_tag, &highlighter),
+                ExportFormat::Pet => cheatsheet::export_pet(&selected),
+                ExportFormat::Navi => cheatsheet::export_navi(&selected),
+                ExportFormat::Site => {
+                    let dir = output.unwrap_or_else(|| "codecache-site".into());
+                    match export::site(&selected, &dir, &highlighter) {
+                        Ok(count) => println!(
+                            "exported {count} snippet(s) to {}",
+                            dir.join("index.html").display()
+                        ),
+                        Err(err) => {
+                            eprintln!("{err}");
+                            process::exit(1);
+                        }
+                    }
+                    return;
+                }
+                ExportFormat::Vscode => {
+                    export_files(output, "vscode-snippets", vscode::export(&selected));
+                    return;
+                }
+                ExportFormat::Sublime => {
+                    let files = textmate::export_sublime(&selected, &highlighter);
+                    export_files(output, "sublime-snippets", files);
+                    return;
+                }
+                ExportFormat::Textmate => {
+                    let files = textmate::export_tm(&selected, &highlighter);
+                    export_files(output, "textmate-snippets", files);
+                    return;
+                }
+                ExportFormat::Ultisnips => {
+                    let files = vim::export_ultisnips(&selected);
+                    export_files(output, "ultisnips-snippets", files);
+                    return;
+                }
+                ExportFormat::Snipmate => {
+                    let files = vim::export_snipmate(&selected);
+                    export_files(output, "snipmate-snippets", files);
+                    return;
+                }
+                ExportFormat::Yasnippet => {
+                    export_files(output, "yasnippet-snippets", yasnippet::export(&selected));
+                    return;
+                }
+            };
+            match output {
+                Some(path) => {
+                    if let Err(err) = fs::write(&path, text) {
+                        eprintln!("{}: {err}", path.display());
+                        process::exit(1);
+                    }
+                    eprintln!(
+                        "exported {} snippet(s) to {}",
+                        selected.len(),
+                        path.display()
+                    );
+                }
+                None => print!("{text}"),
+            }
+        }
+        Some(Command::Pick {
+            query,
+            lang,
+            tag,
+            raw,
+            print0,
+            json,
+            list,
+            select,
+        }) => {
+            let mut keyring = app::Keyring::new();
+            let snippets = store::load(&file_path, &config, &mut keyring);
+            let snippets = store::with_layers(snippets, &config);
+            let query = query.unwrap_or_default();
+            let end = if print0 { '\0' } else { '\n' };
+
+            if list || select.is_some() {
+                // launchers can't ask for a passphrase, so secrets are left out
+                let filter = lang.iter().fold(
+                    tag.iter()
+                        .fold(Query::parse(&query), |query, tag| query.with_tag(tag)),
+                    |query, lang| query.with_lang(lang),
+                );
+                let matching = snippets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| !s.secret && filter.matches(s));
+                let Some(line) = select else {
+                    // stop quietly when the launcher closes the pipe
+                    let mut stdout = io::stdout().lock();
+                    for (i, snippet) in matching {
+                        if write!(stdout, "{}{end}", pick::list_line(i, snippet)).is_err() {
+                            break;
+                        }
+                    }
+                    return;
+                };
+                let line = if line == "-" {
+                    let mut line = String::new();
+                    let _ = io::stdin().read_line(&mut line);
+                    line
+                } else {
+                    line
+                };
+                let Some(snippet) = pick::from_list_line(&line, &snippets) else {
+                    eprintln!("no snippet for {:?}", line.trim_end());
+                    process::exit(1);
+                };
+                let code = if raw {
+                    snippet.code.clone()
+                } else {
+                    app::fill(&app::template_code(snippet), &[])
+                };
+                pick::print(snippet, &code, json, end);
+                return;
+            }
+
+            // the picker starts with the filters in its search box
+            let mut search = vec![query];
+            search.extend(lang.iter().map(|lang| format!("lang:{lang}")));
+            search.extend(tag.iter().map(|tag| format!("#{tag}")));
+            let search = search.join(" ").trim().to_string() + " ";
+            let mut codecache = app::CodeCache::new(snippets, &config, keyring);
+            match codecache.pick(search.trim_start(), raw) {
+                Ok(Some((snippet, code))) => pick::print(&snippet, &code, json, end),
+                // cancelled, like fzf
+                Ok(None) => process::exit(130),
+                Err(err) => {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+            }
+        }
+        Some(Command::Init { shell }) => print!("{}", shell::init(shell)),
+        Some(Command::Check { lang, tag, search }) => {
+            let mut keyring = app::Keyring::new();
+            let mut snippets = store::load(&file_path, &config, &mut keyring);
+            let checks = check::checks(&config.checks);
+            let filter = lang.iter().fold(
+                tag.iter()
+                    .fold(Query::parse(&search.unwrap_or_default()), |query, tag| {
+                        query.with_tag(tag)
+                    }),
+                |query, lang| query.with_lang(lang),
+            );
+            let (mut passed, mut failed, mut unchecked) = (0, 0, 0);
+            // secrets are skipped, their diagnostics would be stored in cleartext
+            for snippet in snippets
+                .iter_mut()
+                .filter(|s| !s.secret && filter.matches(s))
+            {
+                let result = match check::check(snippet, &checks) {
+                    Some(Ok(result)) => result,
+                    Some(Err(err)) => {
+                        eprintln!("skip {}: {err}", snippet.title);
+                        unchecked += 1;
+                        continue;
+                    }
+                    None => {
+                        unchecked += 1;
+                        continue;
+                    }
+                };
+                println!("{}", check::report(snippet, &result));
+                if result.passed {
+                    passed += 1;
+                } else {
+                    failed += 1;
+                }
+                snippet.check = Some(result);
+            }
+            if passed + failed > 0
+                && let Err(err) = store::save(&file_path, &snippets, &config, &mut keyring)
+            {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+            println!("{passed} passed, {failed} failed, {unchecked} not checked");
+            if failed > 0 {
+                process::exit(1);
+            }
+        }
+        Some(Command::Format {
+            lang,
+            tag,
+            search,
+            yes,
+            check,
+        }) => {
+            let mut keyring = app::Keyring::new();
+            let mut snippets = store::load(&file_path, &config, &mut keyring);
+            let formatters = format::formatters(&config.formatters);
+            let filter = lang.iter().fold(
+                tag.iter()
+                    .fold(Query::parse(&search.unwrap_or_default()), |query, tag| {
+                        query.with_tag(tag)
+                    }),
+                |query, lang| query.with_lang(lang),
+            );
+            let mut changes = Vec::new();
+            for (idx, snippet) in snippets.iter().enumerate() {
+                if snippet.is_locked() || !filter.matches(snippet) {
+                    continue;
+                }
+                match format::format_code(&snippet.code, &snippet.lang, &formatters) {
+                    Some(Ok(code)) if code != snippet.code => {
+                        print!("{}", format::unified_diff(snippet, &code));
+                        changes.push((idx, code));
+                    }
+                    Some(Err(err)) => eprintln!("skip {}: {err}", snippet.title),
+                    _ => {}
+                }
+            }
+            if changes.is_empty() {
+                println!("everything is formatted");
+                return;
+            }
+            if check {
+                process::exit(1);
+            }
+            if !yes {
+                if !io::stdin().is_terminal() {
+                    eprintln!("pass --yes to apply {} change(s)", changes.len());
+                    process::exit(1);
+                }
+                eprint!("apply {} change(s)? [y/N] ", changes.len());
+                let mut answer = String::new();
+                let _ = io::stdin().read_line(&mut answer);
+                if !answer.trim().eq_ignore_ascii_case("y") {
+                    return;
+                }
+            }
+            let count = changes.len();
+            for (idx, code) in changes {
+                snippets[idx] = format::apply(&snippets[idx], code);
+            }
+            if let Err(err) = store::save(&file_path, &snippets, &config, &mut keyring) {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+            println!("formatted {count} snippet(s)");
+        }
+        Some(Command::Sources { refresh }) => {
+            let mut keyring = app::Keyring::new();
+            let mut snippets = store::load(&file_path, &config, &mut keyring);
+            let mut refreshed = 0;
+            for snippet in snippets.iter_mut().filter(|s| !s.is_locked()) {
+                let Some(source) = &snippet.source else {
+                    continue;
+                };
+                let status = source.status(&snippet.code);
+                println!(
+                    "{}  {} ({})",
+                    snippet.title,
+                    source.describe(),
+                    status.label()
+                );
+                if refresh && !matches!(status, source::Status::Fresh | source::Status::Missing) {
+                    match source::refreshed(snippet) {
+                        Ok(updated) => {
+                            *snippet = updated;
+                            refreshed += 1;
+                        }
+                        Err(err) => eprintln!("  {err}"),
+                    }
+                }
+            }
+            if refreshed > 0 {
+                if let Err(err) = store::save(&file_path, &snippets, &config, &mut keyring) {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+                println!("refreshed {refreshed} snippet(s)");
+            }
+        }
+        Some(Command::Sync) => {
+            let mut keyring = app::Keyring::new();
+            // unlocks an encrypted library before both sides are read
+            store::load(&file_path, &config, &mut keyring);
+            match git::sync(&file_path, &config, &mut keyring) {
+                Ok(report) => println!("{report}"),
+                Err(err) => {
+                    eprintln!("sync failed: {err}");
+                    process::exit(1);
+                }
+            }
+        }
+        Some(Command::Merge {
+            base,
+            ours,
+            theirs,
+            output,
+            keep,
+        }) => {
+            let resolution = keep.map(|keep| match keep {
+                Keep::Ours => merge::Resolution::Ours,
+                Keep::Theirs => merge::Resolution::Theirs,
+                Keep::Both => merge::Resolution::Both,
+            });
+            let mut keyring = app::Keyring::new();
+            let paths = [base.as_path(), ours.as_path(), theirs.as_path()];
+            match git::merge_files(paths, output.as_deref(), resolution, &config, &mut keyring) {
+                Ok(0) => {}
+                Ok(conflicts) => {
+                    // a failing driver tells git the file still needs resolving
+                    eprintln!(
+                        "{conflicts} snippet(s) were changed on both sides, open codecache and press m to resolve them"
+                    );
+                    process::exit(1);
+                }
+                Err(err) => {
+                    eprintln!("merge failed: {err}");
+                    process::exit(2);
+                }
+            }
+        }
+        Some(Command::Watch { interval }) => {
+            // an encrypted library would need a passphrase, so only dedup against the inbox then
+            let library: Vec<String> = store::load_unencrypted(&file_path)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| s.code)
+                .collect();
+            app::watch(&config, &library, Duration::from_secs(interval.max(1)));
+        }
+        Some(Command::Trash { action }) => match action {
+            TrashAction::List => {
+                let entries = trash::load();
+                if entries.is_empty() {
+                    println!("trash is empty");
+                }
+                for (number, entry) in entries.iter().enumerate() {
+                    println!(
+                        "{:>3}  {} [{}] deleted {}",
+                        number + 1,
+                        entry.snippet.title,
+                        entry.snippet.lang,
+                        timestamp::ago(entry.deleted_at)
+                    );
+                }
+            }
+            TrashAction::Restore { number } => {
+                let mut entries = trash::load();
+                if number == 0 || number > entries.len() {
+                    eprintln!("no snippet with number {number} in the trash");
+                    process::exit(1);
+                }
+                let mut keyring = app::Keyring::new();
+                let mut snippets = store::load(&file_path, &config, &mut keyring);
+                let entry = entries.remove(number - 1);
+                let restored = format!("restored {}", entry.snippet.title);
+                snippets.push(entry.snippet);
+                if let Err(err) = store::save(&file_path, &snippets, &config, &mut keyring) {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+                if let Err(err) = trash::save(&entries) {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+                println!("{restored}");
+            }
+            TrashAction::Purge { days } => match trash::purge(days) {
+                Ok(purged) => println!("purged {purged} snippet(s)"),
+                Err(err) => {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+            },
+            TrashAction::Empty => match trash::purge(0) {
+                Ok(purged) => println!("purged {purged} snippet(s)"),
+                Err(err) => {
+                    eprintln!("{err}");
+                    process::exit(1);
+                }
+            },
+        },
+    }
+}
 
-    path.push("snippets.json");
-    path
+/// writes the files of an editor snippet export into a directory
+fn export_files(output: Option<PathBuf>, default: &str, files: BTreeMap<String, String>) {
+    let dir = output.unwrap_or_else(|| default.into());
+    if let Err(err) = export::write_files(&dir, &files) {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+    println!("exported {} file(s) to {}", files.len(), dir.display());
 }
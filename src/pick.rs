@@ -0,0 +1,42 @@
+use std::io::{self, Write};
+
+use serde_json::json;
+
+use crate::app::SaveSnippet;
+
+/// a line of `codecache pick --list`, starting with the number of the snippet
+pub fn list_line(idx: usize, snippet: &SaveSnippet) -> String {
+    let mut line = format!("{}: {} ({})", idx + 1, snippet.title, snippet.lang);
+    if !snippet.tags.is_empty() {
+        line.push_str(&format!(" #{}", snippet.tags.join(" #")));
+    }
+    // launchers show one line per entry
+    line.replace(['\n', '\0'], " ")
+}
+
+/// the snippet a line of `codecache pick --list` stands for
+pub fn from_list_line<'a>(line: &str, snippets: &'a [SaveSnippet]) -> Option<&'a SaveSnippet> {
+    let (number, _) = line.trim().split_once(':')?;
+    let idx = number.trim().parse::<usize>().ok()?.checked_sub(1)?;
+    snippets.get(idx).filter(|snippet| !snippet.secret)
+}
+
+/// prints the picked code, or the whole snippet as JSON
+pub fn print(snippet: &SaveSnippet, code: &str, json: bool, end: char) {
+    let text = if json {
+        json!({
+            "title": snippet.title,
+            "desc": snippet.desc,
+            "lang": snippet.lang,
+            "tags": snippet.tags,
+            "trigger": snippet.trigger,
+            "code": code,
+        })
+        .to_string()
+    } else {
+        code.to_string()
+    };
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "{text}{end}");
+    let _ = stdout.flush();
+}
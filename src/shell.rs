@@ -0,0 +1,97 @@
+use crate::cli::Shell;
+
+/// `Ctrl+G` inserts a picked snippet at the cursor, `Alt+S` saves the last command
+const BASH: &str = r#"# codecache widgets, add `eval "$(codecache init bash)"` to ~/.bashrc
+
+__codecache_pick() {
+    local snippet
+    snippet="$(command codecache pick)" || return
+    READLINE_LINE="${READLINE_LINE:0:READLINE_POINT}${snippet}${READLINE_LINE:READLINE_POINT}"
+    READLINE_POINT=$((READLINE_POINT + ${#snippet}))
+}
+
+__codecache_save_last() {
+    local cmd title
+    cmd="$(HISTTIMEFORMAT= builtin history 1 | sed '1s/^ *[0-9]*\*\{0,1\} *//')"
+    [ -n "$cmd" ] || return
+    printf 'save: %s\n' "$cmd" >&2
+    read -r -e -p 'title: ' title </dev/tty || return
+    if [ -n "$title" ]; then
+        printf '%s\n' "$cmd" | command codecache add --lang sh --title "$title"
+    else
+        printf '%s\n' "$cmd" | command codecache add --lang sh
+    fi
+}
+
+bind -x '"\C-g": __codecache_pick'
+bind -x '"\es": __codecache_save_last'
+"#;
+
+const ZSH: &str = r#"# codecache widgets, add `eval "$(codecache init zsh)"` to ~/.zshrc
+
+zmodload -F zsh/parameter p:history
+autoload -Uz read-from-minibuffer
+
+codecache-pick-widget() {
+    local snippet
+    snippet="$(command codecache pick)"
+    local ret=$?
+    [[ $ret -eq 0 ]] && LBUFFER+="$snippet"
+    zle reset-prompt
+    return $ret
+}
+
+codecache-save-last-widget() {
+    local cmd="${history[$((HISTCMD - 1))]}"
+    [[ -n $cmd ]] || return
+    read-from-minibuffer 'title: ' || return
+    local -a args=(--lang sh)
+    [[ -n $REPLY ]] && args+=(--title "$REPLY")
+    zle -M "$(print -r -- "$cmd" | command codecache add "${args[@]}" 2>&1)"
+}
+
+zle -N codecache-pick-widget
+zle -N codecache-save-last-widget
+bindkey '^G' codecache-pick-widget
+bindkey '^[s' codecache-save-last-widget
+"#;
+
+const FISH: &str = r#"# codecache widgets, add `codecache init fish | source` to ~/.config/fish/config.fish
+
+function __codecache_pick
+    set -l snippet (command codecache pick | string collect)
+    and commandline -i -- $snippet
+    commandline -f repaint
+end
+
+function __codecache_save_last
+    set -l cmd $history[1]
+    test -n "$cmd"; or return
+    echo >&2
+    echo "save: $cmd" >&2
+    read -l -P 'title: ' title </dev/tty; or begin
+        commandline -f repaint
+        return
+    end
+    set -l args --lang sh
+    test -n "$title"; and set -a args --title $title
+    printf '%s\n' $cmd | command codecache add $args
+    commandline -f repaint
+end
+
+bind \cg __codecache_pick
+bind \es __codecache_save_last
+if bind -M insert >/dev/null 2>&1
+    bind -M insert \cg __codecache_pick
+    bind -M insert \es __codecache_save_last
+end
+"#;
+
+/// the widgets printed by `codecache init`
+pub fn init(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    }
+}
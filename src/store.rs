@@ -0,0 +1,365 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    app::{Keyring, SaveSnippet, dirstore, ensure_ids, forget_code, prompt_passphrase},
+    config::{self, Config},
+    git,
+};
+
+/// on-disk format of a library encrypted as a whole
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    codecache_encrypted: String,
+}
+
+/// reads the snippets, asking for the passphrase if the file is encrypted
+pub fn load(path: &Path, config: &Config, keyring: &mut Keyring) -> Vec<SaveSnippet> {
+    if path.is_dir() {
+        return dirstore::load(path);
+    }
+    let snippets_file = fs::read_to_string(path).unwrap_or("[]".to_string());
+
+    let mut snippets = match serde_json::from_str::<Envelope>(&snippets_file) {
+        Ok(envelope) => decrypt(&envelope, keyring),
+        Err(_) => serde_json::from_str(&snippets_file).unwrap_or_else(|_| Vec::new()),
+    };
+    ensure_ids(&mut snippets);
+
+    if config.encrypt_store && !keyring.is_unlocked() {
+        set_passphrase(keyring);
+    }
+    snippets
+}
+
+/// reads the snippets only if that needs no passphrase, for background commands
+pub fn load_unencrypted(path: &Path) -> Option<Vec<SaveSnippet>> {
+    if path.is_dir() {
+        return Some(dirstore::load(path));
+    }
+    let snippets_file = fs::read_to_string(path).unwrap_or("[]".to_string());
+    if serde_json::from_str::<Envelope>(&snippets_file).is_ok() {
+        return None;
+    }
+    let mut snippets = serde_json::from_str(&snippets_file).unwrap_or_else(|_| Vec::new());
+    ensure_ids(&mut snippets);
+    Some(snippets)
+}
+
+/// the personal library with the team libraries of the config, ordered by priority.
+/// a snippet in more than one library is shown once, the personal one or a fork of it
+/// always wins and otherwise the library with the highest priority.
+pub fn with_layers(personal: Vec<SaveSnippet>, config: &Config) -> Vec<SaveSnippet> {
+    let mut layers: Vec<(i32, Vec<SaveSnippet>)> = vec![(0, personal)];
+    for library in &config.libraries {
+        let path = config::expand_home(&library.path);
+        if !path.exists() {
+            eprintln!(
+                "library {}: {} does not exist",
+                library.name,
+                path.display()
+            );
+            continue;
+        }
+        let Some(mut snippets) = load_unencrypted(&path) else {
+            eprintln!(
+                "library {}: encrypted libraries can't be shared",
+                library.name
+            );
+            continue;
+        };
+        for snippet in &mut snippets {
+            snippet.library = Some(library.name.clone());
+        }
+        layers.push((library.priority, snippets));
+    }
+    // stable, so the personal library comes first among equal priorities
+    layers.sort_by_key(|(priority, _)| Reverse(*priority));
+
+    let mut seen: HashSet<String> = layers
+        .iter()
+        .flat_map(|(_, snippets)| snippets)
+        .filter(|snippet| snippet.library.is_none())
+        .map(|snippet| snippet.id.clone())
+        .collect();
+    let mut snippets = Vec::new();
+    for (_, layer) in layers {
+        for snippet in layer {
+            if snippet.library.is_none() || seen.insert(snippet.id.clone()) {
+                snippets.push(snippet);
+            }
+        }
+    }
+    snippets
+}
+
+/// whether a library file is encrypted as a whole
+pub fn is_encrypted(text: &str) -> bool {
+    serde_json::from_str::<Envelope>(text).is_ok()
+}
+
+/// reads a library from text without asking for anything, an encrypted one needs an unlocked keyring
+pub fn parse(text: &str, keyring: &mut Keyring) -> Result<Vec<SaveSnippet>, String> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut snippets: Vec<SaveSnippet> = match serde_json::from_str::<Envelope>(text) {
+        Ok(envelope) if keyring.is_unlocked() => {
+            let snippets_str = keyring.open(&envelope.codecache_encrypted)?;
+            serde_json::from_str(&snippets_str)
+        }
+        Ok(_) => return Err("the library is encrypted".to_string()),
+        Err(_) => serde_json::from_str(text),
+    }
+    .map_err(|err| format!("invalid snippet library: {err}"))?;
+    ensure_ids(&mut snippets);
+    Ok(snippets)
+}
+
+/// writes the snippets, committing them if the library is kept in git
+pub fn save(
+    path: &Path,
+    snippets: &[SaveSnippet],
+    config: &Config,
+    keyring: &mut Keyring,
+) -> Result<(), String> {
+    if !config.git {
+        return write(path, snippets, config, keyring);
+    }
+    let old = if path.is_dir() {
+        dirstore::load(path)
+    } else {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| parse(&text, keyring).ok())
+            .unwrap_or_default()
+    };
+    write(path, snippets, config, keyring)?;
+    // titles stay out of the log of an encrypted library
+    let message = if config.encrypt_store {
+        "edit: encrypted library".to_string()
+    } else {
+        git::describe_changes(&old, snippets)
+    };
+    if let Err(err) = git::commit(path, &config.git_branch, &message) {
+        eprintln!("failed to commit the library: {err}");
+    }
+    Ok(())
+}
+
+/// writes the snippets, sealed if the library is encrypted. a library directory that
+/// can't be written is saved to a json file next to it, so the edits are not lost.
+pub fn write(
+    path: &Path,
+    snippets: &[SaveSnippet],
+    config: &Config,
+    keyring: &mut Keyring,
+) -> Result<(), String> {
+    // earlier code of secret snippets never reaches the disk, whatever brought it back
+    let snippets: &[SaveSnippet] = &snippets
+        .iter()
+        .cloned()
+        .map(|mut snippet| {
+            if snippet.secret {
+                forget_code(&mut snippet);
+            }
+            snippet
+        })
+        .collect::<Vec<_>>();
+    if !path.is_dir() {
+        return write_file(path, snippets, config, keyring);
+    }
+    let Err(err) = dirstore::save(path, snippets) else {
+        return Ok(());
+    };
+    let backup = backup_path(path);
+    match write_file(&backup, snippets, config, keyring) {
+        Ok(()) => Err(format!(
+            "failed to save the library: {err}, it was saved to {} instead",
+            backup.display()
+        )),
+        Err(backup_err) => Err(format!(
+            "failed to save the library: {err}, and the backup failed too: {backup_err}"
+        )),
+    }
+}
+
+/// `<dir>-backup.json` next to a library directory
+fn backup_path(dir: &Path) -> PathBuf {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "snippets".to_string());
+    dir.with_file_name(format!("{name}-backup.json"))
+}
+
+fn write_file(
+    path: &Path,
+    snippets: &[SaveSnippet],
+    config: &Config,
+    keyring: &mut Keyring,
+) -> Result<(), String> {
+    let snippets_str =
+        serde_json::to_string_pretty(snippets).expect("failed to save snippets to file");
+
+    // an encrypted library is never written in cleartext, not even when the passphrase was skipped
+    if config.encrypt_store && !keyring.is_unlocked() {
+        set_passphrase(keyring);
+        if !keyring.is_unlocked() {
+            return Err(format!(
+                "{} was not saved, an encrypted library needs a passphrase",
+                path.display()
+            ));
+        }
+    }
+    let file_str = if config.encrypt_store {
+        let envelope = Envelope {
+            codecache_encrypted: keyring.seal(&Zeroizing::new(snippets_str))?,
+        };
+        serde_json::to_string_pretty(&envelope).expect("failed to save snippets to file")
+    } else {
+        snippets_str
+    };
+    fs::write(path, file_str).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn decrypt(envelope: &Envelope, keyring: &mut Keyring) -> Vec<SaveSnippet> {
+    for _ in 0..3 {
+        let Some(passphrase) = prompt_passphrase("Passphrase for your snippet library") else {
+            break;
+        };
+        keyring.unlock(passphrase);
+        match keyring.open(&envelope.codecache_encrypted) {
+            Ok(snippets_str) => {
+                return serde_json::from_str(&snippets_str).unwrap_or_else(|_| Vec::new());
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+    keyring.lock();
+    eprintln!("could not unlock the snippet library");
+    process::exit(1);
+}
+
+/// asks for a new passphrase twice before the library gets encrypted for the first time
+fn set_passphrase(keyring: &mut Keyring) {
+    loop {
+        let Some(passphrase) = prompt_passphrase("New passphrase to encrypt your snippet library")
+        else {
+            eprintln!(
+                "no passphrase entered, it will be asked for again when the library is saved"
+            );
+            return;
+        };
+        let Some(repeated) = prompt_passphrase("Repeat the passphrase") else {
+            continue;
+        };
+        if passphrase == repeated {
+            keyring.unlock(passphrase);
+            return;
+        }
+        eprintln!("passphrases do not match");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwritable_directory_falls_back_to_a_backup() {
+        let root = std::env::temp_dir().join(format!("codecache-store-{}", process::id()));
+        let dir = root.join("library");
+        fs::create_dir_all(&dir).unwrap();
+        // a file name longer than any file system allows can't be written
+        let mut snippet = SaveSnippet::new("echo hello", "sh");
+        snippet.title = "hello".repeat(60);
+        let snippets = vec![snippet];
+
+        let err = write(&dir, &snippets, &Config::default(), &mut Keyring::new()).unwrap_err();
+        let backup = root.join("library-backup.json");
+        assert!(err.contains(&backup.display().to_string()));
+        let saved: Vec<SaveSnippet> =
+            serde_json::from_str(&fs::read_to_string(&backup).unwrap()).unwrap();
+        assert_eq!(saved, snippets);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn encrypted_library_is_sealed() {
+        let path = std::env::temp_dir().join(format!("codecache-sealed-{}.json", process::id()));
+        let config = Config {
+            encrypt_store: true,
+            ..Config::default()
+        };
+        let mut keyring = Keyring::new();
+        keyring.unlock(Zeroizing::new("correct horse".to_string()));
+        let snippets = vec![SaveSnippet::new("echo hunter2", "sh")];
+
+        write(&path, &snippets, &config, &mut keyring).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("hunter2"));
+        assert_eq!(parse(&text, &mut keyring).unwrap(), snippets);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn layers_are_ordered_by_priority_and_shown_once() {
+        let root = std::env::temp_dir().join(format!("codecache-layers-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let snippet = |id: &str, title: &str| {
+            let mut snippet = SaveSnippet::new("echo", "sh");
+            snippet.id = id.to_string();
+            snippet.title = title.to_string();
+            snippet
+        };
+        let library = |name: &str, priority: i32, snippets: &[SaveSnippet]| {
+            let path = root.join(format!("{name}.json"));
+            fs::write(&path, serde_json::to_string(snippets).unwrap()).unwrap();
+            crate::config::Library {
+                name: name.to_string(),
+                path,
+                priority,
+            }
+        };
+        let mut fork = snippet("b", "fork of b");
+        fork.forked_from = Some("ops".to_string());
+        let personal = vec![snippet("a", "mine"), fork];
+        let config = Config {
+            libraries: vec![
+                library(
+                    "ops",
+                    5,
+                    &[
+                        snippet("b", "ops b"),
+                        snippet("c", "ops c"),
+                        snippet("e", "ops e"),
+                    ],
+                ),
+                library(
+                    "infra",
+                    10,
+                    &[snippet("c", "infra c"), snippet("a", "infra a")],
+                ),
+                library("attic", i32::MIN, &[snippet("d", "attic d")]),
+            ],
+            ..Config::default()
+        };
+
+        let titles: Vec<String> = with_layers(personal, &config)
+            .into_iter()
+            .map(|snippet| snippet.title)
+            .collect();
+        // personal snippets and forks always win, otherwise the highest priority does
+        assert_eq!(titles, ["infra c", "ops e", "mine", "fork of b", "attic d"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}